@@ -0,0 +1,26 @@
+//! Textures shared by the benchmarks
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+/// A noisy gradient, roughly like a block texture, or an item texture with
+/// some transparent pixels if `transparent`
+pub fn texture(size: u32, transparent: bool) -> DynamicImage {
+    let mut rng = StdRng::seed_from_u64(u64::from(size));
+    let scale = 255. / size as f32;
+    RgbaImage::from_fn(size, size, |x, y| {
+        let noise: f32 = rng.random_range(-24.0..24.0);
+        let alpha = if transparent && rng.random_ratio(1, 16) {
+            0
+        } else {
+            255
+        };
+        Rgba([
+            (x as f32 * scale + noise).clamp(0., 255.) as u8,
+            (y as f32 * scale + noise).clamp(0., 255.) as u8,
+            (96. + noise).clamp(0., 255.) as u8,
+            alpha,
+        ])
+    })
+    .into()
+}
@@ -0,0 +1,33 @@
+//! The Kuwahara filter at a few radii, which is the most expensive filter per
+//! pixel, on a 64x64 texture (a vanilla one after the "Oil Paint" pack's
+//! resize) and a 512x512 one.
+
+mod common;
+
+use std::hint::black_box;
+
+use common::texture;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use gen_rp_rs::filter::{Kuwahara, Transform};
+
+fn kuwahara(c: &mut Criterion) {
+    for size in [64, 512] {
+        let image = texture(size, false);
+        let mut group = c.benchmark_group(format!("kuwahara/{}x{}", size, size));
+        group.throughput(Throughput::Elements(u64::from(size * size)));
+        if size > 64 {
+            group.sample_size(10);
+        }
+        for radius in [1, 3, 8] {
+            group.bench_with_input(
+                BenchmarkId::from_parameter(radius),
+                &radius,
+                |b, &radius| b.iter(|| Kuwahara { radius }.apply(black_box(image.clone()))),
+            );
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, kuwahara);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+//! The parts of generating a pack that take the time: running each built-in
+//! pack's filter, re-encoding (and optimising) the PNGs and writing the zip.  Each is run on a
+//! 16x16 texture, like most vanilla ones, and a 512x512 one, like a high
+//! resolution pack's.
+
+mod common;
+
+use std::{hint::black_box, io::Cursor, path::Path};
+
+use common::texture;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use gen_rp_rs::{
+    builtin::PACKS,
+    optimize::OptLevel,
+    output::{MemorySink, OutputSink},
+};
+use image::{DynamicImage, ImageFormat};
+
+const SIZES: [u32; 2] = [16, 512];
+
+fn encode(image: &DynamicImage) -> Vec<u8> {
+    let mut data = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+        .expect("encoding PNG");
+    data
+}
+
+fn filters(c: &mut Criterion) {
+    for size in SIZES {
+        let image = texture(size, true);
+        let mut group = c.benchmark_group(format!("filter/{}x{}", size, size));
+        if size > 64 {
+            group.sample_size(10);
+        }
+        for pack in PACKS {
+            group.bench_function(pack.name, |b| {
+                b.iter(|| (pack.func)(black_box(image.clone())))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn png(c: &mut Criterion) {
+    let mut group = c.benchmark_group("png");
+    for size in SIZES {
+        let image = texture(size, true);
+        let data = encode(&image);
+        group.throughput(Throughput::Elements(u64::from(size * size)));
+        group.bench_with_input(BenchmarkId::new("encode", size), &image, |b, image| {
+            b.iter(|| encode(black_box(image)))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", size), &data, |b, data| {
+            b.iter(|| image::load_from_memory_with_format(black_box(data), ImageFormat::Png))
+        });
+        group.bench_with_input(BenchmarkId::new("optimize", size), &data, |b, data| {
+            b.iter(|| {
+                let mut data = data.clone();
+                OptLevel::Fast
+                    .optimize(black_box(&mut data))
+                    .expect("optimising PNG");
+                data
+            })
+        });
+    }
+    group.finish();
+}
+
+fn zip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zip");
+    // about as many files as a pack of all the block textures, and a handful
+    // of high resolution ones
+    for (size, files) in [(16, 1024), (512, 16)] {
+        let data = encode(&texture(size, true));
+        let paths: Vec<_> = (0..files)
+            .map(|i| format!("assets/minecraft/textures/block/texture_{}.png", i))
+            .collect();
+        group.throughput(Throughput::Bytes((data.len() * files) as u64));
+        group.bench_function(BenchmarkId::new("write", size), |b| {
+            b.iter(|| {
+                let mut sink: Box<dyn OutputSink> = Box::new(MemorySink::new(true));
+                for path in &paths {
+                    sink.add_file(Path::new(path), black_box(&data))
+                        .expect("adding file");
+                }
+                sink.finish().expect("finishing zip")
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, filters, png, zip);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+mod common;
+
+use std::hint::black_box;
+
+use common::texture;
+use criterion::{Criterion, criterion_group, criterion_main};
+use gen_rp_rs::quantize::{KMeans, MedianCut, Octree, Quantizer};
+
+fn quantizers(c: &mut Criterion) {
+    // a 64x64 block texture
+    let pixels: Vec<_> = texture(64, false).to_rgb8().pixels().copied().collect();
+    let mut group = c.benchmark_group("quantize");
+    for (name, quantizer) in [
+        ("k_means", &KMeans::default() as &dyn Quantizer),
+        ("median_cut", &MedianCut),
+        ("octree", &Octree),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| quantizer.palette(black_box(&pixels), black_box(16)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, quantizers);
+criterion_main!(benches);
@@ -0,0 +1,200 @@
+//! Statistics about textures, for tuning filters or as a dataset of their own
+
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use image::{ImageReader, Rgb};
+use rand::{SeedableRng, rngs::StdRng};
+use serde::{Serialize, Serializer};
+use walkdir::WalkDir;
+
+use crate::{
+    error::{GenRpError, IoContext, Result},
+    k_means::{DEFAULT_MIN_ALPHA, k_means_labelled, visible_pixels},
+};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TextureStats {
+    /// Relative to the root of the pack
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// Mean colour of the visible pixels
+    #[serde(serialize_with = "hex")]
+    pub average: Rgb<u8>,
+    /// Number of distinct colours among the visible pixels
+    pub unique_colours: usize,
+    /// The k-means clusters of the visible pixels, most common first
+    pub dominant: Vec<DominantColour>,
+    pub alpha: AlphaUsage,
+    /// Fraction of pixels that aren't fully opaque
+    pub transparent: f32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DominantColour {
+    #[serde(serialize_with = "hex")]
+    pub colour: Rgb<u8>,
+    /// Fraction of the visible pixels closest to this colour
+    pub share: f32,
+}
+
+/// How a texture uses its alpha channel, which decides whether it needs the
+/// cutout or translucent render type
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlphaUsage {
+    Opaque,
+    /// Only fully opaque and fully transparent pixels
+    Cutout,
+    Translucent,
+}
+
+fn hex<S: Serializer>(colour: &Rgb<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&to_hex(*colour))
+}
+
+fn to_hex(Rgb([r, g, b]): Rgb<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Gather [`TextureStats`] for every png in `textures_dir`, clustering each
+/// into `k` dominant colours
+pub fn analyze_textures(textures_dir: &Path, k: usize) -> Result<Vec<TextureStats>> {
+    let mut stats = Vec::new();
+    for entry in WalkDir::new(textures_dir).sort_by_file_name() {
+        let entry = entry?;
+        if entry.path().extension().is_none_or(|ext| ext != "png") {
+            continue;
+        }
+
+        let image = ImageReader::open(entry.path())
+            .io_context(|| format!("Opening {}", entry.path().display()))?
+            .decode()
+            .map_err(|source| GenRpError::Decode {
+                path: entry.path().to_path_buf(),
+                source,
+            })?
+            .into_rgba8();
+        let path = entry
+            .path()
+            .strip_prefix(textures_dir)
+            .expect("path is in textures_dir")
+            .to_path_buf();
+
+        let visible = visible_pixels(&image, DEFAULT_MIN_ALPHA);
+
+        let alpha = if image.pixels().all(|px| px[3] == 255) {
+            AlphaUsage::Opaque
+        } else if image.pixels().all(|px| px[3] == 0 || px[3] == 255) {
+            AlphaUsage::Cutout
+        } else {
+            AlphaUsage::Translucent
+        };
+        let transparent = image.pixels().filter(|px| px[3] < 255).count() as f32
+            / (image.width() * image.height()).max(1) as f32;
+
+        stats.push(TextureStats {
+            path,
+            width: image.width(),
+            height: image.height(),
+            average: average(&visible),
+            unique_colours: visible.iter().collect::<HashSet<_>>().len(),
+            dominant: dominant(&visible, k),
+            alpha,
+            transparent,
+        });
+    }
+
+    Ok(stats)
+}
+
+fn average(pixels: &[Rgb<u8>]) -> Rgb<u8> {
+    let mut sum = [0u64; 3];
+    for px in pixels {
+        for (sum, c) in sum.iter_mut().zip(px.0) {
+            *sum += c as u64;
+        }
+    }
+    let len = pixels.len().max(1) as u64;
+    Rgb(sum.map(|sum| (sum / len) as u8))
+}
+
+fn dominant(pixels: &[Rgb<u8>], k: usize) -> Vec<DominantColour> {
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+
+    // a fixed seed, so analyzing the same textures twice gives the same
+    // dominant colours
+    let clustering = k_means_labelled(k, pixels, &mut StdRng::seed_from_u64(0));
+    let counts = clustering.sizes();
+
+    let mut dominant: Vec<_> = clustering
+        .centroids
+        .into_iter()
+        .zip(counts)
+        .filter(|&(_, count)| count > 0)
+        .map(|(colour, count)| DominantColour {
+            colour,
+            share: count as f32 / pixels.len() as f32,
+        })
+        .collect();
+    dominant.sort_by(|a, b| b.share.total_cmp(&a.share));
+    dominant
+}
+
+pub fn write_json(stats: &[TextureStats], mut out: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(&mut out, stats)?;
+    out.flush().io_context(|| "Writing JSON report".into())
+}
+
+/// Quotes a CSV field if it contains a comma, quote or line break
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// One row per texture, with the dominant colours as `#rrggbb:share`
+/// separated by spaces
+pub fn write_csv(stats: &[TextureStats], mut out: impl Write) -> Result<()> {
+    let mut write = || -> std::io::Result<()> {
+        writeln!(
+            out,
+            "path,width,height,average,unique_colours,dominant,alpha,transparent"
+        )?;
+        for s in stats {
+            let dominant: Vec<_> = s
+                .dominant
+                .iter()
+                .map(|d| format!("{}:{:.3}", to_hex(d.colour), d.share))
+                .collect();
+            let alpha = match s.alpha {
+                AlphaUsage::Opaque => "opaque",
+                AlphaUsage::Cutout => "cutout",
+                AlphaUsage::Translucent => "translucent",
+            };
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{},{:.3}",
+                csv_field(&s.path.to_string_lossy()),
+                s.width,
+                s.height,
+                to_hex(s.average),
+                s.unique_colours,
+                dominant.join(" "),
+                alpha,
+                s.transparent
+            )?;
+        }
+        out.flush()
+    };
+    write().io_context(|| "Writing CSV report".into())
+}
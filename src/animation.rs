@@ -0,0 +1,137 @@
+//! Animated textures are a grid (usually a vertical strip) of frames,
+//! described by a `<texture>.png.mcmeta` next to the texture.  Transforms are
+//! run on each frame separately so that things like rotating or averaging
+//! don't mix frames together.
+// https://minecraft.wiki/w/Resource_pack#Animation
+
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, GenericImageView, RgbaImage, imageops};
+use serde::Deserialize;
+
+use crate::error::{IoContext, Result};
+
+#[derive(Clone, Debug, Deserialize)]
+struct McMeta {
+    animation: Option<Animation>,
+}
+
+/// The parts of the `animation` section of a `.mcmeta` we care about
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct Animation {
+    /// Width of each frame in pixels
+    pub width: Option<u32>,
+    /// Height of each frame in pixels
+    pub height: Option<u32>,
+}
+
+impl Animation {
+    /// Read the animation for the texture at `png`, if it has one
+    pub fn load(png: &Path) -> Result<Option<Self>> {
+        let path = mcmeta_path(png);
+        let s = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).io_context(|| format!("Reading {}", path.display())),
+        };
+
+        let mcmeta: McMeta = serde_json::from_str(&s)?;
+        Ok(mcmeta.animation)
+    }
+
+    /// Size of each frame of a `width`x`height` texture, the same way the game
+    /// works it out: squares the size of the shorter side unless the mcmeta
+    /// says otherwise
+    pub fn frame_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match (self.width, self.height) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, height),
+            (None, Some(h)) => (width, h),
+            (None, None) => (width.min(height), width.min(height)),
+        }
+    }
+
+    /// Whether a `width`x`height` texture divides evenly into frames
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        let (frame_width, frame_height) = self.frame_size(width, height);
+        frame_width != 0
+            && frame_height != 0
+            && width.is_multiple_of(frame_width)
+            && height.is_multiple_of(frame_height)
+    }
+}
+
+fn mcmeta_path(png: &Path) -> PathBuf {
+    let mut path = png.as_os_str().to_owned();
+    path.push(".mcmeta");
+    PathBuf::from(path)
+}
+
+/// Split `image` into `frame_width`x`frame_height` frames, in reading order
+pub fn split_frames(image: &RgbaImage, frame_width: u32, frame_height: u32) -> Vec<RgbaImage> {
+    let (width, height) = image.dimensions();
+    let (columns, rows) = (width / frame_width, height / frame_height);
+
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            image
+                .view(
+                    column * frame_width,
+                    row * frame_height,
+                    frame_width,
+                    frame_height,
+                )
+                .to_image()
+        })
+        .collect()
+}
+
+/// Inverse of [`split_frames`], laying `frames` out in `columns` columns.
+/// Every frame is assumed to be the same size as the first.
+pub fn join_frames(frames: &[RgbaImage], columns: u32) -> RgbaImage {
+    let Some(first) = frames.first() else {
+        return RgbaImage::new(0, 0);
+    };
+    let (frame_width, frame_height) = first.dimensions();
+    let rows = (frames.len() as u32).div_ceil(columns);
+
+    let mut out = RgbaImage::new(frame_width * columns, frame_height * rows);
+    for (i, frame) in frames.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        imageops::replace(
+            &mut out,
+            frame,
+            (column * frame_width) as i64,
+            (row * frame_height) as i64,
+        );
+    }
+    out
+}
+
+/// Apply `f` to each frame of `image` and put them back together.  If the
+/// frames don't evenly divide the texture, `f` gets the whole texture.
+pub fn map_frames(
+    image: DynamicImage,
+    animation: &Animation,
+    f: impl Fn(DynamicImage) -> DynamicImage,
+) -> DynamicImage {
+    let image = image.into_rgba8();
+    let (width, height) = image.dimensions();
+    let (frame_width, frame_height) = animation.frame_size(width, height);
+
+    if !animation.fits(width, height) {
+        return f(image.into());
+    }
+
+    let frames: Vec<RgbaImage> = split_frames(&image, frame_width, frame_height)
+        .into_iter()
+        .map(|frame| f(frame.into()).into_rgba8())
+        .collect();
+
+    join_frames(&frames, width / frame_width).into()
+}
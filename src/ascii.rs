@@ -0,0 +1,131 @@
+//! Drawing textures as text: each square cell of a texture becomes a
+//! character of the [bitmap font](crate::bitmap_font), picked by how bright the
+//! cell is, in the cell's colour on a plain background.  Glyphs are 3x5
+//! pixels, so textures read best when they're made bigger first:
+//!
+//! ```toml
+//! filters = [
+//!     { type = "resize", target = 48, filter = "nearest" },
+//!     { type = "ascii", cell = 6 },
+//! ]
+//! ```
+
+use image::{DynamicImage, Rgb, Rgba, RgbaImage, imageops};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bitmap_font::{GLYPH_HEIGHT, GLYPH_WIDTH, render_text},
+    filter::{HexColour, Transform, luma, mean_colour},
+};
+
+/// Characters the font has, from emptiest to fullest
+const DEFAULT_RAMP: &str = " .:-+oxam8";
+/// Cells less opaque than this are left transparent, keeping the shapes of
+/// items
+const MIN_ALPHA: u8 = 128;
+
+/// Draw each `cell`x`cell` square of every texture as a character of `ramp`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ascii {
+    /// Pixels across each character takes
+    #[serde(default = "default_cell")]
+    pub cell: u32,
+    /// The characters to draw with, in any order as they're sorted by how
+    /// much of their cell they cover
+    #[serde(default = "default_ramp")]
+    pub ramp: String,
+    /// The colour of the characters, or the colour of their cell if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<HexColour>,
+    #[serde(default = "default_background")]
+    pub background: HexColour,
+}
+
+fn default_cell() -> u32 {
+    6
+}
+
+fn default_ramp() -> String {
+    DEFAULT_RAMP.into()
+}
+
+fn default_background() -> HexColour {
+    HexColour(Rgb([0, 0, 0]))
+}
+
+impl Default for Ascii {
+    fn default() -> Self {
+        Self {
+            cell: default_cell(),
+            ramp: default_ramp(),
+            foreground: None,
+            background: default_background(),
+        }
+    }
+}
+
+/// Each character of `ramp` as a `cell`x`cell` mask, sorted by how much of it
+/// is covered.  Glyphs are centred at the biggest whole scale that fits with a
+/// pixel of space around them, or squashed to fit if the cell is too small.
+fn atlas(ramp: &str, cell: u32) -> Vec<RgbaImage> {
+    let (width, height) = (GLYPH_WIDTH + 1, GLYPH_HEIGHT + 1);
+    let scale = (cell / width).min(cell / height);
+
+    let mut glyphs: Vec<_> = ramp
+        .chars()
+        .map(|c| {
+            let glyph = render_text(&c.to_string(), Rgba([255; 4]), scale.max(1));
+            if scale == 0 {
+                let mut padded = RgbaImage::new(width, height);
+                imageops::overlay(&mut padded, &glyph, 0, 0);
+                return imageops::resize(&padded, cell, cell, imageops::FilterType::Nearest);
+            }
+            let mut mask = RgbaImage::new(cell, cell);
+            let x = (cell - glyph.width()) / 2;
+            let y = (cell - glyph.height()) / 2;
+            imageops::overlay(&mut mask, &glyph, x as i64, y as i64);
+            mask
+        })
+        .collect();
+    glyphs.sort_by_key(|mask| mask.pixels().filter(|px| px[3] > 0).count());
+    glyphs
+}
+
+impl Transform for Ascii {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = image.into_rgba8();
+        let cell = self.cell.max(1);
+        let glyphs = atlas(&self.ramp, cell);
+        if glyphs.is_empty() {
+            return image.into();
+        }
+
+        let (width, height) = image.dimensions();
+        let Rgb([br, bg, bb]) = self.background.0;
+        let mut out = RgbaImage::new(width, height);
+        for y0 in (0..height).step_by(cell as usize) {
+            for x0 in (0..width).step_by(cell as usize) {
+                let (x1, y1) = ((x0 + cell).min(width), (y0 + cell).min(height));
+                let colour = mean_colour(&image, x0..x1, y0..y1);
+                if colour[3] < MIN_ALPHA {
+                    continue;
+                }
+
+                let index = (luma(colour) * (glyphs.len() - 1) as f32).round() as usize;
+                let glyph = &glyphs[index.min(glyphs.len() - 1)];
+                let Rgb([fr, fg, fb]) = self
+                    .foreground
+                    .map_or(Rgb([colour[0], colour[1], colour[2]]), |f| f.0);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let lit = glyph.get_pixel(x - x0, y - y0)[3] > 0;
+                        let rgb = if lit { [fr, fg, fb] } else { [br, bg, bb] };
+                        out.put_pixel(x, y, Rgba([rgb[0], rgb[1], rgb[2], colour[3]]));
+                    }
+                }
+            }
+        }
+
+        out.into()
+    }
+}
@@ -0,0 +1,180 @@
+//! Assets that aren't in the client jar, like sounds, most languages and the
+//! title screen panorama, which are listed in each version's asset index and
+//! downloaded from Mojang's resource server.  Objects are cached by their
+//! hash, so they're shared between versions.
+//! See <https://minecraft.wiki/w/.minecraft/assets>
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    Version,
+    cache::CacheDir,
+    download,
+    error::{IoContext, Result},
+};
+
+const RESOURCES_URL: &str = "https://resources.download.minecraft.net";
+
+/// How many assets are downloaded at once
+const DOWNLOAD_THREADS: usize = 8;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AssetObject {
+    /// SHA-1 of the file, which is also where it's stored
+    pub hash: String,
+    pub size: u64,
+}
+
+impl AssetObject {
+    pub fn url(&self) -> String {
+        format!("{}/{}/{}", RESOURCES_URL, &self.hash[..2], self.hash)
+    }
+}
+
+/// The asset index of a version
+#[derive(Clone, Debug, Deserialize)]
+pub struct Assets {
+    /// By path, like `minecraft/sounds/ambient/cave/cave1.ogg`
+    pub objects: BTreeMap<String, AssetObject>,
+}
+
+impl Assets {
+    /// Read the asset index for `version`, downloading it if it isn't cached
+    pub fn get(version: &Version, cache: &CacheDir) -> Result<Self> {
+        let path = cache.asset_index_path(version);
+        if !path
+            .try_exists()
+            .io_context(|| format!("Checking for {}", path.display()))?
+        {
+            let version_dir = cache.version_dir(&version.id);
+            fs::create_dir_all(&version_dir)
+                .io_context(|| format!("Creating {} directory", version_dir.display()))?;
+            let meta = version.meta()?;
+            download::download_file(&meta.asset_index.url, &path, &meta.asset_index.sha1)?;
+        }
+
+        let file = File::open(&path).io_context(|| format!("Opening {}", path.display()))?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    /// Copy every asset with a path matching `filter` into `dir`, laid out
+    /// like a resource pack (`assets/<path>`), downloading the ones that
+    /// aren't cached yet.  Returns how many were copied.
+    pub fn extract(
+        &self,
+        cache: &CacheDir,
+        dir: &Path,
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<usize> {
+        let wanted: Vec<_> = self
+            .objects
+            .iter()
+            .filter(|(name, _)| filter(name))
+            .collect();
+
+        let mut missing = Vec::new();
+        for &(_, object) in &wanted {
+            let cached = cache.object_path(&object.hash);
+            if !cached
+                .try_exists()
+                .io_context(|| format!("Checking for {}", cached.display()))?
+            {
+                missing.push(object);
+            }
+        }
+        if !missing.is_empty() {
+            tracing::info!("Downloading {} assets", missing.len());
+            download_objects(&missing, cache)?;
+        }
+
+        for &(name, object) in &wanted {
+            let dest = dir.join("assets").join(name);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)
+                    .io_context(|| format!("Creating {}", parent.display()))?;
+            }
+            let cached = cache.object_path(&object.hash);
+            fs::copy(&cached, &dest)
+                .io_context(|| format!("Copying {} to {}", cached.display(), dest.display()))?;
+        }
+
+        Ok(wanted.len())
+    }
+}
+
+fn download_objects(objects: &[&AssetObject], cache: &CacheDir) -> Result<()> {
+    let next = Mutex::new(objects.iter());
+    thread::scope(|s| {
+        let workers: Vec<_> = (0..DOWNLOAD_THREADS)
+            .map(|_| {
+                s.spawn(|| -> Result<()> {
+                    loop {
+                        let Some(object) = next.lock().expect("poisoned").next() else {
+                            return Ok(());
+                        };
+                        let path = cache.object_path(&object.hash);
+                        if let Some(parent) = path.parent() {
+                            fs::create_dir_all(parent)
+                                .io_context(|| format!("Creating {}", parent.display()))?;
+                        }
+                        download::download_file(&object.url(), &path, &object.hash)?;
+                    }
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().expect("download thread panicked"))
+    })
+}
+
+/// Read the asset index for `version` and [`Assets::extract`] the assets
+/// matching `filter` into `dir`
+pub fn extract_assets(
+    version: &Version,
+    cache: &CacheDir,
+    dir: &Path,
+    filter: impl Fn(&str) -> bool,
+) -> Result<usize> {
+    Assets::get(version, cache)?.extract(cache, dir, filter)
+}
+
+fn is_sound(name: &str) -> bool {
+    name == "minecraft/sounds.json"
+        || (name.starts_with("minecraft/sounds/") && name.ends_with(".ogg"))
+}
+
+/// Extract the vanilla sounds and `sounds.json` for `version` to
+/// [`CacheDir::sounds_dir`], unless they already are
+pub fn extract_sounds(version: &Version, cache: &CacheDir) -> Result<PathBuf> {
+    let sounds_dir = cache.sounds_dir(version);
+    if sounds_dir
+        .try_exists()
+        .io_context(|| format!("Checking for {}", sounds_dir.display()))?
+    {
+        return Ok(sounds_dir);
+    }
+
+    // extract next to it first, so an interrupted extraction isn't mistaken
+    // for a finished one
+    let part = sounds_dir.with_added_extension("part");
+    if part.exists() {
+        fs::remove_dir_all(&part).io_context(|| format!("Removing {}", part.display()))?;
+    }
+    let count = extract_assets(version, cache, &part, is_sound)?;
+    fs::rename(&part, &sounds_dir)
+        .io_context(|| format!("Renaming {} to {}", part.display(), sounds_dir.display()))?;
+    tracing::info!("Extracted {} sounds to {}", count, sounds_dir.display());
+
+    Ok(sounds_dir)
+}
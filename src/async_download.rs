@@ -0,0 +1,202 @@
+//! Async versions of the manifest/version/jar/asset fetching in the crate
+//! root, for use from inside an async runtime without spawning blocking
+//! threads.
+//!
+//! Only available with the `async` feature.
+
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+use tokio::{fs, io::AsyncWriteExt, task::JoinSet};
+
+use crate::{
+    MANIFEST_URL, Manifest, Version, VersionMeta,
+    assets::{AssetObject, Assets},
+    cache::CacheDir,
+    error::{GenRpError, IoContext, Result},
+};
+
+/// Most asset objects downloaded at once by [`download_assets`]
+pub const MAX_CONCURRENT_DOWNLOADS: usize = 16;
+
+pub async fn fetch_manifest() -> Result<Manifest> {
+    Ok(reqwest::get(MANIFEST_URL)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+pub async fn fetch_version(id: &str) -> Result<Version> {
+    fetch_manifest()
+        .await?
+        .versions
+        .into_iter()
+        .find(|v| v.id == id)
+        .ok_or_else(|| GenRpError::UnknownVersion(id.to_string()))
+}
+
+pub async fn fetch_version_meta(version: &Version) -> Result<VersionMeta> {
+    Ok(reqwest::get(&version.url)
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Async equivalent of [`Version::download_jar`], returning the path of the
+/// jar rather than an open file.
+pub async fn download_jar(version: &Version, cache: &CacheDir) -> Result<PathBuf> {
+    let jar_path = cache.jar_path(version);
+    let meta = fetch_version_meta(version).await?;
+    let client = &meta.downloads.client;
+    if fs::try_exists(&jar_path)
+        .await
+        .io_context(|| format!("Checking for {}", jar_path.display()))?
+    {
+        if sha1_file(&jar_path).await? == client.sha1 {
+            return Ok(jar_path);
+        }
+        tracing::warn!(
+            "{} doesn't match its SHA-1, downloading it again",
+            jar_path.display()
+        );
+    }
+
+    let version_dir = cache.version_dir(&version.id);
+    fs::create_dir_all(&version_dir)
+        .await
+        .io_context(|| format!("Creating {} directory", version_dir.display()))?;
+
+    download_file(&client.url, &jar_path, &client.sha1).await?;
+
+    Ok(jar_path)
+}
+
+/// Async equivalent of [`crate::download::sha1_file`]
+async fn sha1_file(path: &Path) -> Result<String> {
+    let data = fs::read(path)
+        .await
+        .io_context(|| format!("Reading {}", path.display()))?;
+    Ok(format!("{:x}", Sha1::digest(&data)))
+}
+
+/// Download `url` to `dest` through `<dest>.part`, which is only renamed once
+/// it matches `sha1`
+async fn download_file(url: &str, dest: &Path, sha1: &str) -> Result<()> {
+    let part = dest.with_added_extension("part");
+    let mut file = fs::File::create(&part)
+        .await
+        .io_context(|| format!("Creating {}", part.display()))?;
+
+    let mut res = reqwest::get(url).await?.error_for_status()?;
+    let mut hasher = Sha1::new();
+    while let Some(chunk) = res.chunk().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk)
+            .await
+            .io_context(|| format!("Writing {}", part.display()))?;
+    }
+    file.flush()
+        .await
+        .io_context(|| format!("Writing {}", part.display()))?;
+    drop(file);
+
+    let actual = format!("{:x}", hasher.finalize());
+    if actual != sha1 {
+        fs::remove_file(&part)
+            .await
+            .io_context(|| format!("Removing {}", part.display()))?;
+        return Err(GenRpError::Sha1Mismatch {
+            expected: sha1.to_string(),
+            actual,
+        });
+    }
+
+    fs::rename(&part, dest)
+        .await
+        .io_context(|| format!("Renaming {} to {}", part.display(), dest.display()))
+}
+
+/// Download the jars for all of `versions` concurrently, returning their
+/// paths in the same order as `versions`.
+pub async fn download_jars(versions: &[Version], cache: &CacheDir) -> Result<Vec<PathBuf>> {
+    let mut set = JoinSet::new();
+    for (i, version) in versions.iter().enumerate() {
+        let version = version.clone();
+        let cache = cache.clone();
+        set.spawn(async move { (i, download_jar(&version, &cache).await) });
+    }
+
+    let mut paths = vec![PathBuf::new(); versions.len()];
+    while let Some(res) = set.join_next().await {
+        let (i, path) = res?;
+        paths[i] = path?;
+    }
+
+    Ok(paths)
+}
+
+/// Async equivalent of [`Assets::get`]
+pub async fn fetch_assets(version: &Version, cache: &CacheDir) -> Result<Assets> {
+    let path = cache.asset_index_path(version);
+    if !fs::try_exists(&path)
+        .await
+        .io_context(|| format!("Checking for {}", path.display()))?
+    {
+        let version_dir = cache.version_dir(&version.id);
+        fs::create_dir_all(&version_dir)
+            .await
+            .io_context(|| format!("Creating {} directory", version_dir.display()))?;
+        let meta = fetch_version_meta(version).await?;
+        download_file(&meta.asset_index.url, &path, &meta.asset_index.sha1).await?;
+    }
+
+    let json = fs::read(&path)
+        .await
+        .io_context(|| format!("Reading {}", path.display()))?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Download every object in `assets` with a path matching `filter` into the
+/// cache, at most [`MAX_CONCURRENT_DOWNLOADS`] at a time, skipping the ones
+/// that are already there.  Returns how many were downloaded.
+pub async fn download_assets(
+    assets: &Assets,
+    cache: &CacheDir,
+    filter: impl Fn(&str) -> bool,
+) -> Result<usize> {
+    let mut set = JoinSet::new();
+    let mut downloaded = 0;
+    for (_, object) in assets.objects.iter().filter(|(name, _)| filter(name)) {
+        let path = cache.object_path(&object.hash);
+        if fs::try_exists(&path)
+            .await
+            .io_context(|| format!("Checking for {}", path.display()))?
+        {
+            continue;
+        }
+
+        if set.len() >= MAX_CONCURRENT_DOWNLOADS {
+            set.join_next().await.expect("set isn't empty")??;
+        }
+        let object = object.clone();
+        set.spawn(async move { download_object(&object, &path).await });
+        downloaded += 1;
+    }
+
+    while let Some(res) = set.join_next().await {
+        res??;
+    }
+
+    Ok(downloaded)
+}
+
+async fn download_object(object: &AssetObject, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .io_context(|| format!("Creating {}", parent.display()))?;
+    }
+    download_file(&object.url(), path, &object.hash).await
+}
@@ -10,10 +10,12 @@ use anyhow::{Context, bail};
 use clap::Parser;
 use gen_rp_rs::{
     Manifest, Pack, Version, build_packs,
+    cache::CacheDir,
     colour::{hsv_to_rgb, rgb_to_hsv},
     modrinth::{self, CreateVersionReq, VersionStatus, VersionType},
 };
-use tempfile::TempDir;
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
 
 fn upload_version(
     modrinth_token: &str,
@@ -31,7 +33,7 @@ fn upload_version(
         })?;
 
     if already_exists {
-        println!("Version for {} already exists on Modrinth", version);
+        info!("Version for {} already exists on Modrinth", version);
         return Ok(());
     }
 
@@ -57,6 +59,7 @@ const PACKS: &[Pack] = &[
         name: "Saturation",
         desc: "§6Saturates all textures\n§3By: funnyboy_roks",
         slug: "yTgcjxyL",
+        only: &[],
         func: |image| {
             let mut image = image.into_rgba8();
 
@@ -78,6 +81,7 @@ const PACKS: &[Pack] = &[
         name: "Greyscale",
         desc: "§7All Textures are Greyscale\n§3By: funnyboy_roks",
         slug: "mHNsfZ54",
+        only: &[],
         func: |image| image.grayscale(),
     },
 ];
@@ -86,18 +90,12 @@ fn publish_version(version: &Version) -> anyhow::Result<()> {
     let modrinth_token =
         std::env::var("MODRINTH_TOKEN").context("MODRINTH_TOKEN env var not set")?;
 
-    let textures_dir = TempDir::new().context("Creating temporary directory for textures")?;
+    let cache = CacheDir::default_location()?;
     let out_dir: Arc<Path> = Arc::from(Path::new("out"));
 
-    build_packs(
-        version,
-        PACKS,
-        Arc::from(textures_dir.path()),
-        out_dir.clone(),
-    )
-    .context("Building resource packs")?;
+    build_packs(version, PACKS, &cache, &out_dir).context("Building resource packs")?;
 
-    eprintln!("Uploading to Modrinth...");
+    info!("Uploading to Modrinth...");
     for pack in PACKS {
         upload_version(
             &modrinth_token,
@@ -108,7 +106,7 @@ fn publish_version(version: &Version) -> anyhow::Result<()> {
         )
         .context("Uploading Saturation")?;
     }
-    eprintln!("Done uploading.");
+    info!("Done uploading.");
 
     Ok(())
 }
@@ -116,7 +114,7 @@ fn publish_version(version: &Version) -> anyhow::Result<()> {
 fn update_existing() -> anyhow::Result<()> {
     let modrinth_token =
         std::env::var("MODRINTH_TOKEN").context("MODRINTH_TOKEN env var not set")?;
-    let textures_dir = TempDir::new().context("Creating temporary directory for textures")?;
+    let cache = CacheDir::default_location()?;
     let out_dir: Arc<Path> = Arc::from(Path::new("out"));
 
     let manifest = Manifest::get().context("Getting manifest")?;
@@ -125,23 +123,23 @@ fn update_existing() -> anyhow::Result<()> {
     let version_map = manifest.get_version_map();
 
     for pack in PACKS {
-        eprintln!("Checking {} for needed updates", pack.name);
+        info!("Checking {} for needed updates", pack.name);
         let latest_mr = modrinth::project_latest_version(&modrinth_token, pack.slug, &version_map)
             .context("Getting latest modrinth version")?;
 
         let latest_mr = if let Some(latest_mr) = latest_mr {
-            eprintln!("Latest version: {}", latest_mr);
+            info!("Latest version: {}", latest_mr);
             latest_mr
         } else {
-            println!("No versions");
+            info!("No versions");
             manifest.versions.first().unwrap()
         };
 
         if latest_mc <= latest_mr {
-            eprintln!("Modrinth up to date");
+            info!("Modrinth up to date");
         }
 
-        eprintln!("Modrinth out of date");
+        info!("Modrinth out of date");
         let mut between = manifest
             .versions
             .range((Bound::Excluded(latest_mr), Bound::Included(latest_mc)))
@@ -151,16 +149,11 @@ fn update_existing() -> anyhow::Result<()> {
         between.reverse(); // ensure we build/upload in the correct order
 
         for v in between {
-            eprintln!("Building for {}", v);
-            build_packs(
-                v,
-                std::slice::from_ref(pack),
-                Arc::from(textures_dir.path()),
-                out_dir.clone(),
-            )
-            .context("Building resource packs")?;
+            info!("Building for {}", v);
+            build_packs(v, std::slice::from_ref(pack), &cache, &out_dir)
+                .context("Building resource packs")?;
 
-            eprintln!("Uploading to Modrinth...");
+            info!("Uploading to Modrinth...");
             upload_version(
                 &modrinth_token,
                 pack.slug,
@@ -169,7 +162,7 @@ fn update_existing() -> anyhow::Result<()> {
                 out_dir.clone(),
             )
             .context("Uploading Saturation")?;
-            eprintln!("Done uploading.");
+            info!("Done uploading.");
         }
     }
 
@@ -184,31 +177,38 @@ struct Cli {
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    // this runs unattended, so the timestamps are kept
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new("warn,gen_rp_rs=info,poll=info")),
+        )
+        .init();
 
     update_existing().context("Updating existing packs")?;
 
     let mut last_version = None::<Version>;
-    eprintln!("Polling for updates...");
+    info!("Polling for updates...");
     loop {
         let start = Instant::now();
 
         let version = Version::get_latest().context("Fetching latest version")?;
-        eprintln!("Latest version: {}", version);
+        info!("Latest version: {}", version);
 
         if last_version.as_ref().is_none_or(|l| *l != version) {
             if let Some(last) = last_version {
-                eprintln!("{} -> {}", last, version);
+                info!("{} -> {}", last, version);
             };
 
             if let Err(e) = publish_version(&version) {
-                eprintln!("Error Publishing version: {:?}", e);
+                error!("Error Publishing version: {:?}", e);
             }
 
             last_version = Some(version);
         }
 
         let wait = Duration::from(cli.interval) - start.elapsed();
-        eprintln!("Waiting {}", humantime::format_duration(wait));
+        info!("Waiting {}", humantime::format_duration(wait));
         thread::sleep(wait);
     }
 }
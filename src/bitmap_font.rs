@@ -0,0 +1,122 @@
+//! A tiny 3x5 pixel font for text drawn onto images, like the labels of
+//! [previews](crate::preview) and [watermarks](crate::watermark).  It only has
+//! digits, lowercase letters (uppercase ones are drawn lowercase) and a few
+//! symbols; anything else is drawn as a box.
+
+use image::{Rgba, RgbaImage};
+
+/// Each row being the bottom 3 bits
+const GLYPHS: &[(char, [u8; 5])] = &[
+    ('0', [0b111, 0b101, 0b101, 0b101, 0b111]),
+    ('1', [0b010, 0b110, 0b010, 0b010, 0b111]),
+    ('2', [0b111, 0b001, 0b111, 0b100, 0b111]),
+    ('3', [0b111, 0b001, 0b111, 0b001, 0b111]),
+    ('4', [0b101, 0b101, 0b111, 0b001, 0b001]),
+    ('5', [0b111, 0b100, 0b111, 0b001, 0b111]),
+    ('6', [0b111, 0b100, 0b111, 0b101, 0b111]),
+    ('7', [0b111, 0b001, 0b001, 0b001, 0b001]),
+    ('8', [0b111, 0b101, 0b111, 0b101, 0b111]),
+    ('9', [0b111, 0b101, 0b111, 0b001, 0b111]),
+    ('a', [0b010, 0b101, 0b111, 0b101, 0b101]),
+    ('b', [0b110, 0b101, 0b110, 0b101, 0b110]),
+    ('c', [0b011, 0b100, 0b100, 0b100, 0b011]),
+    ('d', [0b110, 0b101, 0b101, 0b101, 0b110]),
+    ('e', [0b111, 0b100, 0b110, 0b100, 0b111]),
+    ('f', [0b111, 0b100, 0b110, 0b100, 0b100]),
+    ('g', [0b011, 0b100, 0b101, 0b101, 0b011]),
+    ('h', [0b101, 0b101, 0b111, 0b101, 0b101]),
+    ('i', [0b111, 0b010, 0b010, 0b010, 0b111]),
+    ('j', [0b001, 0b001, 0b001, 0b101, 0b010]),
+    ('k', [0b101, 0b101, 0b110, 0b101, 0b101]),
+    ('l', [0b100, 0b100, 0b100, 0b100, 0b111]),
+    ('m', [0b101, 0b111, 0b111, 0b101, 0b101]),
+    ('n', [0b110, 0b101, 0b101, 0b101, 0b101]),
+    ('o', [0b010, 0b101, 0b101, 0b101, 0b010]),
+    ('p', [0b110, 0b101, 0b110, 0b100, 0b100]),
+    ('q', [0b010, 0b101, 0b101, 0b110, 0b011]),
+    ('r', [0b110, 0b101, 0b110, 0b101, 0b101]),
+    ('s', [0b011, 0b100, 0b010, 0b001, 0b110]),
+    ('t', [0b111, 0b010, 0b010, 0b010, 0b010]),
+    ('u', [0b101, 0b101, 0b101, 0b101, 0b111]),
+    ('v', [0b101, 0b101, 0b101, 0b101, 0b010]),
+    ('w', [0b101, 0b101, 0b111, 0b111, 0b101]),
+    ('x', [0b101, 0b101, 0b010, 0b101, 0b101]),
+    ('y', [0b101, 0b101, 0b010, 0b010, 0b010]),
+    ('z', [0b111, 0b001, 0b010, 0b100, 0b111]),
+    (' ', [0b000, 0b000, 0b000, 0b000, 0b000]),
+    ('_', [0b000, 0b000, 0b000, 0b000, 0b111]),
+    ('-', [0b000, 0b000, 0b111, 0b000, 0b000]),
+    ('+', [0b000, 0b010, 0b111, 0b010, 0b000]),
+    ('.', [0b000, 0b000, 0b000, 0b000, 0b010]),
+    (',', [0b000, 0b000, 0b000, 0b010, 0b100]),
+    (':', [0b000, 0b010, 0b000, 0b010, 0b000]),
+    ('!', [0b010, 0b010, 0b010, 0b000, 0b010]),
+    ('?', [0b111, 0b001, 0b010, 0b000, 0b010]),
+    ('\'', [0b010, 0b010, 0b000, 0b000, 0b000]),
+    ('/', [0b001, 0b001, 0b010, 0b100, 0b100]),
+];
+const MISSING: [u8; 5] = [0b111, 0b101, 0b101, 0b101, 0b111];
+
+pub const GLYPH_WIDTH: u32 = 3;
+pub const GLYPH_HEIGHT: u32 = 5;
+/// From the left of one character to the left of the next
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_lowercase();
+    GLYPHS
+        .iter()
+        .find(|(g, _)| *g == c)
+        .map_or(MISSING, |(_, rows)| *rows)
+}
+
+/// How wide `text` is drawn, in pixels
+pub fn text_width(text: &str) -> u32 {
+    (text.chars().count() as u32 * ADVANCE).saturating_sub(1)
+}
+
+/// Draw `text` in `colour` with its top left at `(x, y)`, cutting it off at
+/// `max_width` and at the edges of `image`
+pub fn draw_text(
+    image: &mut RgbaImage,
+    text: &str,
+    x: u32,
+    y: u32,
+    max_width: u32,
+    colour: Rgba<u8>,
+) {
+    for (i, c) in text.chars().enumerate() {
+        let x0 = x + i as u32 * ADVANCE;
+        if x0 + GLYPH_WIDTH > x + max_width {
+            break;
+        }
+
+        for (dy, row) in glyph(c).into_iter().enumerate() {
+            for dx in 0..GLYPH_WIDTH {
+                if row & (1 << (GLYPH_WIDTH - 1 - dx)) != 0
+                    && let Some(px) = image.get_pixel_mut_checked(x0 + dx, y + dy as u32)
+                {
+                    *px = colour;
+                }
+            }
+        }
+    }
+}
+
+/// `text` in `colour` on a transparent image just big enough for it, with
+/// each pixel of the font `scale` pixels across
+pub fn render_text(text: &str, colour: Rgba<u8>, scale: u32) -> RgbaImage {
+    let scale = scale.max(1);
+    let width = text_width(text);
+    if width == 0 {
+        return RgbaImage::new(0, 0);
+    }
+    let mut image = RgbaImage::new(width, GLYPH_HEIGHT);
+    draw_text(&mut image, text, 0, 0, width, colour);
+    image::imageops::resize(
+        &image,
+        width * scale,
+        GLYPH_HEIGHT * scale,
+        image::imageops::FilterType::Nearest,
+    )
+}
@@ -0,0 +1,147 @@
+//! Blend modes for laying one colour over another, like the layer modes of
+//! an image editor, following the
+//! [W3C compositing spec](https://www.w3.org/TR/compositing-1/#blending).
+//! Colours are `[r, g, b]` with channels in `0.0..=1.0`.
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// How a colour on top is combined with the one under it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    /// Just the colour on top
+    #[default]
+    Normal,
+    /// Darkens, black stays black and white changes nothing
+    Multiply,
+    /// Lightens, the opposite of [`BlendMode::Multiply`]
+    Screen,
+    /// Multiply for the dark parts of the colour below and screen for the
+    /// light parts, which adds contrast
+    Overlay,
+    /// A gentler [`BlendMode::Overlay`], like shining a diffuse light on the
+    /// colour below
+    SoftLight,
+    /// The hue and saturation of the colour on top with the brightness of
+    /// the one below, which tints without losing any detail
+    Color,
+}
+
+impl BlendMode {
+    /// `top` blended onto `base`
+    pub fn apply(self, base: [f32; 3], top: [f32; 3]) -> [f32; 3] {
+        match self {
+            BlendMode::Color => set_lum(top, lum(base)),
+            _ => std::array::from_fn(|c| self.channel(base[c], top[c])),
+        }
+    }
+
+    /// One channel of a separable mode
+    fn channel(self, base: f32, top: f32) -> f32 {
+        match self {
+            BlendMode::Normal => top,
+            BlendMode::Multiply => base * top,
+            BlendMode::Screen => 1. - (1. - base) * (1. - top),
+            BlendMode::Overlay => {
+                if base < 0.5 {
+                    2. * base * top
+                } else {
+                    1. - 2. * (1. - base) * (1. - top)
+                }
+            }
+            BlendMode::SoftLight => {
+                if top <= 0.5 {
+                    base - (1. - 2. * top) * base * (1. - base)
+                } else {
+                    let d = if base <= 0.25 {
+                        ((16. * base - 12.) * base + 4.) * base
+                    } else {
+                        base.sqrt()
+                    };
+                    base + (2. * top - 1.) * (d - base)
+                }
+            }
+            BlendMode::Color => unreachable!("colour blending isn't per channel"),
+        }
+    }
+
+    /// `top` blended onto `base`, then mixed with `base` by `opacity` (from
+    /// 0 to 1)
+    pub fn blend(self, base: [f32; 3], top: [f32; 3], opacity: f32) -> [f32; 3] {
+        let blended = self.apply(base, top);
+        std::array::from_fn(|c| base[c] + (blended[c] - base[c]) * opacity)
+    }
+
+    /// `top` blended onto `base`, where they're both visible, and laid over
+    /// it with its alpha times `opacity`
+    pub fn blend_pixel(self, base: Rgba<u8>, top: Rgba<u8>, opacity: f32) -> Rgba<u8> {
+        let top_alpha = top[3] as f32 / 255. * opacity;
+        let base_alpha = base[3] as f32 / 255.;
+        let alpha = top_alpha + base_alpha * (1. - top_alpha);
+        if alpha <= 0. {
+            return base;
+        }
+
+        let base_rgb = [base[0], base[1], base[2]].map(|c| c as f32 / 255.);
+        let top_rgb = [top[0], top[1], top[2]].map(|c| c as f32 / 255.);
+        let blended = self.apply(base_rgb, top_rgb);
+        let mut out = Rgba([0, 0, 0, to_u8(alpha)]);
+        for c in 0..3 {
+            // where the base is transparent there's nothing to blend with
+            let top = (1. - base_alpha) * top_rgb[c] + base_alpha * blended[c];
+            let rgb = (top_alpha * top + base_alpha * (1. - top_alpha) * base_rgb[c]) / alpha;
+            out[c] = to_u8(rgb);
+        }
+        out
+    }
+}
+
+/// Blend `top` onto `base` with its top left corner at `(x, y)`, see
+/// [`BlendMode::blend_pixel`].  The parts of `top` outside of `base` are
+/// cut off.
+pub fn blend_image(
+    base: &mut RgbaImage,
+    top: &RgbaImage,
+    x: i64,
+    y: i64,
+    mode: BlendMode,
+    opacity: f32,
+) {
+    for (tx, ty, px) in top.enumerate_pixels() {
+        let (Ok(bx), Ok(by)) = (u32::try_from(x + tx as i64), u32::try_from(y + ty as i64)) else {
+            continue;
+        };
+        if let Some(below) = base.get_pixel_mut_checked(bx, by) {
+            *below = mode.blend_pixel(*below, *px, opacity);
+        }
+    }
+}
+
+fn to_u8(c: f32) -> u8 {
+    (c.clamp(0., 1.) * 255.).round() as u8
+}
+
+/// Rec. 601 luma, like [`crate::filter::luma`]
+fn lum([r, g, b]: [f32; 3]) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+/// `c` with its luma changed to `l`, keeping its hue and as much of its
+/// saturation as fits
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    let c = c.map(|c| c + d);
+
+    let l = lum(c);
+    let min = c[0].min(c[1]).min(c[2]);
+    let max = c[0].max(c[1]).max(c[2]);
+    let mut out = c;
+    if min < 0. {
+        out = out.map(|c| l + (c - l) * l / (l - min));
+    }
+    if max > 1. {
+        out = out.map(|c| l + (c - l) * (1. - l) / (max - l));
+    }
+    out
+}
@@ -0,0 +1,586 @@
+//! The packs built by default
+
+use image::{Rgb, Rgba};
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::{
+    Pack,
+    ascii::Ascii,
+    blend::BlendMode,
+    colour::{hsv_to_rgb, rgb_to_hsv, to_8bit},
+    dither::{BayerSize, Dither, OneBitColour, OneBitMono, dither},
+    filter::{
+        Brightness, Channel, ChannelSwap, Contrast, Convolve, Duotone, EdgeOperator, Glitch,
+        GradientOverlay, GradientShape, Greyscale, HexColour, HueRange, HueRotate, IsolateChannel,
+        Kuwahara, Orientation, Outline, Resize, ResizeFilter, Temperature, Threshold, Transform,
+        Vhs,
+    },
+    halftone::Halftone,
+    k_means::{DEFAULT_MIN_ALPHA, k_means_auto, recolour, visible_pixels},
+    mosaic::{Mosaic, Tile},
+    palette::{CGA, GAME_BOY, NES, PICO_8, PaletteMap},
+    upscale::Scale2x,
+};
+
+/// The textures the seasonal packs recolour, along with the colormaps that
+/// tint the grey ones
+const FOLIAGE: &[&str] = &[
+    "leaves", "grass", "vine", "vines", "fern", "bush", "azalea", "lily_pad", "moss",
+];
+
+pub const PACKS: &[Pack] = &[
+    Pack {
+        name: "Saturation",
+        desc: "§6Saturates all textures\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let mut image = image.into_rgba8();
+
+            let (width, height) = image.dimensions();
+            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                let px = image.get_pixel_mut(x, y);
+
+                let mut hsv = rgb_to_hsv(&[px[0], px[1], px[2]]);
+                hsv[1] = (hsv[1] * 2.).min(1.);
+                let rgb = hsv_to_rgb(hsv);
+
+                px.0[..3].copy_from_slice(&rgb);
+            }
+
+            image.into()
+        },
+    },
+    Pack {
+        name: "Hue Shift",
+        desc: "§6Rotates the hue of all textures\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| HueRotate { degrees: 180. }.apply(image),
+    },
+    Pack {
+        name: "Swapped Channels",
+        desc: "§6The red and blue of all textures are swapped\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            ChannelSwap {
+                order: [Channel::Blue, Channel::Green, Channel::Red],
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Red World",
+        desc: "§cOnly the red of all textures\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            IsolateChannel {
+                channel: Channel::Red,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Green World",
+        desc: "§aOnly the green of all textures\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            IsolateChannel {
+                channel: Channel::Green,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Greyscale",
+        desc: "§7All Textures are Greyscale\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Greyscale::default().apply(image),
+    },
+    Pack {
+        name: "Invert",
+        desc: "§6All Textures are Inverted\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |mut image| {
+            image.invert();
+            image
+        },
+    },
+    Pack {
+        name: "Upside Down",
+        desc: "§6All textures are upside down\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Orientation::Rotate180.apply(image),
+    },
+    Pack {
+        name: "Mirrored",
+        desc: "§6All textures are mirrored\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Orientation::FlipH.apply(image),
+    },
+    Pack {
+        name: "8-bit",
+        desc: "§6Convert all textures to 8-bit colour\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| dither(image, Dither::FloydSteinberg, to_8bit),
+    },
+    Pack {
+        name: "8-bit Ordered",
+        desc: "§6Convert all textures to 8-bit colour with a Bayer pattern\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            dither(
+                image,
+                Dither::Bayer {
+                    size: BayerSize::X4,
+                    spread: 48,
+                },
+                to_8bit,
+            )
+        },
+    },
+    Pack {
+        name: "1-bit",
+        desc: "§6Every colour channel of all textures is on or off\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            OneBitColour {
+                dither: Dither::FloydSteinberg,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "1-bit Mono",
+        desc: "§6All textures are black and white\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            OneBitMono {
+                dither: Dither::FloydSteinberg,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Game Boy",
+        desc: "§2All textures use the Game Boy palette\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            PaletteMap {
+                palette: GAME_BOY,
+                dither: Dither::Bayer {
+                    size: BayerSize::X4,
+                    spread: 48,
+                },
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "CGA",
+        desc: "§bAll textures use the CGA palette\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            PaletteMap {
+                palette: CGA,
+                dither: Dither::Bayer {
+                    size: BayerSize::X2,
+                    spread: 96,
+                },
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "NES",
+        desc: "§6All textures use the NES palette\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            PaletteMap {
+                palette: NES,
+                dither: Dither::None,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "PICO-8",
+        desc: "§dAll textures use the PICO-8 palette\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            PaletteMap {
+                palette: PICO_8,
+                dither: Dither::Atkinson,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Wireframe",
+        desc: "§fAll textures are outlines\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            Outline {
+                operator: EdgeOperator::Sobel,
+                threshold: 0.5,
+                line: Rgba([255, 255, 255, 255]),
+                background: Rgba([0, 0, 0, 255]),
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Noir",
+        desc: "§fAll textures are black and white\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Threshold { level: 0.4 }.apply(image),
+    },
+    Pack {
+        name: "Blueprint",
+        desc: "§9All textures are blueprints\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            Duotone {
+                dark: HexColour(Rgb([0x0b, 0x2e, 0x6b])),
+                light: HexColour(Rgb([0xd6, 0xe6, 0xff])),
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Emboss",
+        desc: "§7All textures are embossed\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Convolve::EMBOSS.apply(image),
+    },
+    Pack {
+        name: "Darker",
+        desc: "§8All textures are darker\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Brightness { factor: 0.6 }.apply(image),
+    },
+    Pack {
+        name: "High Contrast",
+        desc: "§fAll textures have more contrast\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Contrast { factor: 1.8 }.apply(image),
+    },
+    Pack {
+        name: "Warm",
+        desc: "§6All textures are warmer\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Temperature { kelvin: 4000. }.apply(image),
+    },
+    Pack {
+        name: "Cool",
+        desc: "§bAll textures are cooler\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Temperature { kelvin: 10000. }.apply(image),
+    },
+    Pack {
+        name: "VHS",
+        desc: "§5All textures look like an old tape\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            Vhs {
+                chroma_shift: 1,
+                scanlines: 0.2,
+                noise: 0.06,
+                seed: 1987,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "8x8 Demake",
+        desc: "§6All textures are 8x8\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            Resize {
+                target: 8,
+                filter: ResizeFilter::Box,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "32x32 Upscaled",
+        desc: "§6All textures are 32x32\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            Resize {
+                target: 32,
+                filter: ResizeFilter::Nearest,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Smooth 2x",
+        desc: "§6All textures are smoothly upscaled\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| Scale2x { smooth: true }.apply(image),
+    },
+    Pack {
+        name: "Average",
+        desc: "§6Averages all textures\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let mut image = image.into_rgba8();
+
+            let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
+            let mut i = 0u32;
+
+            let (width, height) = image.dimensions();
+
+            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                let px = image.get_pixel(x, y);
+
+                if px[3] > 0 {
+                    r += px[0] as u32;
+                    g += px[1] as u32;
+                    b += px[2] as u32;
+                    i += 1;
+                }
+            }
+
+            if i == 0 {
+                return image.into();
+            }
+
+            let r = (r / i) as u8;
+            let g = (g / i) as u8;
+            let b = (b / i) as u8;
+
+            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                let px = image.get_pixel_mut(x, y);
+
+                if px[3] > 0 {
+                    px[0] = r;
+                    px[1] = g;
+                    px[2] = b;
+                }
+            }
+
+            image.into()
+        },
+    },
+    Pack {
+        name: "8bit",
+        desc: "§6All textures are 8-bit\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let mut image = image.into_rgba8();
+
+            let (width, height) = image.dimensions();
+            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+                let px = image.get_pixel_mut(x, y);
+
+                px[0] = (px[0] / 32) * 32;
+                px[1] = (px[1] / 32) * 32;
+                px[2] = (px[2] / 64) * 64;
+            }
+
+            image.into()
+        },
+    },
+    Pack {
+        name: "Sunset",
+        desc: "§6Every texture fades from orange to purple\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            GradientOverlay {
+                from: HexColour(Rgb([0xff, 0xb3, 0x47])),
+                to: HexColour(Rgb([0x6a, 0x30, 0x93])),
+                shape: GradientShape::Vertical,
+                mode: BlendMode::Overlay,
+                opacity: 0.6,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Toxic",
+        desc: "§aEvery texture glows a sickly green\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            GradientOverlay {
+                from: HexColour(Rgb([0xc6, 0xff, 0x4a])),
+                to: HexColour(Rgb([0x1e, 0x4d, 0x00])),
+                shape: GradientShape::Radial,
+                mode: BlendMode::Overlay,
+                opacity: 0.7,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Autumn",
+        desc: "§6Leaves and grass turn orange and red\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: FOLIAGE,
+        func: |image| {
+            HueRange {
+                from: 50.,
+                to: 170.,
+                feather: 20.,
+                min_saturation: 0.1,
+                shift: -75.,
+                saturation: 1.2,
+                value: 1.,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "Winter",
+        desc: "§fLeaves and grass are covered in snow\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: FOLIAGE,
+        func: |image| {
+            HueRange {
+                from: 50.,
+                to: 170.,
+                feather: 20.,
+                min_saturation: 0.1,
+                shift: 0.,
+                saturation: 0.15,
+                value: 1.6,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "ASCII",
+        desc: "§aEvery texture is drawn in text\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let image = Resize {
+                target: 48,
+                filter: ResizeFilter::Nearest,
+            }
+            .apply(image);
+            Ascii::default().apply(image)
+        },
+    },
+    Pack {
+        name: "Bricks",
+        desc: "§cEvery texture is built from toy bricks\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let image = Resize {
+                target: 64,
+                filter: ResizeFilter::Nearest,
+            }
+            .apply(image);
+            Mosaic::new(4, Tile::Brick).apply(image)
+        },
+    },
+    Pack {
+        name: "Cross Stitch",
+        desc: "§dEvery texture is stitched into cloth\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let image = Resize {
+                target: 64,
+                filter: ResizeFilter::Nearest,
+            }
+            .apply(image);
+            Mosaic::new(4, Tile::Stitch)
+                .with_background(HexColour(Rgb([0xe8, 0xdc, 0xc4])))
+                .apply(image)
+        },
+    },
+    Pack {
+        name: "Oil Paint",
+        desc: "§6Every texture is painted in thick strokes\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let image = Resize {
+                target: 64,
+                filter: ResizeFilter::Nearest,
+            }
+            .apply(image);
+            Kuwahara { radius: 3 }.apply(image)
+        },
+    },
+    Pack {
+        name: "Newspaper",
+        desc: "§7Every texture is printed in the paper\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let image = Resize {
+                target: 64,
+                filter: ResizeFilter::Nearest,
+            }
+            .apply(image);
+            Halftone::default().apply(image)
+        },
+    },
+    Pack {
+        name: "Corrupted",
+        desc: "§5Every texture is glitched\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            Glitch {
+                max_offset: 1,
+                probability: 0.15,
+                seed: 0,
+            }
+            .apply(image)
+        },
+    },
+    Pack {
+        name: "K-Means",
+        desc: "§6Every texture is cut down to its main colours\n§3By: funnyboy_roks",
+        slug: "unused",
+        only: &[],
+        func: |image| {
+            let mut image = image.into_rgba8();
+            let pixels = visible_pixels(&image, DEFAULT_MIN_ALPHA);
+            // seeded, so the pack comes out the same every time
+            let mut rng = StdRng::seed_from_u64(0);
+            let clusters = k_means_auto(&pixels, 2..=16, &mut rng).centroids;
+            recolour(&mut image, &clusters, DEFAULT_MIN_ALPHA);
+
+            image.into()
+        },
+    },
+];
@@ -0,0 +1,101 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Version,
+    error::{GenRpError, IoContext, Result},
+};
+
+/// Where downloaded client jars and extracted textures are kept, with one
+/// subdirectory per Minecraft version, and the assets that aren't in the jar
+/// shared between them:
+///
+/// ```text
+/// <root>/
+///   <version>/
+///     client.jar
+///     assets.json
+///     textures/
+///     sounds/
+///   objects/
+///     <ab>/<abcdef...>
+/// ```
+#[derive(Clone, Debug)]
+pub struct CacheDir {
+    root: PathBuf,
+}
+
+impl CacheDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The platform cache directory, i.e. `~/.cache/gen-rp` on Linux
+    pub fn default_location() -> Result<Self> {
+        let cache = dirs::cache_dir().ok_or(GenRpError::NoCacheDir)?;
+        Ok(Self::new(cache.join("gen-rp")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn version_dir(&self, version_id: &str) -> PathBuf {
+        self.root.join(version_id)
+    }
+
+    pub fn jar_path(&self, version: &Version) -> PathBuf {
+        self.version_dir(&version.id).join("client.jar")
+    }
+
+    /// Extracted textures, laid out the same way as in a resource pack
+    pub fn textures_dir(&self, version: &Version) -> PathBuf {
+        self.version_dir(&version.id).join("textures")
+    }
+
+    /// The version's asset index, see [`crate::assets`]
+    pub fn asset_index_path(&self, version: &Version) -> PathBuf {
+        self.version_dir(&version.id).join("assets.json")
+    }
+
+    /// An asset by its hash, as it's stored on Mojang's resource server
+    pub fn object_path(&self, hash: &str) -> PathBuf {
+        self.root.join("objects").join(&hash[..2]).join(hash)
+    }
+
+    /// Extracted sounds and `sounds.json`, laid out the same way as in a
+    /// resource pack
+    pub fn sounds_dir(&self, version: &Version) -> PathBuf {
+        self.version_dir(&version.id).join("sounds")
+    }
+
+    /// Get the textures dir for `version`, removing anything left from a
+    /// previous extraction.
+    pub fn fresh_textures_dir(&self, version: &Version) -> Result<PathBuf> {
+        let textures_dir = self.textures_dir(version);
+        remove_dir_if_exists(&textures_dir)?;
+        fs::create_dir_all(&textures_dir)
+            .io_context(|| format!("Creating {}", textures_dir.display()))?;
+        Ok(textures_dir)
+    }
+
+    /// Remove everything in the cache
+    pub fn clean(&self) -> Result<()> {
+        remove_dir_if_exists(&self.root)
+    }
+
+    /// Remove the cache for a single version
+    pub fn clean_version(&self, version_id: &str) -> Result<()> {
+        remove_dir_if_exists(&self.version_dir(version_id))
+    }
+}
+
+fn remove_dir_if_exists(dir: &Path) -> Result<()> {
+    match fs::remove_dir_all(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).io_context(|| format!("Removing {}", dir.display())),
+    }
+}
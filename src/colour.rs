@@ -1,23 +1,19 @@
 use image::Rgba;
 
+/// The nearest 3-3-2 "8-bit" colour, with 8 levels of red and green and 4
+/// of blue spread evenly from 0 to 255
 pub fn to_8bit(rgb: Rgba<i32>) -> Rgba<i32> {
+    let levels = |c: i32, steps: i32| ((c.clamp(0, 255) * steps + 127) / 255) * 255 / steps;
     Rgba([
-        (rgb.0[0] / 32) * 32,
-        (rgb.0[1] / 32) * 32,
-        (rgb.0[2] / 64) * 64,
+        levels(rgb.0[0], 7),
+        levels(rgb.0[1], 7),
+        levels(rgb.0[2], 3),
         rgb.0[3],
     ])
 }
 
-pub fn rgb_to_hsv([r, g, b]: &[u8; 3]) -> [f32; 3] {
-    let rp = *r as f32 / 255.;
-    let gp = *g as f32 / 255.;
-    let bp = *b as f32 / 255.;
-
-    let c_max = rp.max(gp).max(bp);
-    let c_min = rp.min(gp).min(bp);
-    let delta = c_max - c_min;
-
+/// Hue in degrees, `0..360`
+fn hue(rp: f32, gp: f32, bp: f32, c_max: f32, delta: f32) -> f32 {
     let h = if delta == 0. {
         0.
     } else if c_max == rp {
@@ -30,22 +26,18 @@ pub fn rgb_to_hsv([r, g, b]: &[u8; 3]) -> [f32; 3] {
         unreachable!()
     };
 
-    let s = if c_max == 0. { 0. } else { delta / c_max };
-    let v = c_max;
-
-    [h, s, v]
+    h.rem_euclid(360.)
 }
 
+/// Inverse of [`hue`], given the chroma and the value to add to each channel
 // https://docs.rs/hsv/latest/hsv/fn.hsv_to_rgb.html
-pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [u8; 3] {
+fn from_hue_chroma(h: f32, c: f32, m: f32) -> [u8; 3] {
     fn is_between(value: f32, min: f32, max: f32) -> bool {
         min <= value && value < max
     }
 
-    let c = v * s;
-    let h = h / 60.0;
+    let h = h.rem_euclid(360.) / 60.0;
     let x = c * (1.0 - ((h % 2.0) - 1.0).abs());
-    let m = v - c;
 
     let (r, g, b) = if is_between(h, 0.0, 1.0) {
         (c, x, 0.0)
@@ -62,8 +54,197 @@ pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [u8; 3] {
     };
 
     [
-        ((r + m) * 255.0) as u8,
-        ((g + m) * 255.0) as u8,
-        ((b + m) * 255.0) as u8,
+        ((r + m) * 255.0).round() as u8,
+        ((g + m) * 255.0).round() as u8,
+        ((b + m) * 255.0).round() as u8,
     ]
 }
+
+pub fn rgb_to_hsv([r, g, b]: &[u8; 3]) -> [f32; 3] {
+    let rp = *r as f32 / 255.;
+    let gp = *g as f32 / 255.;
+    let bp = *b as f32 / 255.;
+
+    let c_max = rp.max(gp).max(bp);
+    let c_min = rp.min(gp).min(bp);
+    let delta = c_max - c_min;
+
+    let h = hue(rp, gp, bp, c_max, delta);
+    let s = if c_max == 0. { 0. } else { delta / c_max };
+    let v = c_max;
+
+    [h, s, v]
+}
+
+pub fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [u8; 3] {
+    let c = v * s;
+    from_hue_chroma(h, c, v - c)
+}
+
+pub fn rgb_to_hsl([r, g, b]: &[u8; 3]) -> [f32; 3] {
+    let rp = *r as f32 / 255.;
+    let gp = *g as f32 / 255.;
+    let bp = *b as f32 / 255.;
+
+    let c_max = rp.max(gp).max(bp);
+    let c_min = rp.min(gp).min(bp);
+    let delta = c_max - c_min;
+
+    let h = hue(rp, gp, bp, c_max, delta);
+    let l = (c_max + c_min) / 2.;
+    let s = if delta == 0. {
+        0.
+    } else {
+        delta / (1. - (2. * l - 1.).abs())
+    };
+
+    [h, s, l]
+}
+
+pub fn hsl_to_rgb([h, s, l]: [f32; 3]) -> [u8; 3] {
+    let c = (1. - (2. * l - 1.).abs()) * s;
+    from_hue_chroma(h, c, l - c / 2.)
+}
+
+/// sRGB channel to linear light, `0.0..=1.0`
+// https://en.wikipedia.org/wiki/SRGB#Transformation
+pub fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0., 1.);
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    };
+    (c * 255.).round() as u8
+}
+
+pub fn rgb_to_linear([r, g, b]: &[u8; 3]) -> [f32; 3] {
+    [srgb_to_linear(*r), srgb_to_linear(*g), srgb_to_linear(*b)]
+}
+
+pub fn linear_to_rgb([r, g, b]: [f32; 3]) -> [u8; 3] {
+    [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)]
+}
+
+/// Approximate colour of a black body at `kelvin`, as linear RGB
+// https://tannerhelland.com/2012/09/18/convert-temperature-rgb-algorithm-code.html
+pub fn blackbody(kelvin: f32) -> [f32; 3] {
+    let t = kelvin.clamp(1000., 40000.) / 100.;
+
+    let r = if t <= 66. {
+        255.
+    } else {
+        329.69873 * (t - 60.).powf(-0.13320476)
+    };
+    let g = if t <= 66. {
+        99.4708 * t.ln() - 161.11957
+    } else {
+        288.12217 * (t - 60.).powf(-0.07551485)
+    };
+    let b = if t >= 66. {
+        255.
+    } else if t <= 19. {
+        0.
+    } else {
+        138.51773 * (t - 10.).ln() - 305.0448
+    };
+
+    [r, g, b].map(|c| srgb_to_linear(c.clamp(0., 255.).round() as u8))
+}
+
+/// sRGB to Oklab, where distances match how different colours look, with
+/// lightness `0.0..=1.0` and `a` and `b` within about `-0.4..=0.4`
+// https://bottosson.github.io/posts/oklab/
+pub fn rgb_to_oklab(rgb: &[u8; 3]) -> [f32; 3] {
+    let [r, g, b] = rgb_to_linear(rgb);
+    let l = (0.4122215 * r + 0.5363325 * g + 0.0514460 * b).cbrt();
+    let m = (0.2119035 * r + 0.6806995 * g + 0.107397 * b).cbrt();
+    let s = (0.0883025 * r + 0.2817188 * g + 0.6299787 * b).cbrt();
+    [
+        0.2104543 * l + 0.7936178 * m - 0.0040720 * s,
+        1.9779985 * l - 2.4285922 * m + 0.4505937 * s,
+        0.0259040 * l + 0.7827718 * m - 0.8086758 * s,
+    ]
+}
+
+/// Inverse of [`rgb_to_oklab`], clamping colours outside of sRGB
+pub fn oklab_to_rgb([l, a, b]: [f32; 3]) -> [u8; 3] {
+    let l_ = (l + 0.3963378 * a + 0.2158038 * b).powi(3);
+    let m_ = (l - 0.1055613 * a - 0.0638542 * b).powi(3);
+    let s_ = (l - 0.0894842 * a - 1.2914855 * b).powi(3);
+    linear_to_rgb([
+        4.0767417 * l_ - 3.3077116 * m_ + 0.2309699 * s_,
+        -1.268438 * l_ + 2.6097574 * m_ - 0.3413194 * s_,
+        -0.0041961 * l_ - 0.7034186 * m_ + 1.7076147 * s_,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every colour with channels in steps of 17, which includes the greys
+    fn grid() -> impl Iterator<Item = [u8; 3]> {
+        let steps = || (0..=255).step_by(17);
+        steps().flat_map(move |r| steps().flat_map(move |g| steps().map(move |b| [r, g, b])))
+    }
+
+    #[test]
+    fn hsv_round_trips() {
+        for rgb in grid() {
+            assert_eq!(hsv_to_rgb(rgb_to_hsv(&rgb)), rgb, "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn hsl_round_trips() {
+        for rgb in grid() {
+            assert_eq!(hsl_to_rgb(rgb_to_hsl(&rgb)), rgb, "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn linear_round_trips() {
+        for c in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(c)), c);
+        }
+        for rgb in grid() {
+            assert_eq!(linear_to_rgb(rgb_to_linear(&rgb)), rgb, "{:?}", rgb);
+        }
+    }
+
+    #[test]
+    fn greys_have_no_hue_or_saturation() {
+        for c in (0..=255).step_by(17) {
+            let [h, s, v] = rgb_to_hsv(&[c, c, c]);
+            assert_eq!((h, s), (0., 0.));
+            assert_eq!(v, c as f32 / 255.);
+            let [h, s, _] = rgb_to_hsl(&[c, c, c]);
+            assert_eq!((h, s), (0., 0.));
+        }
+    }
+
+    #[test]
+    fn hue_wraps_around() {
+        // just short of red, going the other way round from yellow
+        let [h, ..] = rgb_to_hsv(&[255, 0, 1]);
+        assert!((0. ..360.).contains(&h), "{}", h);
+        assert!(h > 359.);
+        let [h, ..] = rgb_to_hsl(&[255, 0, 1]);
+        assert!((0. ..360.).contains(&h), "{}", h);
+
+        assert_eq!(hsv_to_rgb([360., 1., 1.]), [255, 0, 0]);
+        assert_eq!(hsv_to_rgb([-30., 1., 1.]), hsv_to_rgb([330., 1., 1.]));
+        assert_eq!(hsv_to_rgb([390., 1., 1.]), hsv_to_rgb([30., 1., 1.]));
+        assert_eq!(hsl_to_rgb([-120., 1., 0.5]), [0, 0, 255]);
+    }
+}
@@ -0,0 +1,309 @@
+//! Packs defined in a TOML file rather than in code, each applying a list of
+//! filters in order:
+//!
+//! ```toml
+//! [[pack]]
+//! name = "Moody"
+//! description = "§8Dark and contrasty"
+//! filters = [
+//!     { type = "brightness", factor = 0.8 },
+//!     { type = "contrast", factor = 1.4 },
+//! ]
+//! ```
+
+use std::{fs, path::Path};
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(feature = "script")]
+use crate::script::ScriptFilter;
+#[cfg(feature = "wasm")]
+use crate::wasm::WasmFilter;
+use crate::{
+    ascii::Ascii,
+    error::{GenRpError, IoContext, Result},
+    filter::{
+        Brightness, ChannelSwap, Contrast, Convolve, Curves, Duotone, Gamma, Glitch,
+        GradientOverlay, Grain, Greyscale, HueRange, HueRotate, IsolateChannel, Kuwahara,
+        Orientation, RandomHue, ReplaceColour, Resize, Temperature, Threshold, Tint, TintByName,
+        Transform, Vhs, is_colormap, is_font, texture_category,
+    },
+    halftone::Halftone,
+    lut::LutFilter,
+    mosaic::Mosaic,
+    output::check_pack_name,
+    upscale::Scale2x,
+    watermark::Watermark,
+};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Config {
+    #[serde(rename = "pack", default)]
+    pub packs: Vec<PackConfig>,
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let s =
+            fs::read_to_string(path).io_context(|| format!("Reading config {}", path.display()))?;
+        let mut config: Self = toml::from_str(&s).map_err(|e| GenRpError::Config(e.to_string()))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for pack in &mut config.packs {
+            pack.resolve(base)?;
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PackConfig {
+    pub name: String,
+    pub description: String,
+    pub filters: Vec<Filter>,
+    /// Leave font textures alone, see [`crate::filter::ProtectFonts`]
+    #[serde(default = "default_protect_fonts")]
+    pub protect_fonts: bool,
+    /// How to treat the biome colormaps (`textures/colormap/*.png`)
+    #[serde(default)]
+    pub colormaps: ColormapMode,
+    /// Only include the font textures, with the filters applied to them, to
+    /// make a font pack (see [`crate::font`])
+    #[serde(default)]
+    pub fonts_only: bool,
+    /// Only include the textures in these categories, like `["item"]`, see
+    /// [`texture_category`].  Every texture is included if it's empty.
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColormapMode {
+    /// Apply the filters like to any other texture
+    #[default]
+    Transform,
+    /// Leave them alone
+    Skip,
+    /// Apply the filters, then blur away the banding that filters like
+    /// palettes leave, which would otherwise show as hard edges between
+    /// biomes
+    Smooth,
+}
+
+fn default_protect_fonts() -> bool {
+    true
+}
+
+impl PackConfig {
+    /// Load the files that the filters use, like WASM modules, LUTs, mosaic
+    /// tiles and watermark images, with their paths relative to `base`, and
+    /// check the pack's name and the filters' settings
+    pub(crate) fn resolve(&mut self, base: &Path) -> Result<()> {
+        check_pack_name(&self.name)?;
+        for filter in &mut self.filters {
+            match filter {
+                Filter::Grain(f) => f.validate()?,
+                Filter::Watermark(f) => f.resolve(base)?,
+                Filter::Lut(f) => f.resolve(base)?,
+                Filter::Mosaic(f) => f.resolve(base)?,
+                #[cfg(feature = "wasm")]
+                Filter::Wasm(f) => f.resolve(base)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transform for PackConfig {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self.filters
+            .iter()
+            .fold(image, |image, filter| filter.apply(image))
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        if self.protect_fonts && !self.fonts_only && is_font(path) {
+            return image;
+        }
+
+        let colormap = is_colormap(path);
+        if colormap && self.colormaps == ColormapMode::Skip {
+            return image;
+        }
+
+        let image = self
+            .filters
+            .iter()
+            .fold(image, |image, filter| filter.apply_with_path(image, path));
+
+        if colormap && self.colormaps == ColormapMode::Smooth {
+            (0..4).fold(image, |image, _| Convolve::BOX_BLUR.apply(image))
+        } else {
+            image
+        }
+    }
+
+    /// The filters one after the other, leaving protected fonts and
+    /// colormaps that aren't just transformed to the CPU
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, path: &Path) -> Option<String> {
+        if (self.protect_fonts && !self.fonts_only && is_font(path))
+            || (is_colormap(path) && self.colormaps != ColormapMode::Transform)
+        {
+            return None;
+        }
+
+        let bodies = self
+            .filters
+            .iter()
+            .map(|filter| filter.wgsl(path).map(|body| format!("{{ {} }}", body)))
+            .collect::<Option<Vec<_>>>()?;
+        Some(bodies.join("\n"))
+    }
+
+    /// The pack as it is written in the config
+    fn describe(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        self.categories.is_empty()
+            || texture_category(path)
+                .is_some_and(|category| self.categories.iter().any(|c| c == category))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Filter {
+    Greyscale(Greyscale),
+    Invert,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipH,
+    FlipV,
+    HueRotate(HueRotate),
+    Brightness(Brightness),
+    Contrast(Contrast),
+    Gamma(Gamma),
+    Grain(Grain),
+    Vhs(Vhs),
+    Glitch(Glitch),
+    Resize(Resize),
+    Scale2x(Scale2x),
+    ChannelSwap(ChannelSwap),
+    IsolateChannel(IsolateChannel),
+    Temperature(Temperature),
+    Tint(Tint),
+    Threshold(Threshold),
+    Duotone(Duotone),
+    RandomHue(RandomHue),
+    TintByName(TintByName),
+    GradientOverlay(GradientOverlay),
+    HueRange(HueRange),
+    ReplaceColour(ReplaceColour),
+    Curves(Curves),
+    Kuwahara(Kuwahara),
+    /// Textures drawn as text, see [`crate::ascii`]
+    Ascii(Ascii),
+    /// Textures built out of tiles, see [`crate::mosaic`]
+    Mosaic(Mosaic),
+    /// Textures printed in dots, see [`crate::halftone`]
+    Halftone(Halftone),
+    /// A `.cube` colour grade, see [`crate::lut`]
+    Lut(LutFilter),
+    /// A stamp on a few textures, see [`crate::watermark`]
+    Watermark(Watermark),
+    /// A filter compiled to WASM, see [`crate::wasm`]
+    #[cfg(feature = "wasm")]
+    Wasm(WasmFilter),
+    /// A per-pixel Rhai script, see [`crate::script`]
+    #[cfg(feature = "script")]
+    Script(ScriptFilter),
+}
+
+impl Transform for Filter {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Filter::Greyscale(f) => f.apply(image),
+            Filter::Invert => {
+                let mut image = image;
+                image.invert();
+                image
+            }
+            Filter::Rotate90 => Orientation::Rotate90.apply(image),
+            Filter::Rotate180 => Orientation::Rotate180.apply(image),
+            Filter::Rotate270 => Orientation::Rotate270.apply(image),
+            Filter::FlipH => Orientation::FlipH.apply(image),
+            Filter::FlipV => Orientation::FlipV.apply(image),
+            Filter::HueRotate(f) => f.apply(image),
+            Filter::Brightness(f) => f.apply(image),
+            Filter::Contrast(f) => f.apply(image),
+            Filter::Gamma(f) => f.apply(image),
+            Filter::Grain(f) => f.apply(image),
+            Filter::Vhs(f) => f.apply(image),
+            Filter::Glitch(f) => f.apply(image),
+            Filter::Resize(f) => f.apply(image),
+            Filter::Scale2x(f) => f.apply(image),
+            Filter::ChannelSwap(f) => f.apply(image),
+            Filter::IsolateChannel(f) => f.apply(image),
+            Filter::Temperature(f) => f.apply(image),
+            Filter::Tint(f) => f.apply(image),
+            Filter::Threshold(f) => f.apply(image),
+            Filter::Duotone(f) => f.apply(image),
+            Filter::RandomHue(f) => f.apply(image),
+            Filter::TintByName(f) => f.apply(image),
+            Filter::GradientOverlay(f) => f.apply(image),
+            Filter::HueRange(f) => f.apply(image),
+            Filter::ReplaceColour(f) => f.apply(image),
+            Filter::Curves(f) => f.apply(image),
+            Filter::Kuwahara(f) => f.apply(image),
+            Filter::Ascii(f) => f.apply(image),
+            Filter::Mosaic(f) => f.apply(image),
+            Filter::Halftone(f) => f.apply(image),
+            Filter::Lut(f) => f.apply(image),
+            Filter::Watermark(f) => f.apply(image),
+            #[cfg(feature = "wasm")]
+            Filter::Wasm(f) => f.apply(image),
+            #[cfg(feature = "script")]
+            Filter::Script(f) => f.apply(image),
+        }
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        match self {
+            Filter::RandomHue(f) => f.apply_with_path(image, path),
+            Filter::Glitch(f) => f.apply_with_path(image, path),
+            Filter::TintByName(f) => f.apply_with_path(image, path),
+            Filter::Watermark(f) => f.apply_with_path(image, path),
+            #[cfg(feature = "script")]
+            Filter::Script(f) => f.apply_with_path(image, path),
+            _ => self.apply(image),
+        }
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, path: &Path) -> Option<String> {
+        match self {
+            Filter::Greyscale(f) => f.wgsl(path),
+            Filter::Invert => Some("px = vec4(1.0 - px.rgb, px.a);".into()),
+            Filter::HueRotate(f) => f.wgsl(path),
+            Filter::Brightness(f) => f.wgsl(path),
+            Filter::Contrast(f) => f.wgsl(path),
+            Filter::Gamma(f) => f.wgsl(path),
+            Filter::ChannelSwap(f) => f.wgsl(path),
+            Filter::IsolateChannel(f) => f.wgsl(path),
+            Filter::Threshold(f) => f.wgsl(path),
+            Filter::TintByName(f) => f.wgsl(path),
+            Filter::Watermark(f) => f.wgsl(path),
+            _ => None,
+        }
+    }
+}
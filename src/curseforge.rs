@@ -0,0 +1,113 @@
+//! Uploading packs to CurseForge with the upload API
+//! See <https://support.curseforge.com/en/support/solutions/articles/9000197321-curseforge-upload-api>
+
+use std::path::Path;
+
+use ::reqwest::{
+    StatusCode,
+    blocking::{self as reqwest, Client},
+};
+use lazy_static::lazy_static;
+use reqwest::{Response, multipart::Form};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::error::{GenRpError, IoContext, Result};
+
+const CURSEFORGE_API: &str = "https://minecraft.curseforge.com/api";
+
+lazy_static! {
+    static ref CLIENT: Client = Client::builder()
+        .user_agent("funnyboy-roks/generated-resource-packs (fbr@fbr.dev)")
+        .build()
+        .expect("Failed to build client");
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseType {
+    Release,
+    Beta,
+    Alpha,
+}
+
+#[derive(Debug, Deserialize)]
+struct GameVersion {
+    id: u32,
+    name: String,
+}
+
+/// CurseForge's ids for the Minecraft versions in `names`, e.g. `1.21.4`.
+/// Snapshots aren't listed, so asking for one is an error.
+pub fn game_version_ids(curseforge_token: &str, names: &[&str]) -> Result<Vec<u32>> {
+    let response = CLIENT
+        .get(format!("{}/game/versions", CURSEFORGE_API))
+        .header("X-Api-Token", curseforge_token)
+        .send()?;
+    let versions: Vec<GameVersion> = parse_response(response)?;
+
+    names
+        .iter()
+        .map(|name| {
+            versions
+                .iter()
+                .find(|v| v.name == *name)
+                .map(|v| v.id)
+                .ok_or_else(|| GenRpError::CurseForge {
+                    status: StatusCode::NOT_FOUND,
+                    body: Some(format!("no game version called {}", name).into()),
+                })
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadFileReq<'a> {
+    pub changelog: &'a str,
+    pub changelog_type: &'a str,
+    pub display_name: &'a str,
+    pub game_versions: &'a [u32],
+    pub release_type: ReleaseType,
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadFileRes {
+    id: u64,
+}
+
+impl UploadFileReq<'_> {
+    /// Upload `file` to the project with the numeric id `project_id`,
+    /// returning the id of the new file
+    pub fn send(self, curseforge_token: &str, project_id: &str, file: &Path) -> Result<u64> {
+        let response = CLIENT
+            .post(format!(
+                "{}/projects/{}/upload-file",
+                CURSEFORGE_API, project_id
+            ))
+            .header("X-Api-Token", curseforge_token)
+            .multipart(
+                Form::new()
+                    .text(
+                        "metadata",
+                        serde_json::to_string(&self)
+                            .expect("This structure can't fail to serialize"),
+                    )
+                    .file("file", file)
+                    .io_context(|| format!("Reading {}", file.display()))?,
+            )
+            .send()?;
+
+        let res: UploadFileRes = parse_response(response)?;
+        Ok(res.id)
+    }
+}
+
+fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    match response.status() {
+        StatusCode::OK => Ok(response.json()?),
+        status => Err(GenRpError::CurseForge {
+            status,
+            body: response.json::<serde_json::Value>().ok(),
+        }),
+    }
+}
@@ -0,0 +1,145 @@
+//! Data packs to go alongside the resource packs, for packs that need more
+//! than textures (like custom item models, which need an item modifier or
+//! recipe to hand out the items).
+//!
+//! Files can be added from code with [`DataPack::add_json`], or from a
+//! directory of templates with [`DataPack::add_templates`]:
+//!
+//! ```text
+//! templates/
+//! └── gen_rp/
+//!     ├── recipe/shiny_diamond.json
+//!     ├── item_modifier/make_shiny.json
+//!     └── tags/item/shiny.json
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use walkdir::WalkDir;
+
+use crate::{
+    GenerateOptions, GenerationReport, PackOutput,
+    error::{GenRpError, IoContext, Result},
+    finish_pack,
+    shaders::substitute,
+};
+
+/// A data pack, built up in memory and then written with [`DataPack::write`]
+#[derive(Clone, Debug)]
+pub struct DataPack {
+    pub description: String,
+    /// The `data_major` of the game's `version.json`, which is separate from
+    /// the resource pack format
+    pub pack_format: u32,
+    /// Path in the pack and contents of each file
+    files: BTreeMap<PathBuf, Vec<u8>>,
+    warnings: Vec<String>,
+}
+
+impl DataPack {
+    pub fn new(description: impl Into<String>, pack_format: u32) -> Self {
+        Self {
+            description: description.into(),
+            pack_format,
+            files: BTreeMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Add `value` as `data/<namespace>/<kind>/<path>.json`, for an `id` like
+    /// `namespace:path` (or just `path` for `minecraft`) and a `kind` like
+    /// `recipe`, `advancement`, `item_modifier` or `tags/item`
+    pub fn add_json(&mut self, kind: &str, id: &str, value: &impl Serialize) -> Result<()> {
+        let (namespace, path) = id.split_once(':').unwrap_or(("minecraft", id));
+        if namespace.is_empty() || path.is_empty() {
+            return Err(GenRpError::InvalidPack(format!(
+                "invalid resource location {}",
+                id
+            )));
+        }
+
+        let path = Path::new("data")
+            .join(namespace)
+            .join(kind)
+            .join(format!("{}.json", path));
+        self.files.insert(path, serde_json::to_vec_pretty(value)?);
+        Ok(())
+    }
+
+    /// Add every file in `dir`, which is laid out like the `data` directory
+    /// of a data pack (`<namespace>/recipe/...`), replacing each `${NAME}`
+    /// in them with its value in `vars`.  Variables that aren't in `vars`
+    /// and JSON that doesn't parse afterwards are reported as warnings.
+    pub fn add_templates(
+        &mut self,
+        dir: impl AsRef<Path>,
+        vars: &BTreeMap<&str, String>,
+    ) -> Result<()> {
+        let dir = dir.as_ref();
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                continue;
+            }
+
+            let template = fs::read_to_string(entry.path())
+                .io_context(|| format!("Reading template {}", entry.path().display()))?;
+            let path =
+                Path::new("data").join(entry.path().strip_prefix(dir).expect("path is in dir"));
+
+            let (rendered, unknown) = substitute(&template, vars);
+            self.warnings.extend(
+                unknown
+                    .into_iter()
+                    .map(|name| format!("unknown variable ${{{}}} in {}", name, path.display())),
+            );
+            if path.extension().is_some_and(|ext| ext == "json")
+                && let Err(e) = serde_json::from_str::<serde_json::Value>(&rendered)
+            {
+                self.warnings
+                    .push(format!("{} isn't valid JSON: {}", path.display(), e));
+            }
+
+            self.files.insert(path, rendered.into_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Write the pack to `options.out_dir`, in `options.format`
+    pub fn write(&self, pack_name: &str, options: &GenerateOptions) -> Result<PackOutput> {
+        let start = Instant::now();
+        let mut sink = options.sink(pack_name)?;
+        let mut report = GenerationReport {
+            seed: options.seed,
+            warnings: self.warnings.clone(),
+            ..Default::default()
+        };
+
+        for (path, data) in &self.files {
+            sink.add_file(path, data)?;
+            report.files_written += 1;
+            report.bytes_written += data.len() as u64;
+        }
+
+        let options = GenerateOptions {
+            pack_format: self.pack_format,
+            ..options.clone()
+        };
+        finish_pack(
+            sink,
+            &self.description,
+            Value::Null,
+            &options,
+            report,
+            start,
+        )
+    }
+}
@@ -0,0 +1,151 @@
+//! Comparing two packs, for reviewing what a change to a filter did
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{GenericImage, Rgba, RgbaImage};
+
+use crate::{
+    error::{GenRpError, IoContext, Result},
+    pack_source::PackSource,
+};
+
+#[derive(Clone, Debug, Default)]
+pub struct PackDiff {
+    /// Files only in the new pack
+    pub added: Vec<PathBuf>,
+    /// Files only in the old pack
+    pub removed: Vec<PathBuf>,
+    pub changed: Vec<Change>,
+    /// Files in both packs that are the same, or images that were only
+    /// re-encoded
+    pub unchanged: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// An image with the same size and some different pixels
+    Pixels { changed: u64, total: u64 },
+    /// An image with a different size, so the pixels can't be compared
+    Resized { old: (u32, u32), new: (u32, u32) },
+    /// Any other file with different contents
+    Contents,
+}
+
+/// Compare the packs (zips or directories) at `old` and `new`.
+///
+/// If `images_dir` is given, a diff image is written there for each image
+/// with changed pixels, at the same path as in the pack: the old texture,
+/// the new one, and the changed pixels highlighted in red, side by side.
+pub fn diff_packs(
+    old: impl AsRef<Path>,
+    new: impl AsRef<Path>,
+    images_dir: Option<&Path>,
+) -> Result<PackDiff> {
+    let mut old = PackSource::open(old)?;
+    let mut new = PackSource::open(new)?;
+    let old_files = old.files()?;
+    let new_files = new.files()?;
+
+    let mut diff = PackDiff::default();
+    for path in &old_files {
+        if new_files.binary_search(path).is_err() {
+            diff.removed.push(path.clone());
+        }
+    }
+
+    for path in new_files {
+        if old_files.binary_search(&path).is_err() {
+            diff.added.push(path);
+            continue;
+        }
+
+        let old_data = old.read(&path)?;
+        let new_data = new.read(&path)?;
+        if old_data == new_data {
+            diff.unchanged += 1;
+            continue;
+        }
+
+        if path.extension().is_none_or(|ext| ext != "png") {
+            diff.changed.push(Change {
+                path,
+                kind: ChangeKind::Contents,
+            });
+            continue;
+        }
+
+        let decode = |data: &[u8]| {
+            image::load_from_memory(data)
+                .map(|image| image.into_rgba8())
+                .map_err(|source| GenRpError::Decode {
+                    path: path.clone(),
+                    source,
+                })
+        };
+        let old_image = decode(&old_data)?;
+        let new_image = decode(&new_data)?;
+
+        let kind = if old_image.dimensions() != new_image.dimensions() {
+            ChangeKind::Resized {
+                old: old_image.dimensions(),
+                new: new_image.dimensions(),
+            }
+        } else {
+            let changed = old_image
+                .pixels()
+                .zip(new_image.pixels())
+                .filter(|(a, b)| a != b)
+                .count() as u64;
+            if changed == 0 {
+                diff.unchanged += 1;
+                continue;
+            }
+
+            if let Some(images_dir) = images_dir {
+                let out = images_dir.join(&path);
+                let parent = out.parent().expect("path is inside images_dir");
+                fs::create_dir_all(parent)
+                    .io_context(|| format!("Creating dir {}", parent.display()))?;
+                diff_image(&old_image, &new_image).save(&out)?;
+            }
+
+            ChangeKind::Pixels {
+                changed,
+                total: old_image.width() as u64 * old_image.height() as u64,
+            }
+        };
+        diff.changed.push(Change { path, kind });
+    }
+
+    Ok(diff)
+}
+
+/// `old`, `new`, and a darkened `old` with the changed pixels in red, side by
+/// side
+fn diff_image(old: &RgbaImage, new: &RgbaImage) -> RgbaImage {
+    let (width, height) = old.dimensions();
+
+    let mask = RgbaImage::from_fn(width, height, |x, y| {
+        let a = old.get_pixel(x, y);
+        if a == new.get_pixel(x, y) {
+            Rgba([a[0] / 3, a[1] / 3, a[2] / 3, a[3].max(64)])
+        } else {
+            Rgba([255, 0, 0, 255])
+        }
+    });
+
+    let mut out = RgbaImage::new(width * 3, height);
+    out.copy_from(old, 0, 0).expect("old fits");
+    out.copy_from(new, width, 0).expect("new fits");
+    out.copy_from(&mask, width * 2, 0).expect("mask fits");
+    out
+}
@@ -0,0 +1,194 @@
+use image::{DynamicImage, Rgba};
+
+use crate::filter::Transform;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BayerSize {
+    X2,
+    X4,
+    X8,
+}
+
+impl BayerSize {
+    pub fn size(self) -> u32 {
+        match self {
+            BayerSize::X2 => 2,
+            BayerSize::X4 => 4,
+            BayerSize::X8 => 8,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dither {
+    /// Quantize each pixel on its own
+    None,
+    /// <https://en.wikipedia.org/wiki/Floyd%E2%80%93Steinberg_dithering>
+    FloydSteinberg,
+    /// Like Floyd-Steinberg, but only diffuses 3/4 of the error, which keeps
+    /// more contrast on small textures.
+    /// <https://en.wikipedia.org/wiki/Atkinson_dithering>
+    Atkinson,
+    /// Ordered dithering using a Bayer threshold matrix, `spread` is how far
+    /// (in 0-255 units) a channel may be pushed by the threshold map, and
+    /// should be roughly the distance between two quantization levels.
+    /// <https://en.wikipedia.org/wiki/Ordered_dithering>
+    Bayer { size: BayerSize, spread: i32 },
+}
+
+/// (dx, dy, numerator), the denominator is passed separately
+type Kernel = &'static [(isize, isize, i32)];
+
+const FLOYD_STEINBERG: Kernel = &[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+const ATKINSON: Kernel = &[
+    (1, 0, 1),
+    (2, 0, 1),
+    (-1, 1, 1),
+    (0, 1, 1),
+    (1, 1, 1),
+    (0, 2, 1),
+];
+
+pub fn dither(
+    image: DynamicImage,
+    method: Dither,
+    quantize: impl Fn(Rgba<i32>) -> Rgba<i32>,
+) -> DynamicImage {
+    match method {
+        Dither::None => map_pixels(image, |_, _, px| quantize(px)),
+        Dither::FloydSteinberg => error_diffusion(image, FLOYD_STEINBERG, 16, quantize),
+        Dither::Atkinson => error_diffusion(image, ATKINSON, 8, quantize),
+        Dither::Bayer { size, spread } => {
+            let size = size.size();
+            map_pixels(image, |x, y, px| {
+                let t = bayer_threshold(size, x % size, y % size);
+                let offset = ((t as f32 + 0.5) / (size * size) as f32 - 0.5) * spread as f32;
+                let offset = offset as i32;
+                quantize(Rgba([
+                    px.0[0] + offset,
+                    px.0[1] + offset,
+                    px.0[2] + offset,
+                    px.0[3],
+                ]))
+            })
+        }
+    }
+}
+
+/// Value of the `size`x`size` Bayer matrix at (`x`, `y`), in `0..size*size`
+fn bayer_threshold(size: u32, x: u32, y: u32) -> u32 {
+    if size == 1 {
+        return 0;
+    }
+    let n = size / 2;
+    4 * bayer_threshold(n, x % n, y % n) + [[0, 2], [3, 1]][(y / n) as usize][(x / n) as usize]
+}
+
+/// Each colour channel fully on or off, for 8 colours in total
+pub fn one_bit(px: Rgba<i32>) -> Rgba<i32> {
+    let on_off = |c: i32| if c >= 128 { 255 } else { 0 };
+    Rgba([on_off(px[0]), on_off(px[1]), on_off(px[2]), px[3]])
+}
+
+/// Black or white, whichever the pixel's luma is closer to
+pub fn black_white(px: Rgba<i32>) -> Rgba<i32> {
+    let luma = 0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32;
+    let c = if luma >= 127.5 { 255 } else { 0 };
+    Rgba([c, c, c, px[3]])
+}
+
+/// Reduce every texture to [`one_bit`] colour.  A [`Dither::Bayer`] spread
+/// of around 255 suits the single step between levels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OneBitColour {
+    pub dither: Dither,
+}
+
+impl Transform for OneBitColour {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        dither(image, self.dither, one_bit)
+    }
+}
+
+/// Reduce every texture to [`black_white`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OneBitMono {
+    pub dither: Dither,
+}
+
+impl Transform for OneBitMono {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        dither(image, self.dither, black_white)
+    }
+}
+
+fn map_pixels(
+    image: DynamicImage,
+    mut f: impl FnMut(u32, u32, Rgba<i32>) -> Rgba<i32>,
+) -> DynamicImage {
+    let mut image = image.into_rgba8();
+
+    for (x, y, px) in image.enumerate_pixels_mut() {
+        let new = f(
+            x,
+            y,
+            Rgba([px.0[0] as _, px.0[1] as _, px.0[2] as _, px.0[3] as _]),
+        );
+        px.0[0] = new.0[0].clamp(0, 255) as u8;
+        px.0[1] = new.0[1].clamp(0, 255) as u8;
+        px.0[2] = new.0[2].clamp(0, 255) as u8;
+        px.0[3] = new.0[3].clamp(0, 255) as u8;
+    }
+
+    image.into()
+}
+
+fn error_diffusion(
+    image: DynamicImage,
+    kernel: Kernel,
+    denominator: i32,
+    quantize: impl Fn(Rgba<i32>) -> Rgba<i32>,
+) -> DynamicImage {
+    let mut image = image.into_rgba8();
+
+    let (width, height) = image.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    // the error is spread in floats, as integer division would drop most of
+    // it for the small weights of the kernels
+    let mut px: Vec<[f32; 4]> = image.pixels().map(|p| p.0.map(|c| c as f32)).collect();
+
+    for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
+        let old = px[y * width + x];
+        let new = quantize(Rgba(old.map(|c| c.round() as i32)))
+            .0
+            .map(|c| c as f32);
+        px[y * width + x] = new;
+        let quant = std::array::from_fn::<_, 4, _>(|c| old[c] - new[c]);
+
+        let mut add = |dx, dy, numerator| {
+            let x = x.checked_add_signed(dx)?;
+            if x >= width {
+                return None;
+            };
+            let y = y.checked_add_signed(dy)?;
+            if y >= height {
+                return None;
+            };
+            let a = &mut px[y * width + x];
+            for c in 0..4 {
+                a[c] += quant[c] * numerator as f32 / denominator as f32;
+            }
+            Some(())
+        };
+
+        for &(dx, dy, numerator) in kernel {
+            add(dx, dy, numerator);
+        }
+    }
+
+    image.pixels_mut().zip(px).for_each(|(old, new)| {
+        old.0 = new.map(|c| c.round().clamp(0., 255.) as u8);
+    });
+
+    image.into()
+}
@@ -0,0 +1,123 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+use ::reqwest::{StatusCode, header::RANGE};
+use reqwest::blocking as reqwest;
+use sha1::{Digest, Sha1};
+
+use crate::error::{GenRpError, IoContext, Result};
+
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Download `url` to `dest`, verifying it against `sha1`.
+///
+/// The file is written to `<dest>.part` first, which is resumed (using a
+/// range request) if it already exists, and only renamed to `dest` once the
+/// hash matches, so `dest` is never left truncated.
+pub fn download_file(url: &str, dest: &Path, sha1: &str) -> Result<()> {
+    let part = dest.with_added_extension("part");
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let res = download_part(url, &part).and_then(|()| {
+            let actual = sha1_file(&part)?;
+            if actual != sha1 {
+                // start from scratch next time, resuming a bad file won't fix it
+                fs::remove_file(&part).io_context(|| format!("Removing {}", part.display()))?;
+                return Err(GenRpError::Sha1Mismatch {
+                    expected: sha1.to_string(),
+                    actual,
+                });
+            }
+            Ok(())
+        });
+
+        match res {
+            Ok(()) => {
+                fs::rename(&part, dest)
+                    .io_context(|| format!("Renaming {} to {}", part.display(), dest.display()))?;
+                return Ok(());
+            }
+            Err(e) if attempt < MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "Download of {} failed (attempt {}/{}), retrying in {:?}: {}",
+                    url,
+                    attempt,
+                    MAX_ATTEMPTS,
+                    backoff,
+                    e
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            Err(e) => {
+                return Err(GenRpError::Download {
+                    url: url.to_string(),
+                    attempts: MAX_ATTEMPTS,
+                    source: Box::new(e),
+                });
+            }
+        }
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+fn download_part(url: &str, part: &Path) -> Result<()> {
+    let existing = match fs::metadata(part) {
+        Ok(meta) => meta.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e).io_context(|| format!("Reading {}", part.display())),
+    };
+
+    let client = reqwest::Client::new();
+    let mut req = client.get(url);
+    if existing > 0 {
+        req = req.header(RANGE, format!("bytes={}-", existing));
+    }
+    let res = req.send()?;
+
+    let status = res.status();
+    if status == StatusCode::RANGE_NOT_SATISFIABLE && existing > 0 {
+        // the part file already holds the whole thing
+        return Ok(());
+    }
+    let mut res = res.error_for_status()?;
+
+    let mut file = if status == StatusCode::PARTIAL_CONTENT {
+        OpenOptions::new()
+            .append(true)
+            .open(part)
+            .io_context(|| format!("Opening {}", part.display()))?
+    } else {
+        // server ignored the range, so start over
+        File::create(part).io_context(|| format!("Creating {}", part.display()))?
+    };
+
+    io::copy(&mut res, &mut file).io_context(|| format!("Writing {}", part.display()))?;
+
+    Ok(())
+}
+
+/// Hex-encoded SHA-1 of the file at `path`
+pub fn sha1_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).io_context(|| format!("Opening {}", path.display()))?;
+    let mut hasher = Sha1::new();
+    let mut buf = [0; 8192];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .io_context(|| format!("Reading {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
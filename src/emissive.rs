@@ -0,0 +1,173 @@
+//! "Glowing" packs using OptiFine's emissive textures: the bright pixels of
+//! some block and item textures (ores, torches, lanterns...) are copied to a
+//! `<texture>_e.png` next to them, which OptiFine draws over the texture at
+//! full brightness, so they glow in the dark.  The pack only adds files, so
+//! it can go on top of any other pack.
+
+use std::{fs, path::Path, time::Instant};
+
+use image::{ImageReader, Rgba, RgbaImage};
+use serde_json::json;
+use walkdir::WalkDir;
+
+use crate::{
+    GenerateOptions, GenerationReport, PackOutput,
+    error::{GenRpError, IoContext, Result},
+    filter::{luma, name_has_word, texture_category},
+    finish_pack,
+    optimize::encode_png,
+    path_in_pack,
+};
+
+/// Added to the name of each texture for its emissive one
+const SUFFIX: &str = "_e";
+const PROPERTIES: &str = "assets/minecraft/optifine/emissive.properties";
+
+/// Textures with these in their names glow, for when none are picked
+pub const DEFAULT_KEYWORDS: &[&str] = &[
+    "ore",
+    "torch",
+    "lantern",
+    "glowstone",
+    "lava",
+    "fire",
+    "magma",
+    "shroomlight",
+    "froglight",
+    "redstone",
+    "beacon",
+    "end_rod",
+    "glow",
+    "candle",
+    "amethyst",
+];
+
+/// Which textures glow, and which of their pixels
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmissiveOptions {
+    /// Pixels at least this bright (luma, from 0 to 1) glow
+    pub threshold: f32,
+    /// Block and item textures glow if one of these is in their name, as
+    /// whole `_`-separated words (`ore` is in `deepslate_iron_ore`, but not
+    /// in `spore_blossom`)
+    pub keywords: Vec<String>,
+}
+
+impl Default for EmissiveOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.7,
+            keywords: DEFAULT_KEYWORDS.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+}
+
+impl EmissiveOptions {
+    /// Whether the texture at `path` (in the pack) can glow
+    pub fn matches(&self, path: &Path) -> bool {
+        if !matches!(texture_category(path), Some("block" | "item"))
+            || path.extension().is_none_or(|ext| ext != "png")
+        {
+            return false;
+        }
+        let is_emissive = path
+            .file_stem()
+            .is_some_and(|stem| stem.to_string_lossy().ends_with(SUFFIX));
+        !is_emissive && name_has_word(path, &self.keywords)
+    }
+
+    /// The pixels of `image` that glow, with the rest transparent, or
+    /// nothing if none do
+    pub fn emissive(&self, image: &RgbaImage) -> Option<RgbaImage> {
+        let mut any = false;
+        let out = RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+            let px = *image.get_pixel(x, y);
+            if px[3] > 0 && luma(px) >= self.threshold {
+                any = true;
+                px
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        any.then_some(out)
+    }
+}
+
+/// Write a pack with the emissive textures of every texture
+/// [`EmissiveOptions::matches`] and OptiFine's `emissive.properties`.
+/// Animated textures keep their animation.
+pub fn generate_emissive_pack(
+    pack_name: &str,
+    description: &str,
+    emissive: &EmissiveOptions,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut sink = options.sink(pack_name)?;
+    let mut report = GenerationReport {
+        seed: options.seed,
+        ..Default::default()
+    };
+
+    let mut glowing = 0;
+    for entry in WalkDir::new(&options.textures_dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = path_in_pack(&options.textures_dir, &entry);
+        if !entry.file_type().is_file() || !emissive.matches(&path) {
+            continue;
+        }
+        if options.cancelled() {
+            return Err(GenRpError::Cancelled);
+        }
+
+        let image = match ImageReader::open(entry.path())
+            .io_context(|| format!("Opening {}", entry.path().display()))?
+            .decode()
+        {
+            Ok(image) => image.into_rgba8(),
+            Err(e) => {
+                options.on_error.handle(&mut report, &path, e)?;
+                continue;
+            }
+        };
+        let Some(glow) = emissive.emissive(&image) else {
+            continue;
+        };
+
+        let stem = path
+            .file_stem()
+            .expect("matched a file name")
+            .to_string_lossy();
+        let glow_path = path.with_file_name(format!("{}{}.png", stem, SUFFIX));
+        let data = encode_png(&glow.into(), options.optimize)?;
+        sink.add_file(&glow_path, &data)?;
+        report.files_written += 1;
+        report.bytes_written += data.len() as u64;
+        glowing += 1;
+
+        let mcmeta = entry.path().with_extension("png.mcmeta");
+        if mcmeta.exists() {
+            let data = fs::read(&mcmeta).io_context(|| format!("Reading {}", mcmeta.display()))?;
+            sink.add_file(&glow_path.with_extension("png.mcmeta"), &data)?;
+            report.files_written += 1;
+            report.bytes_written += data.len() as u64;
+        }
+    }
+
+    if glowing == 0 {
+        report.warnings.push(format!(
+            "no texture has pixels brighter than {}, the pack is empty",
+            emissive.threshold
+        ));
+    }
+    let properties = format!("suffix.emissive={}\n", SUFFIX);
+    sink.add_file(Path::new(PROPERTIES), properties.as_bytes())?;
+    report.files_written += 1;
+    report.bytes_written += properties.len() as u64;
+
+    let transform = json!({
+        "emissive": emissive.keywords,
+        "threshold": emissive.threshold,
+    });
+    finish_pack(sink, description, transform, options, report, start)
+}
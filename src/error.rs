@@ -0,0 +1,110 @@
+use std::{io, path::PathBuf};
+
+use reqwest::StatusCode;
+
+pub type Result<T, E = GenRpError> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GenRpError {
+    #[error("network request failed")]
+    Network(#[from] reqwest::Error),
+    #[error("{context}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("decoding image {}", path.display())]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+    #[error("reading or writing image")]
+    Image(#[from] image::ImageError),
+    #[error("encoding indexed PNG")]
+    Png(#[from] png::EncodingError),
+    #[error("optimising PNG")]
+    Optimize(#[from] oxipng::PngError),
+    #[error("reading or writing zip")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("parsing json")]
+    Json(#[from] serde_json::Error),
+    #[error("walking texture directory")]
+    Walk(#[from] walkdir::Error),
+    #[error("unknown version id: {0}")]
+    UnknownVersion(String),
+    #[error("invalid client jar: {0}")]
+    InvalidJar(String),
+    #[error("SHA-1 mismatch (expected {expected}, got {actual})")]
+    Sha1Mismatch { expected: String, actual: String },
+    #[error("downloading {url} failed after {attempts} attempts")]
+    Download {
+        url: String,
+        attempts: u32,
+        #[source]
+        source: Box<GenRpError>,
+    },
+    #[error("{}: {reason}", path.display())]
+    File { path: PathBuf, reason: String },
+    #[error("invalid resource pack: {0}")]
+    InvalidPack(String),
+    #[error("invalid pack name {0:?}, it must be a file name without `/`, `.` or `..`")]
+    PackName(String),
+    #[error("invalid config: {0}")]
+    Config(String),
+    #[error("invalid palette: {0}")]
+    InvalidPalette(String),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("unable to determine cache directory")]
+    NoCacheDir,
+    #[error("unable to find the .minecraft directory")]
+    NoMinecraftDir,
+    #[error("starting HTTP server")]
+    Serve(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("upload failed: {0}")]
+    Upload(String),
+    #[error("{} already exists", .0.display())]
+    AlreadyInstalled(PathBuf),
+    #[error("Modrinth request failed ({status}): {body:?}")]
+    Modrinth {
+        status: StatusCode,
+        body: Option<serde_json::Value>,
+    },
+    #[error("CurseForge request failed ({status}): {body:?}")]
+    CurseForge {
+        status: StatusCode,
+        body: Option<serde_json::Value>,
+    },
+    #[cfg(feature = "wasm")]
+    #[error("loading WASM filter {}", path.display())]
+    Wasm {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[cfg(feature = "font")]
+    #[error("invalid font {}", .0.display())]
+    InvalidFont(PathBuf),
+    #[cfg(feature = "sound")]
+    #[error("transforming sound {0}")]
+    Sound(String),
+    #[cfg(feature = "async")]
+    #[error("download task failed")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+pub(crate) trait IoContext<T> {
+    /// Like [`anyhow::Context::with_context`], but for io errors
+    fn io_context(self, f: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn io_context(self, f: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|source| GenRpError::Io {
+            context: f(),
+            source,
+        })
+    }
+}
@@ -0,0 +1,281 @@
+//! "3D Items" packs, which replace the flat models of items with ones built
+//! out of a cuboid for each run of visible pixels in the item's texture, so
+//! items look thicker when held or dropped.
+//!
+//! Only items whose models are a single layer on top of `item/generated` or
+//! `item/handheld` are replaced, which is most of them; ones with several
+//! layers (like dyed armour) keep their flat models.  The new models point at
+//! the same textures, so the pack works on top of any other.
+
+use std::{
+    collections::BTreeMap,
+    io::{BufReader, Read, Seek},
+    time::Instant,
+};
+
+use image::{GenericImageView, ImageReader, RgbaImage};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use zip::ZipArchive;
+
+use crate::{
+    GenerateOptions, GenerationReport, PackOutput,
+    error::{GenRpError, IoContext, Result},
+    finish_pack,
+    models::model_path,
+};
+
+const ITEM_MODELS_PREFIX: &str = "assets/minecraft/models/item/";
+/// Textures wider than this would make too many cuboids to be worth it
+const MAX_SIZE: u32 = 64;
+/// Pixels with less alpha than this are left out of the model
+const MIN_ALPHA: u8 = 128;
+
+/// An item model, `assets/minecraft/models/item/<item>.json`
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct ItemModel {
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub textures: BTreeMap<String, String>,
+    /// Models to use instead in some states (like a bow being pulled), in
+    /// versions before item definitions, kept as they are
+    #[serde(default)]
+    pub overrides: Option<Value>,
+}
+
+/// How the flat models of items are held, which the new models need to
+/// say themselves as they can't have `item/generated` as their parent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Hold {
+    Generated,
+    Handheld,
+}
+
+impl ItemModel {
+    /// How the item is held, and its texture, if it can be extruded
+    fn extrudable(&self) -> Option<(Hold, &str)> {
+        let hold = match self.parent.as_deref()?.trim_start_matches("minecraft:") {
+            "item/generated" => Hold::Generated,
+            "item/handheld" => Hold::Handheld,
+            _ => return None,
+        };
+        if self
+            .textures
+            .keys()
+            .any(|k| k != "layer0" && k != "particle")
+        {
+            return None;
+        }
+        Some((hold, self.textures.get("layer0")?))
+    }
+}
+
+/// The vanilla item models
+#[derive(Clone, Debug, Default)]
+pub struct ItemModels {
+    /// Item name (like `apple`) to its model
+    pub models: BTreeMap<String, ItemModel>,
+}
+
+impl ItemModels {
+    /// Read the item models from a client jar
+    pub fn from_jar(jar: impl Read + Seek) -> Result<Self> {
+        let mut dec = ZipArchive::new(BufReader::new(jar))?;
+        let mut models = Self::default();
+
+        for i in 0..dec.len() {
+            let file = dec.by_index(i)?;
+            if let Some(item) = file
+                .name()
+                .strip_prefix(ITEM_MODELS_PREFIX)
+                .and_then(|name| name.strip_suffix(".json"))
+            {
+                let item = item.to_string();
+                models.models.insert(item, serde_json::from_reader(file)?);
+            }
+        }
+
+        Ok(models)
+    }
+}
+
+/// How the extruded models are made
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExtrudeOptions {
+    /// How thick items are, in pixels of their texture (flat items are 1)
+    pub thickness: f32,
+}
+
+impl Default for ExtrudeOptions {
+    fn default() -> Self {
+        Self { thickness: 2. }
+    }
+}
+
+/// The runs of visible pixels in each row of `mask`, as `(y, x_start,
+/// x_end)` with the end exclusive
+fn runs(mask: &RgbaImage) -> Vec<(u32, u32, u32)> {
+    let visible = |x, y| mask.get_pixel(x, y)[3] >= MIN_ALPHA;
+    let mut runs = Vec::new();
+    for y in 0..mask.height() {
+        let mut x = 0;
+        while x < mask.width() {
+            if !visible(x, y) {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < mask.width() && visible(x, y) {
+                x += 1;
+            }
+            runs.push((y, start, x));
+        }
+    }
+    runs
+}
+
+/// Display transforms of `item/generated` and `item/handheld`
+fn display(hold: Hold) -> Value {
+    let mut display = json!({
+        "ground": { "rotation": [0, 0, 0], "translation": [0, 2, 0], "scale": [0.5, 0.5, 0.5] },
+        "head": { "rotation": [0, 180, 0], "translation": [0, 13, 7], "scale": [1, 1, 1] },
+        "thirdperson_righthand": { "rotation": [0, 0, 0], "translation": [0, 3, 1], "scale": [0.55, 0.55, 0.55] },
+        "firstperson_righthand": { "rotation": [0, -90, 25], "translation": [1.13, 3.2, 1.13], "scale": [0.68, 0.68, 0.68] },
+        "fixed": { "rotation": [0, 180, 0], "scale": [1, 1, 1] },
+    });
+    if hold == Hold::Handheld {
+        display["thirdperson_righthand"] = json!({ "rotation": [0, -90, 55], "translation": [0, 4, 0.5], "scale": [0.85, 0.85, 0.85] });
+        display["thirdperson_lefthand"] = json!({ "rotation": [0, 90, -55], "translation": [0, 4, 0.5], "scale": [0.85, 0.85, 0.85] });
+        display["firstperson_lefthand"] = json!({ "rotation": [0, 90, -25], "translation": [1.13, 3.2, 1.13], "scale": [0.68, 0.68, 0.68] });
+    }
+    display
+}
+
+/// A model of `texture` with a cuboid for each run of visible pixels in
+/// `mask` (the texture, or its first frame), `options.thickness` deep
+fn extruded_model(
+    mask: &RgbaImage,
+    texture: &str,
+    hold: Hold,
+    overrides: Option<&Value>,
+    options: &ExtrudeOptions,
+) -> Value {
+    let (width, height) = mask.dimensions();
+    // model space is 16 units across, whatever the size of the texture
+    let unit = 16. / width.max(height) as f32;
+    let (front, back) = (
+        8. + options.thickness * unit / 2.,
+        8. - options.thickness * unit / 2.,
+    );
+    let visible = |x: u32, y: u32| mask.get_pixel(x, y)[3] >= MIN_ALPHA;
+
+    let elements: Vec<_> = runs(mask)
+        .into_iter()
+        .map(|(y, x0, x1)| {
+            let (u0, u1) = (x0 as f32 * unit, x1 as f32 * unit);
+            let (v0, v1) = (y as f32 * unit, (y + 1) as f32 * unit);
+            let face = |uv: [f32; 4]| json!({ "uv": uv, "texture": "#layer0" });
+            let mut faces = json!({
+                "south": face([u0, v0, u1, v1]),
+                "north": face([u1, v0, u0, v1]),
+                "west": face([u0, v0, u0 + unit, v1]),
+                "east": face([u1 - unit, v0, u1, v1]),
+            });
+            // the tops and bottoms of runs are only seen where the row
+            // above or below doesn't cover them
+            if y == 0 || (x0..x1).any(|x| !visible(x, y - 1)) {
+                faces["up"] = face([u0, v0, u1, v1]);
+            }
+            if y + 1 == height || (x0..x1).any(|x| !visible(x, y + 1)) {
+                faces["down"] = face([u0, v0, u1, v1]);
+            }
+            json!({
+                "from": [u0, 16. - v1, back],
+                "to": [u1, 16. - v0, front],
+                "faces": faces,
+            })
+        })
+        .collect();
+
+    let mut model = json!({
+        "gui_light": "front",
+        "textures": { "layer0": texture, "particle": texture },
+        "elements": elements,
+        "display": display(hold),
+    });
+    if let Some(overrides) = overrides {
+        model["overrides"] = overrides.clone();
+    }
+    model
+}
+
+/// Write a pack replacing the model of each item in `models` that can be
+/// extruded, with the shapes taken from the textures in
+/// `options.textures_dir`
+pub fn generate_extruded_pack(
+    pack_name: &str,
+    description: &str,
+    models: &ItemModels,
+    extrude: &ExtrudeOptions,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut sink = options.sink(pack_name)?;
+    let mut report = GenerationReport {
+        seed: options.seed,
+        ..Default::default()
+    };
+
+    for (item, model) in &models.models {
+        if options.cancelled() {
+            return Err(GenRpError::Cancelled);
+        }
+        let Some((hold, texture)) = model.extrudable() else {
+            continue;
+        };
+        let (namespace, id) = texture.split_once(':').unwrap_or(("minecraft", texture));
+        let path = options
+            .textures_dir
+            .join(format!("assets/{}/textures/{}.png", namespace, id));
+        if !path.exists() {
+            report
+                .warnings
+                .push(format!("{} has no texture {}, skipping it", item, texture));
+            continue;
+        }
+
+        let image = match ImageReader::open(&path)
+            .io_context(|| format!("Opening {}", path.display()))?
+            .decode()
+        {
+            Ok(image) => image,
+            Err(e) => {
+                options.on_error.handle(&mut report, &path, e)?;
+                continue;
+            }
+        };
+        if image.width() > MAX_SIZE {
+            report.warnings.push(format!(
+                "{} is too big to extrude ({}x{}), skipping it",
+                texture,
+                image.width(),
+                image.height()
+            ));
+            continue;
+        }
+        // animated textures are a strip of square frames, the first one
+        // gives the shape
+        let side = image.width().min(image.height());
+        let mask = image.view(0, 0, side, side).to_image();
+
+        let model = extruded_model(&mask, texture, hold, model.overrides.as_ref(), extrude);
+        let data = serde_json::to_vec_pretty(&model)?;
+        sink.add_file(&model_path(&format!("item/{}", item)), &data)?;
+        report.files_written += 1;
+        report.bytes_written += data.len() as u64;
+    }
+
+    let transform = json!({ "extrude_items": extrude.thickness });
+    finish_pack(sink, description, transform, options, report, start)
+}
@@ -0,0 +1,1620 @@
+use std::{ops::Range, path::Path};
+
+use image::{
+    DynamicImage, GrayAlphaImage, LumaA, Rgb, Rgba, RgbaImage,
+    imageops::{self, FilterType},
+};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    blend::BlendMode,
+    colour::{
+        blackbody, hsv_to_rgb, linear_to_rgb, oklab_to_rgb, rgb_to_hsv, rgb_to_linear, rgb_to_oklab,
+    },
+    error::GenRpError,
+    palette::parse_hex,
+};
+
+/// A parameterized image transformation applied to every texture in a pack
+pub trait Transform: Send + Sync {
+    fn apply(&self, image: DynamicImage) -> DynamicImage;
+
+    /// Like [`Transform::apply`], but also given the path of the texture
+    /// inside of the pack, for transforms that treat textures differently
+    fn apply_with_path(&self, image: DynamicImage, _path: &Path) -> DynamicImage {
+        self.apply(image)
+    }
+
+    /// WGSL statements doing the same as [`Transform::apply_with_path`] to
+    /// the texture at `path`, one pixel at a time, by updating `px` (a
+    /// `vec4<f32>` with channels in `0.0..=1.0`).  Transforms that return
+    /// this are run on the GPU, see [`crate::gpu`].
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        None
+    }
+
+    /// Whether the file at `path` (inside of the pack) belongs in the pack
+    /// at all.  Packs that leave most textures out are smaller, and can be
+    /// stacked with other packs without undoing them.
+    fn includes(&self, _path: &Path) -> bool {
+        true
+    }
+
+    /// What this does and with which parameters, recorded in each pack's
+    /// [`generated.json`](crate::generated) so that it can be made again.
+    /// Transforms that can't describe themselves, like closures, are `null`.
+    fn describe(&self) -> Value {
+        Value::Null
+    }
+}
+
+impl<F> Transform for F
+where
+    F: Fn(DynamicImage) -> DynamicImage + Send + Sync,
+{
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self(image)
+    }
+}
+
+/// Rotate the hue of every pixel by `degrees`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HueRotate {
+    pub degrees: f32,
+}
+
+impl Transform for HueRotate {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+
+        for px in image.pixels_mut() {
+            let mut hsv = rgb_to_hsv(&[px[0], px[1], px[2]]);
+            hsv[0] = (hsv[0] + self.degrees).rem_euclid(360.);
+            let rgb = hsv_to_rgb(hsv);
+
+            px.0[..3].copy_from_slice(&rgb);
+        }
+
+        image.into()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        Some(format!(
+            "let hsv = rgb_to_hsv(px.rgb); px = vec4(hsv_to_rgb(vec3(hsv.x + {:?}, hsv.yz)), px.a);",
+            self.degrees
+        ))
+    }
+}
+
+/// Apply `f` to each colour channel (in `0.0..=1.0`) of every pixel
+fn map_channels(image: DynamicImage, f: impl Fn(f32) -> f32) -> DynamicImage {
+    let mut image = image.into_rgba8();
+
+    for px in image.pixels_mut() {
+        for c in 0..3 {
+            px[c] = (f(px[c] as f32 / 255.) * 255.).round().clamp(0., 255.) as u8;
+        }
+    }
+
+    image.into()
+}
+
+/// Multiply every channel by `factor`, so `< 1` darkens and `> 1` brightens
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Brightness {
+    pub factor: f32,
+}
+
+impl Transform for Brightness {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        map_channels(image, |c| c * self.factor)
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        Some(format!(
+            "px = vec4(saturate(px.rgb * {:?}), px.a);",
+            self.factor
+        ))
+    }
+}
+
+/// Scale every channel away from (`factor > 1`) or towards (`factor < 1`)
+/// mid-grey
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Contrast {
+    pub factor: f32,
+}
+
+impl Transform for Contrast {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        map_channels(image, |c| (c - 0.5) * self.factor + 0.5)
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        Some(format!(
+            "px = vec4(saturate((px.rgb - 0.5) * {:?} + 0.5), px.a);",
+            self.factor
+        ))
+    }
+}
+
+/// Raise every channel to `1 / gamma`, so `gamma > 1` brightens the
+/// mid-tones and `gamma < 1` darkens them
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Gamma {
+    pub gamma: f32,
+}
+
+impl Transform for Gamma {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        map_channels(image, |c| c.powf(1. / self.gamma))
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        Some(format!(
+            "px = vec4(saturate(pow(px.rgb, vec3({:?}))), px.a);",
+            1. / self.gamma
+        ))
+    }
+}
+
+/// Map every channel through a curve like Photoshop's, going smoothly through
+/// control points `[in, out]` (both from 0 to 255).  Each channel goes through
+/// its own curve and then `rgb`, and a curve with no points leaves the
+/// channel alone:
+///
+/// ```toml
+/// filters = [{ type = "curves", rgb = [[0, 0], [64, 48], [192, 216], [255, 255]], blue = [[0, 24], [255, 255]] }]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Curves {
+    #[serde(default)]
+    pub rgb: Vec<[f32; 2]>,
+    #[serde(default)]
+    pub red: Vec<[f32; 2]>,
+    #[serde(default)]
+    pub green: Vec<[f32; 2]>,
+    #[serde(default)]
+    pub blue: Vec<[f32; 2]>,
+}
+
+/// Each 8-bit value mapped through a monotone cubic spline through `points`
+/// (see <https://en.wikipedia.org/wiki/Monotone_cubic_interpolation>), which
+/// never overshoots between them, and is flat past the first and last.  If two
+/// points have the same input, the later one is used.
+fn curve_table(points: &[[f32; 2]]) -> [u8; 256] {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]));
+    points.reverse();
+    points.dedup_by(|a, b| a[0] == b[0]);
+    points.reverse();
+
+    let n = points.len();
+    if n == 0 {
+        return std::array::from_fn(|i| i as u8);
+    }
+    let (xs, ys): (Vec<_>, Vec<_>) = points.iter().map(|&[x, y]| (x, y)).unzip();
+
+    // slopes of the lines between points, and tangents at the points
+    let secants: Vec<_> = (0..n.saturating_sub(1))
+        .map(|k| (ys[k + 1] - ys[k]) / (xs[k + 1] - xs[k]))
+        .collect();
+    let mut tangents: Vec<_> = (0..n)
+        .map(
+            |k| match (k.checked_sub(1).map(|k| secants[k]), secants.get(k)) {
+                (None, Some(&d)) | (Some(d), None) => d,
+                (Some(d0), Some(&d1)) if d0 * d1 > 0. => (d0 + d1) / 2.,
+                _ => 0.,
+            },
+        )
+        .collect();
+    for (k, &d) in secants.iter().enumerate() {
+        if d == 0. {
+            tangents[k] = 0.;
+            tangents[k + 1] = 0.;
+            continue;
+        }
+        let (a, b) = (tangents[k] / d, tangents[k + 1] / d);
+        let s = a * a + b * b;
+        if s > 9. {
+            let t = 3. / s.sqrt();
+            tangents[k] = t * a * d;
+            tangents[k + 1] = t * b * d;
+        }
+    }
+
+    std::array::from_fn(|i| {
+        let x = i as f32;
+        let y = if x <= xs[0] {
+            ys[0]
+        } else if x >= xs[n - 1] {
+            ys[n - 1]
+        } else {
+            let k = xs.partition_point(|&px| px <= x) - 1;
+            let h = xs[k + 1] - xs[k];
+            let t = (x - xs[k]) / h;
+            let (t2, t3) = (t * t, t * t * t);
+            (2. * t3 - 3. * t2 + 1.) * ys[k]
+                + (t3 - 2. * t2 + t) * h * tangents[k]
+                + (-2. * t3 + 3. * t2) * ys[k + 1]
+                + (t3 - t2) * h * tangents[k + 1]
+        };
+        y.clamp(0., 255.).round() as u8
+    })
+}
+
+impl Transform for Curves {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+        let rgb = curve_table(&self.rgb);
+        let channels = [&self.red, &self.green, &self.blue].map(|points| curve_table(points));
+
+        for px in image.pixels_mut() {
+            for c in 0..3 {
+                px[c] = rgb[channels[c][px[c] as usize] as usize];
+            }
+        }
+
+        image.into()
+    }
+}
+
+/// Convolve every channel of `pixels` (a `width`x`height` image, row-major)
+/// with `kernel`, which is centred on each pixel.  Samples outside of the
+/// image are clamped to the nearest edge.
+pub fn convolve<const N: usize, const C: usize>(
+    pixels: &[[f32; C]],
+    width: u32,
+    height: u32,
+    kernel: &[[f32; N]; N],
+) -> Vec<[f32; C]> {
+    let (width, height) = (width as isize, height as isize);
+    let half = (N / 2) as isize;
+
+    let mut out = vec![[0.; C]; pixels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let sum = &mut out[(y * width + x) as usize];
+            for (ky, row) in kernel.iter().enumerate() {
+                for (kx, k) in row.iter().enumerate() {
+                    let sx = (x + kx as isize - half).clamp(0, width - 1);
+                    let sy = (y + ky as isize - half).clamp(0, height - 1);
+                    let px = pixels[(sy * width + sx) as usize];
+                    for c in 0..C {
+                        sum[c] += px[c] * k;
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Rec. 601 luma of an sRGB pixel, `0.0..=1.0`
+pub fn luma(px: Rgba<u8>) -> f32 {
+    (0.299 * px[0] as f32 + 0.587 * px[1] as f32 + 0.114 * px[2] as f32) / 255.
+}
+
+/// How [`Greyscale`] turns a colour into a single brightness
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LumaWeights {
+    /// `0.299 R + 0.587 G + 0.114 B`, as in [`luma`]
+    Rec601,
+    /// `0.2126 R + 0.7152 G + 0.0722 B`, like [`DynamicImage::grayscale`]
+    #[default]
+    Rec709,
+    /// The mean of the channels
+    Average,
+    /// Halfway between the brightest and darkest channel, like HSL
+    Lightness,
+}
+
+impl LumaWeights {
+    /// Brightness of `[r, g, b]`, in the same range as the channels
+    pub fn luma(self, [r, g, b]: [f32; 3]) -> f32 {
+        match self {
+            LumaWeights::Rec601 => 0.299 * r + 0.587 * g + 0.114 * b,
+            LumaWeights::Rec709 => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            LumaWeights::Average => (r + g + b) / 3.,
+            LumaWeights::Lightness => (r.max(g).max(b) + r.min(g).min(b)) / 2.,
+        }
+    }
+}
+
+/// Turn every texture grey, keeping its alpha
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Greyscale {
+    #[serde(default)]
+    pub weights: LumaWeights,
+}
+
+impl Transform for Greyscale {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = image.into_rgba8();
+        let grey = GrayAlphaImage::from_fn(image.width(), image.height(), |x, y| {
+            let px = image.get_pixel(x, y);
+            let luma = self.weights.luma([px[0], px[1], px[2]].map(|c| c as f32));
+            LumaA([luma.round().clamp(0., 255.) as u8, px[3]])
+        });
+
+        grey.into()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        let luma = match self.weights {
+            LumaWeights::Rec601 => "luma(px.rgb)",
+            LumaWeights::Rec709 => "dot(px.rgb, vec3(0.2126, 0.7152, 0.0722))",
+            LumaWeights::Average => "(px.r + px.g + px.b) / 3.0",
+            LumaWeights::Lightness => {
+                "(max(max(px.r, px.g), px.b) + min(min(px.r, px.g), px.b)) / 2.0"
+            }
+        };
+        Some(format!("px = vec4(vec3({}), px.a);", luma))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdgeOperator {
+    Sobel,
+    Laplacian,
+}
+
+const SOBEL_X: [[f32; 3]; 3] = [[-1., 0., 1.], [-2., 0., 2.], [-1., 0., 1.]];
+const SOBEL_Y: [[f32; 3]; 3] = [[-1., -2., -1.], [0., 0., 0.], [1., 2., 1.]];
+const LAPLACIAN: [[f32; 3]; 3] = [[0., 1., 0.], [1., -4., 1.], [0., 1., 0.]];
+
+/// Draw the edges of each texture in `line` on top of `background`
+#[derive(Clone, Copy, Debug)]
+pub struct Outline {
+    pub operator: EdgeOperator,
+    /// Minimum gradient magnitude (of luma or alpha, `0.0..=1.0`) for a pixel
+    /// to count as an edge
+    pub threshold: f32,
+    pub line: Rgba<u8>,
+    pub background: Rgba<u8>,
+}
+
+impl Transform for Outline {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+
+        // alpha is included so the silhouette of items gets an outline too
+        let pixels: Vec<[f32; 2]> = image
+            .pixels()
+            .map(|px| [luma(*px), px[3] as f32 / 255.])
+            .collect();
+
+        let magnitude: Vec<f32> = match self.operator {
+            EdgeOperator::Sobel => {
+                let gx = convolve(&pixels, width, height, &SOBEL_X);
+                let gy = convolve(&pixels, width, height, &SOBEL_Y);
+                gx.iter()
+                    .zip(&gy)
+                    .map(|(gx, gy)| {
+                        let luma = (gx[0] * gx[0] + gy[0] * gy[0]).sqrt();
+                        let alpha = (gx[1] * gx[1] + gy[1] * gy[1]).sqrt();
+                        luma.max(alpha)
+                    })
+                    .collect()
+            }
+            EdgeOperator::Laplacian => convolve(&pixels, width, height, &LAPLACIAN)
+                .iter()
+                .map(|g| g[0].abs().max(g[1].abs()))
+                .collect(),
+        };
+
+        for (px, magnitude) in image.pixels_mut().zip(magnitude) {
+            *px = if magnitude > self.threshold {
+                self.line
+            } else {
+                self.background
+            };
+        }
+
+        image.into()
+    }
+}
+
+/// Convolve the colour of every texture with `kernel`, leaving alpha alone
+#[derive(Clone, Copy, Debug)]
+pub struct Convolve<const N: usize> {
+    pub kernel: [[f32; N]; N],
+    /// Added to each channel after convolving, in `0..=255` units
+    pub bias: f32,
+}
+
+impl Convolve<3> {
+    pub const EMBOSS: Self = Self {
+        kernel: [[-2., -1., 0.], [-1., 1., 1.], [0., 1., 2.]],
+        bias: 0.,
+    };
+
+    pub const BOX_BLUR: Self = Self {
+        kernel: [[1. / 9.; 3]; 3],
+        bias: 0.,
+    };
+}
+
+impl Convolve<5> {
+    // https://en.wikipedia.org/wiki/Kernel_(image_processing)
+    pub const UNSHARP_MASK: Self = Self {
+        kernel: [
+            [-1. / 256., -4. / 256., -6. / 256., -4. / 256., -1. / 256.],
+            [
+                -4. / 256.,
+                -16. / 256.,
+                -24. / 256.,
+                -16. / 256.,
+                -4. / 256.,
+            ],
+            [
+                -6. / 256.,
+                -24. / 256.,
+                476. / 256.,
+                -24. / 256.,
+                -6. / 256.,
+            ],
+            [
+                -4. / 256.,
+                -16. / 256.,
+                -24. / 256.,
+                -16. / 256.,
+                -4. / 256.,
+            ],
+            [-1. / 256., -4. / 256., -6. / 256., -4. / 256., -1. / 256.],
+        ],
+        bias: 0.,
+    };
+}
+
+impl<const N: usize> Transform for Convolve<N> {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+
+        let pixels: Vec<[f32; 3]> = image
+            .pixels()
+            .map(|px| [px[0] as f32, px[1] as f32, px[2] as f32])
+            .collect();
+
+        let out = convolve(&pixels, width, height, &self.kernel);
+        for (px, new) in image.pixels_mut().zip(out) {
+            for c in 0..3 {
+                px[c] = (new[c] + self.bias).round().clamp(0., 255.) as u8;
+            }
+        }
+
+        image.into()
+    }
+}
+
+/// Add monochrome noise of up to `amount` (`0.0..=1.0`) to every pixel, the
+/// same `seed` always gives the same noise
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Grain {
+    pub amount: f32,
+    pub seed: u64,
+}
+
+impl Grain {
+    /// Check that `amount` is from 0 to 1, as a config can say anything
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if !(0. ..=1.).contains(&self.amount) {
+            return Err(GenRpError::Config(format!(
+                "grain amount must be from 0 to 1, not {}",
+                self.amount
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Transform for Grain {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        // NaN would make an empty range, which `random_range` panics on
+        let amount = if self.amount.is_nan() {
+            0.
+        } else {
+            self.amount.abs().min(1.)
+        };
+
+        for px in image.pixels_mut() {
+            let noise = rng.random_range(-amount..=amount) * 255.;
+            for c in 0..3 {
+                px[c] = (px[c] as f32 + noise).round().clamp(0., 255.) as u8;
+            }
+        }
+
+        image.into()
+    }
+}
+
+/// Smooth every texture into flat strokes, like an oil painting, while keeping
+/// edges sharp (see <https://en.wikipedia.org/wiki/Kuwahara_filter>).  Each
+/// pixel becomes the mean colour of whichever of the four `radius + 1`
+/// squares it's the corner of varies the least in brightness:
+///
+/// ```toml
+/// filters = [{ type = "kuwahara", radius = 3 }]
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Kuwahara {
+    #[serde(default = "default_kuwahara_radius")]
+    pub radius: u32,
+}
+
+fn default_kuwahara_radius() -> u32 {
+    2
+}
+
+/// Sums over rectangles of an image in constant time, from a table of the
+/// sums of everything above and to the left of each pixel
+struct SummedArea<const C: usize> {
+    width: usize,
+    sums: Vec<[f64; C]>,
+}
+
+impl<const C: usize> SummedArea<C> {
+    fn new(width: u32, height: u32, values: impl Iterator<Item = [f64; C]>) -> Self {
+        let (width, height) = (width as usize + 1, height as usize + 1);
+        let mut sums = vec![[0.; C]; width * height];
+        let mut values = values;
+        for y in 1..height {
+            let mut row = [0.; C];
+            for x in 1..width {
+                let value = values.next().expect("a value for each pixel");
+                for c in 0..C {
+                    row[c] += value[c];
+                    sums[y * width + x][c] = sums[(y - 1) * width + x][c] + row[c];
+                }
+            }
+        }
+        Self { width, sums }
+    }
+
+    /// Sum over `x0..x1` and `y0..y1`
+    fn sum(&self, x0: usize, y0: usize, x1: usize, y1: usize) -> [f64; C] {
+        let at = |x: usize, y: usize| self.sums[y * self.width + x];
+        let (a, b, c, d) = (at(x1, y1), at(x0, y1), at(x1, y0), at(x0, y0));
+        std::array::from_fn(|i| a[i] - b[i] - c[i] + d[i])
+    }
+}
+
+impl Transform for Kuwahara {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+        let r = self.radius as usize;
+        if r == 0 {
+            return image.into();
+        }
+
+        // colour weighted by alpha so transparent pixels don't bleed into
+        // the edges of items, and brightness for the variance
+        let table = SummedArea::new(
+            width,
+            height,
+            image.pixels().map(|px| {
+                let a = px[3] as f64 / 255.;
+                let l = luma(*px) as f64;
+                [
+                    px[0] as f64 * a,
+                    px[1] as f64 * a,
+                    px[2] as f64 * a,
+                    a,
+                    l * a,
+                    l * l * a,
+                ]
+            }),
+        );
+
+        let (w, h) = (width as usize, height as usize);
+        for (x, y, px) in image.enumerate_pixels_mut() {
+            let (x, y) = (x as usize, y as usize);
+            let (left, right) = (x.saturating_sub(r), (x + r + 1).min(w));
+            let (top, bottom) = (y.saturating_sub(r), (y + r + 1).min(h));
+            let quadrants = [
+                (left, top, x + 1, y + 1),
+                (x, top, right, y + 1),
+                (left, y, x + 1, bottom),
+                (x, y, right, bottom),
+            ];
+
+            let best = quadrants
+                .into_iter()
+                .map(|(x0, y0, x1, y1)| table.sum(x0, y0, x1, y1))
+                .filter(|sum| sum[3] > f64::EPSILON)
+                .min_by(|a, b| {
+                    let variance = |s: &[f64; 6]| s[5] / s[3] - (s[4] / s[3]).powi(2);
+                    variance(a).total_cmp(&variance(b))
+                });
+            if let Some(sum) = best {
+                for c in 0..3 {
+                    px[c] = (sum[c] / sum[3]).round().clamp(0., 255.) as u8;
+                }
+            }
+        }
+
+        image.into()
+    }
+}
+
+/// Look like an old tape: red and blue are shifted `chroma_shift` pixels in
+/// opposite directions, every other row is darkened by `scanlines`, and
+/// [`Grain`] of `noise` is added on top
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vhs {
+    pub chroma_shift: u32,
+    pub scanlines: f32,
+    pub noise: f32,
+    pub seed: u64,
+}
+
+impl Transform for Vhs {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let source = image.into_rgba8();
+        let width = source.width();
+        let mut image = source.clone();
+
+        for (x, y, px) in image.enumerate_pixels_mut() {
+            let left = x.saturating_sub(self.chroma_shift);
+            let right = x.saturating_add(self.chroma_shift).min(width - 1);
+            px[0] = source.get_pixel(left, y)[0];
+            px[2] = source.get_pixel(right, y)[2];
+
+            if y % 2 == 1 {
+                for c in 0..3 {
+                    px[c] = (px[c] as f32 * (1. - self.scanlines)).round() as u8;
+                }
+            }
+        }
+
+        Grain {
+            amount: self.noise,
+            seed: self.seed,
+        }
+        .apply(image.into())
+    }
+}
+
+/// Corrupt every texture: red and blue are pulled apart by up to
+/// `max_offset` pixels in opposite directions, and each row has a
+/// `probability` of starting a band of rows slid sideways.  Each texture
+/// gets its own glitches, picked from its path (or its pixels, without one)
+/// and `seed`:
+///
+/// ```toml
+/// filters = [{ type = "glitch", max_offset = 2, probability = 0.1, seed = 7 }]
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Glitch {
+    #[serde(default = "default_glitch_offset")]
+    pub max_offset: u32,
+    #[serde(default = "default_glitch_probability")]
+    pub probability: f32,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+fn default_glitch_offset() -> u32 {
+    2
+}
+
+fn default_glitch_probability() -> f32 {
+    0.1
+}
+
+impl Glitch {
+    fn glitch(&self, image: RgbaImage, rng: &mut StdRng) -> RgbaImage {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return image;
+        }
+        let max = self.max_offset as i64;
+        let (dx, dy) = (rng.random_range(-max..=max), rng.random_range(-max..=max));
+
+        // each row is read from a row slid this far, with it wrapping around
+        let mut shifts = vec![0i64; height as usize];
+        let mut y = 0;
+        while y < shifts.len() {
+            if rng.random::<f32>() >= self.probability {
+                y += 1;
+                continue;
+            }
+            let band = rng.random_range(1..=(height as usize / 8).max(1));
+            let limit = (width as i64 / 4).max(1);
+            let shift = rng.random_range(-limit..=limit);
+            for s in shifts.iter_mut().skip(y).take(band) {
+                *s = shift;
+            }
+            y += band;
+        }
+
+        let at = |x: i64, y: i64, c: usize| {
+            let y = y.clamp(0, height as i64 - 1);
+            let x = (x + shifts[y as usize]).rem_euclid(width as i64);
+            image.get_pixel(x as u32, y as u32)[c]
+        };
+        RgbaImage::from_fn(width, height, |x, y| {
+            let (x, y) = (x as i64, y as i64);
+            let x_clamped = |x: i64| x.clamp(0, width as i64 - 1);
+            Rgba([
+                at(x_clamped(x + dx), y + dy, 0),
+                at(x, y, 1),
+                at(x_clamped(x - dx), y - dy, 2),
+                at(x, y, 3),
+            ])
+        })
+    }
+}
+
+impl Transform for Glitch {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = image.into_rgba8();
+        let mut rng = StdRng::seed_from_u64(bytes_seed(image.as_raw(), self.seed));
+        self.glitch(image, &mut rng).into()
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        let mut rng = StdRng::seed_from_u64(path_seed(path, self.seed));
+        self.glitch(image.into_rgba8(), &mut rng).into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeFilter {
+    Nearest,
+    /// Average every source pixel covered by the destination pixel, which is
+    /// the same as nearest neighbour when upscaling
+    Box,
+}
+
+/// Scale every texture so that a vanilla 16x16 texture ends up
+/// `target`x`target`, keeping other sizes (GUIs, animation strips, ...)
+/// proportional
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Resize {
+    pub target: u32,
+    pub filter: ResizeFilter,
+}
+
+impl Transform for Resize {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+        let scale = |n: u32| ((n as u64 * self.target as u64) / 16).max(1) as u32;
+        let (new_width, new_height) = (scale(width), scale(height));
+
+        if (new_width, new_height) == (width, height) {
+            return image.into();
+        }
+
+        match self.filter {
+            ResizeFilter::Nearest => {
+                imageops::resize(&image, new_width, new_height, FilterType::Nearest).into()
+            }
+            ResizeFilter::Box if new_width >= width && new_height >= height => {
+                imageops::resize(&image, new_width, new_height, FilterType::Nearest).into()
+            }
+            ResizeFilter::Box => box_downscale(&image, new_width, new_height).into(),
+        }
+    }
+}
+
+fn box_downscale(image: &RgbaImage, new_width: u32, new_height: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+
+    RgbaImage::from_fn(new_width, new_height, |x, y| {
+        let x0 = x * width / new_width;
+        let x1 = ((x + 1) * width).div_ceil(new_width);
+        let y0 = y * height / new_height;
+        let y1 = ((y + 1) * height).div_ceil(new_height);
+        mean_colour(image, x0..x1, y0..y1)
+    })
+}
+
+/// Mean colour of the pixels of `image` in the rectangle, weighted by alpha
+/// so transparent pixels don't darken edges, with their mean alpha
+pub fn mean_colour(image: &RgbaImage, xs: Range<u32>, ys: Range<u32>) -> Rgba<u8> {
+    let mut sum = [0u64; 4];
+    let mut count = 0u64;
+    for sy in ys {
+        for sx in xs.clone() {
+            let px = image.get_pixel(sx, sy);
+            let a = px[3] as u64;
+            sum[0] += px[0] as u64 * a;
+            sum[1] += px[1] as u64 * a;
+            sum[2] += px[2] as u64 * a;
+            sum[3] += a;
+            count += 1;
+        }
+    }
+
+    if sum[3] == 0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    Rgba([
+        (sum[0] / sum[3]) as u8,
+        (sum[1] / sum[3]) as u8,
+        (sum[2] / sum[3]) as u8,
+        (sum[3] / count) as u8,
+    ])
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+}
+
+impl Channel {
+    fn index(self) -> usize {
+        match self {
+            Channel::Red => 0,
+            Channel::Green => 1,
+            Channel::Blue => 2,
+        }
+    }
+}
+
+/// Rearrange the colour channels, e.g. `[Blue, Green, Red]` turns RGB into
+/// BGR.  Alpha is left alone.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelSwap {
+    pub order: [Channel; 3],
+}
+
+impl Transform for ChannelSwap {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+
+        for px in image.pixels_mut() {
+            let rgb = [px[0], px[1], px[2]];
+            for (c, from) in self.order.iter().enumerate() {
+                px[c] = rgb[from.index()];
+            }
+        }
+
+        image.into()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        let [r, g, b] = self.order.map(Channel::index);
+        Some(format!("px = vec4(px[{}], px[{}], px[{}], px.a);", r, g, b))
+    }
+}
+
+/// Keep only `channel`, zeroing the other colour channels.  Alpha is left
+/// alone.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IsolateChannel {
+    pub channel: Channel,
+}
+
+impl Transform for IsolateChannel {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+
+        for px in image.pixels_mut() {
+            for c in 0..3 {
+                if c != self.channel.index() {
+                    px[c] = 0;
+                }
+            }
+        }
+
+        image.into()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        let mut mask = [0.; 3];
+        mask[self.channel.index()] = 1.;
+        Some(format!(
+            "px = vec4(px.rgb * vec3({:?}, {:?}, {:?}), px.a);",
+            mask[0], mask[1], mask[2]
+        ))
+    }
+}
+
+/// Multiply the linear light of every pixel by `gains`, scaled so that the
+/// overall brightness stays the same
+fn white_balance(image: DynamicImage, gains: [f32; 3]) -> DynamicImage {
+    // Rec. 709 luminance
+    let luminance = 0.2126 * gains[0] + 0.7152 * gains[1] + 0.0722 * gains[2];
+    let gains = gains.map(|g| g / luminance);
+
+    let mut image = image.into_rgba8();
+
+    for px in image.pixels_mut() {
+        let [r, g, b] = rgb_to_linear(&[px[0], px[1], px[2]]);
+        let rgb = linear_to_rgb([r * gains[0], g * gains[1], b * gains[2]]);
+
+        px.0[..3].copy_from_slice(&rgb);
+    }
+
+    image.into()
+}
+
+/// Light every texture as if by a black body at `kelvin`, where 6500 is
+/// neutral, lower is warmer and higher is cooler
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Temperature {
+    pub kelvin: f32,
+}
+
+impl Transform for Temperature {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let target = blackbody(self.kelvin);
+        let neutral = blackbody(6500.);
+        white_balance(image, std::array::from_fn(|c| target[c] / neutral[c]))
+    }
+}
+
+/// Shift every texture towards magenta (`green_magenta > 0`) or green
+/// (`green_magenta < 0`), usually within `-1.0..=1.0`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tint {
+    pub green_magenta: f32,
+}
+
+impl Transform for Tint {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let magenta = 2f32.powf(self.green_magenta * 0.25);
+        let green = 2f32.powf(-self.green_magenta * 0.5);
+        white_balance(image, [magenta, green, magenta])
+    }
+}
+
+/// Make every pixel black or white depending on whether its luma is above
+/// `level` (`0.0..=1.0`)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Threshold {
+    pub level: f32,
+}
+
+impl Transform for Threshold {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+
+        for px in image.pixels_mut() {
+            let c = if luma(*px) > self.level { 255 } else { 0 };
+            px.0[..3].fill(c);
+        }
+
+        image.into()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, _path: &Path) -> Option<String> {
+        Some(format!(
+            "px = vec4(vec3(select(0.0, 1.0, luma(px.rgb) > {:?})), px.a);",
+            self.level
+        ))
+    }
+}
+
+/// Map the luma of every pixel onto a gradient from `dark` to `light`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Duotone {
+    pub dark: HexColour,
+    pub light: HexColour,
+}
+
+impl Transform for Duotone {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+
+        for px in image.pixels_mut() {
+            let t = luma(*px);
+            for c in 0..3 {
+                let (dark, light) = (self.dark.0[c] as f32, self.light.0[c] as f32);
+                px[c] = (dark + (light - dark) * t).round() as u8;
+            }
+        }
+
+        image.into()
+    }
+}
+
+/// Stable 64-bit FNV-1a hash of `path` mixed with `seed`, so that a seed
+/// gives every texture its own but reproducible randomness
+pub fn path_seed(path: &Path, seed: u64) -> u64 {
+    // join with `/` ourselves so the hash is the same on every platform
+    let mut joined = String::new();
+    for (i, component) in path.iter().enumerate() {
+        if i > 0 {
+            joined.push('/');
+        }
+        joined.push_str(&component.to_string_lossy());
+    }
+    bytes_seed(joined.as_bytes(), seed)
+}
+
+/// Stable 64-bit FNV-1a hash of `bytes` mixed with `seed`
+pub fn bytes_seed(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash = 0xcbf29ce484222325 ^ seed;
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Rotate the hue of each texture by a random amount picked from its path
+/// and `seed`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RandomHue {
+    pub seed: u64,
+}
+
+impl Transform for RandomHue {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self.apply_with_path(image, Path::new(""))
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        let mut rng = StdRng::seed_from_u64(path_seed(path, self.seed));
+        HueRotate {
+            degrees: rng.random_range(0. ..360.),
+        }
+        .apply(image)
+    }
+
+    /// The same as it is written in a config's `filters`
+    fn describe(&self) -> Value {
+        json!({ "type": "random_hue", "seed": self.seed })
+    }
+}
+
+/// A colour written as `#rrggbb` in configs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColour(pub Rgb<u8>);
+
+impl TryFrom<String> for HexColour {
+    type Error = GenRpError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        parse_hex(&s).map(Self)
+    }
+}
+
+impl From<HexColour> for String {
+    fn from(HexColour(Rgb([r, g, b])): HexColour) -> Self {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+/// Tint textures with `colour` if `keyword` is in their name, see
+/// [`TintByName`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NameRule {
+    pub keyword: String,
+    pub colour: HexColour,
+}
+
+/// Tint each texture by its name (the file name without the extension):
+/// with the colour of the first of `rules` whose keyword is one of the `_`
+/// separated words of the name, so `ore` matches `deepslate_iron_ore` but not
+/// `spore_blossom`, or else with `hash` a colour picked from the name.
+/// Tinting is a [`BlendMode::Color`] blend, which keeps the brightness of
+/// each pixel.  Textures nothing matches are left alone.
+///
+/// ```toml
+/// filters = [{ type = "tint_by_name", rules = [
+///     { keyword = "ore", colour = "#e0b040" },
+///     { keyword = "water", colour = "#3070ff" },
+/// ] }]
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TintByName {
+    #[serde(default)]
+    pub rules: Vec<NameRule>,
+    /// Tint the textures no rule matches with a colour from a hash of their
+    /// name, so that textures with the same name are always the same colour
+    #[serde(default)]
+    pub hash: bool,
+    /// How far to go towards the tint, from 0 to 1
+    #[serde(default = "default_tint_strength")]
+    pub strength: f32,
+}
+
+fn default_tint_strength() -> f32 {
+    0.5
+}
+
+impl TintByName {
+    /// The colour the texture at `path` is tinted with, if any
+    pub fn colour_for(&self, path: &Path) -> Option<Rgb<u8>> {
+        let name = path.file_stem()?.to_string_lossy();
+        let words: Vec<_> = name.split('_').collect();
+        self.rules
+            .iter()
+            .find(|rule| words.contains(&&*rule.keyword))
+            .map(|rule| rule.colour.0)
+            .or_else(|| {
+                self.hash.then(|| {
+                    let hue = (path_seed(Path::new(&*name), 0) % 360) as f32;
+                    Rgb(hsv_to_rgb([hue, 0.6, 1.]))
+                })
+            })
+    }
+}
+
+impl Transform for TintByName {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self.apply_with_path(image, Path::new(""))
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        let Some(colour) = self.colour_for(path) else {
+            return image;
+        };
+        let target = colour.0.map(|c| c as f32 / 255.);
+
+        let mut image = image.into_rgba8();
+        for px in image.pixels_mut() {
+            let base = [px[0], px[1], px[2]].map(|c| c as f32 / 255.);
+            let rgb = BlendMode::Color.blend(base, target, self.strength);
+            for c in 0..3 {
+                px[c] = (rgb[c].clamp(0., 1.) * 255.).round() as u8;
+            }
+        }
+
+        image.into()
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, path: &Path) -> Option<String> {
+        let Some(colour) = self.colour_for(path) else {
+            return Some(String::new());
+        };
+        let [r, g, b] = colour.0.map(|c| c as f32 / 255.);
+        Some(format!(
+            "px = vec4(mix(px.rgb, set_lum(vec3({:?}, {:?}, {:?}), luma(px.rgb)), {:?}), px.a);",
+            r, g, b, self.strength
+        ))
+    }
+}
+
+/// The shape of a [`GradientOverlay`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GradientShape {
+    /// From the top of the texture to the bottom
+    #[default]
+    Vertical,
+    /// From the centre of the texture to its corners
+    Radial,
+}
+
+/// Blend a gradient from `from` to `to` over every texture (each frame of
+/// animations), for themed packs like a sunset:
+///
+/// ```toml
+/// filters = [{ type = "gradient_overlay", from = "#ffb347", to = "#6a3093", mode = "overlay" }]
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GradientOverlay {
+    pub from: HexColour,
+    pub to: HexColour,
+    #[serde(default)]
+    pub shape: GradientShape,
+    #[serde(default)]
+    pub mode: BlendMode,
+    /// How much of the blended gradient to use, from 0 to 1
+    #[serde(default = "default_gradient_opacity")]
+    pub opacity: f32,
+}
+
+fn default_gradient_opacity() -> f32 {
+    0.5
+}
+
+impl Transform for GradientOverlay {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+        let from = self.from.0.0.map(|c| c as f32 / 255.);
+        let to = self.to.0.0.map(|c| c as f32 / 255.);
+        let (cx, cy) = (
+            width.saturating_sub(1) as f32 / 2.,
+            height.saturating_sub(1) as f32 / 2.,
+        );
+        let corner = cx.hypot(cy).max(f32::EPSILON);
+
+        for (x, y, px) in image.enumerate_pixels_mut() {
+            let t = match self.shape {
+                GradientShape::Vertical => y as f32 / height.saturating_sub(1).max(1) as f32,
+                GradientShape::Radial => (x as f32 - cx).hypot(y as f32 - cy) / corner,
+            };
+            let colour = std::array::from_fn(|c| from[c] + (to[c] - from[c]) * t);
+            let base = [px[0], px[1], px[2]].map(|c| c as f32 / 255.);
+            let rgb = self.mode.blend(base, colour, self.opacity);
+            for c in 0..3 {
+                px[c] = (rgb[c].clamp(0., 1.) * 255.).round() as u8;
+            }
+        }
+
+        image.into()
+    }
+}
+
+/// Change only the colours whose hue is between `from` and `to` (in degrees,
+/// wrapping past 360 if `to` is less than `from`), like turning green leaves
+/// orange.  Greys and colours near them are left alone, as they don't really
+/// have a hue:
+///
+/// ```toml
+/// filters = [{ type = "hue_range", from = 50, to = 170, feather = 20, shift = -75 }]
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HueRange {
+    pub from: f32,
+    pub to: f32,
+    /// Degrees past the ends of the range over which the change fades out
+    #[serde(default)]
+    pub feather: f32,
+    /// Colours less saturated than this (from 0 to 1) are left alone
+    #[serde(default = "default_min_saturation")]
+    pub min_saturation: f32,
+    /// Degrees to rotate the hue of the colours in the range by
+    #[serde(default)]
+    pub shift: f32,
+    /// Multiplies the saturation of the colours in the range
+    #[serde(default = "one")]
+    pub saturation: f32,
+    /// Multiplies the brightness of the colours in the range
+    #[serde(default = "one")]
+    pub value: f32,
+}
+
+fn default_min_saturation() -> f32 {
+    0.1
+}
+
+fn one() -> f32 {
+    1.
+}
+
+impl HueRange {
+    /// How much a colour of hue `hue` is changed, from 0 to 1
+    fn weight(&self, hue: f32) -> f32 {
+        let from = self.from.rem_euclid(360.);
+        let to = self.to.rem_euclid(360.);
+        let inside = if from <= to {
+            (from..=to).contains(&hue)
+        } else {
+            hue >= from || hue <= to
+        };
+        if inside {
+            return 1.;
+        }
+        if self.feather <= 0. {
+            return 0.;
+        }
+
+        let distance = |a: f32| {
+            let d = (hue - a).rem_euclid(360.);
+            d.min(360. - d)
+        };
+        (1. - distance(from).min(distance(to)) / self.feather).max(0.)
+    }
+}
+
+impl Transform for HueRange {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+
+        for px in image.pixels_mut() {
+            let [h, s, v] = rgb_to_hsv(&[px[0], px[1], px[2]]);
+            if s < self.min_saturation {
+                continue;
+            }
+            let weight = self.weight(h);
+            if weight <= 0. {
+                continue;
+            }
+
+            let rgb = hsv_to_rgb([
+                (h + self.shift * weight).rem_euclid(360.),
+                (s * (1. + (self.saturation - 1.) * weight)).clamp(0., 1.),
+                (v * (1. + (self.value - 1.) * weight)).clamp(0., 1.),
+            ]);
+            px.0[..3].copy_from_slice(&rgb);
+        }
+
+        image.into()
+    }
+}
+
+/// Replace the colours near `from` with `to`, like turning redstone blue.
+/// Colours are compared in Oklab (see [`rgb_to_oklab`]), so `tolerance` is
+/// about how different they look, and the matched ones are moved by the
+/// difference between `from` and `to` rather than flattened, so they keep
+/// their shading:
+///
+/// ```toml
+/// filters = [{ type = "replace_colour", from = "#aa0000", to = "#0040ff", tolerance = 0.2 }]
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplaceColour {
+    pub from: HexColour,
+    pub to: HexColour,
+    /// Colours at most this far from `from` (in Oklab, where black and white
+    /// are 1 apart) are replaced
+    #[serde(default = "default_replace_tolerance")]
+    pub tolerance: f32,
+    /// How far past `tolerance` the replacement fades out over
+    #[serde(default = "default_replace_feather")]
+    pub feather: f32,
+}
+
+fn default_replace_tolerance() -> f32 {
+    0.15
+}
+
+fn default_replace_feather() -> f32 {
+    0.05
+}
+
+impl ReplaceColour {
+    /// How much a colour `distance` from `from` is replaced, from 0 to 1
+    fn weight(&self, distance: f32) -> f32 {
+        if distance <= self.tolerance {
+            1.
+        } else if self.feather <= 0. {
+            0.
+        } else {
+            (1. - (distance - self.tolerance) / self.feather).max(0.)
+        }
+    }
+}
+
+impl Transform for ReplaceColour {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let mut image = image.into_rgba8();
+        let from = rgb_to_oklab(&self.from.0.0);
+        let to = rgb_to_oklab(&self.to.0.0);
+
+        for px in image.pixels_mut() {
+            let lab = rgb_to_oklab(&[px[0], px[1], px[2]]);
+            let distance = (0..3)
+                .map(|c| (lab[c] - from[c]).powi(2))
+                .sum::<f32>()
+                .sqrt();
+            let weight = self.weight(distance);
+            if weight <= 0. {
+                continue;
+            }
+
+            let rgb = oklab_to_rgb(std::array::from_fn(|c| lab[c] + (to[c] - from[c]) * weight));
+            px.0[..3].copy_from_slice(&rgb);
+        }
+
+        image.into()
+    }
+}
+
+/// Rotate or flip each texture (or each frame of an animation, see
+/// [`crate::animation`]).  Rotating by 90 or 270 degrees only affects square
+/// textures, as it would break the layout of anything else.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipH,
+    FlipV,
+}
+
+impl Transform for Orientation {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let square = image.width() == image.height();
+        match self {
+            Orientation::Rotate90 if square => image.rotate90(),
+            Orientation::Rotate270 if square => image.rotate270(),
+            Orientation::Rotate90 | Orientation::Rotate270 => image,
+            Orientation::Rotate180 => image.rotate180(),
+            Orientation::FlipH => image.fliph(),
+            Orientation::FlipV => image.flipv(),
+        }
+    }
+}
+
+/// Give every fully transparent pixel the average colour of its nearest
+/// visible neighbours, spreading outwards from the visible edges.  The game
+/// blends transparent pixels into the mipmaps, so leaving whatever colour
+/// they had (often black, or what a filter made of it) shows up as dark
+/// fringes on leaves and grass from a distance.
+pub fn bleed_alpha(image: &mut RgbaImage) {
+    let (width, height) = image.dimensions();
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let neighbours = move |x: u32, y: u32| {
+        (-1i64..=1)
+            .flat_map(move |dy| (-1i64..=1).map(move |dx| (x as i64 + dx, y as i64 + dy)))
+            .filter(move |&(nx, ny)| {
+                (nx, ny) != (x as i64, y as i64)
+                    && (0..width as i64).contains(&nx)
+                    && (0..height as i64).contains(&ny)
+            })
+            .map(|(nx, ny)| (nx as u32, ny as u32))
+    };
+
+    let mut filled: Vec<bool> = image.pixels().map(|px| px[3] > 0).collect();
+    // filled, or already waiting to be
+    let mut queued = filled.clone();
+
+    let mut frontier = Vec::new();
+    for (x, y, _) in image.enumerate_pixels().filter(|(_, _, px)| px[3] > 0) {
+        for (nx, ny) in neighbours(x, y) {
+            if !queued[index(nx, ny)] {
+                queued[index(nx, ny)] = true;
+                frontier.push((nx, ny));
+            }
+        }
+    }
+
+    while !frontier.is_empty() {
+        let colours: Vec<_> = frontier
+            .iter()
+            .map(|&(x, y)| {
+                let mut sum = [0u32; 3];
+                let mut count = 0;
+                for (nx, ny) in neighbours(x, y).filter(|&(nx, ny)| filled[index(nx, ny)]) {
+                    let px = image.get_pixel(nx, ny);
+                    for (sum, c) in sum.iter_mut().zip(&px.0[..3]) {
+                        *sum += *c as u32;
+                    }
+                    count += 1;
+                }
+                sum.map(|sum| (sum / count) as u8)
+            })
+            .collect();
+
+        let mut next = Vec::new();
+        for (&(x, y), rgb) in frontier.iter().zip(colours) {
+            image.get_pixel_mut(x, y).0[..3].copy_from_slice(&rgb);
+            filled[index(x, y)] = true;
+            for (nx, ny) in neighbours(x, y) {
+                if !queued[index(nx, ny)] {
+                    queued[index(nx, ny)] = true;
+                    next.push((nx, ny));
+                }
+            }
+        }
+        frontier = next;
+    }
+}
+
+/// Whether `path` (inside of a pack) is under `assets/*/textures/<dir>`
+fn in_textures_dir(path: &Path, dir: &str) -> bool {
+    let mut components = path.iter();
+    components.next().is_some_and(|c| c == "assets")
+        && components.nth(1).is_some_and(|c| c == "textures")
+        && components.next().is_some_and(|c| c == dir)
+}
+
+/// The kind of texture at `path` (inside of a pack), which is the directory
+/// under `assets/*/textures`, like `block`, `item` or `entity`
+pub fn texture_category(path: &Path) -> Option<&str> {
+    let mut components = path.iter();
+    if components.next()? != "assets" || components.nth(1)? != "textures" {
+        return None;
+    }
+    let category = components.next()?;
+    // a file straight in `textures` doesn't have one
+    components.next()?;
+    category.to_str()
+}
+
+/// Whether one of `words` is a whole `_`-separated word (or several, like
+/// `end_rod`) of the name of the file at `path` (without its extensions, so
+/// `.png.mcmeta` files go with their textures), so `ore` is in
+/// `deepslate_iron_ore` but not in `spore_blossom`
+pub fn name_has_word(path: &Path, words: &[impl AsRef<str>]) -> bool {
+    let Some(name) = path.file_name().and_then(|s| s.to_str()) else {
+        return false;
+    };
+    let stem = format!("_{}_", name.split('.').next().unwrap_or(name));
+    words
+        .iter()
+        .any(|word| stem.contains(&format!("_{}_", word.as_ref())))
+}
+
+/// Whether `path` (inside of a pack) is a font texture
+pub fn is_font(path: &Path) -> bool {
+    in_textures_dir(path, "font")
+}
+
+/// Whether `path` (inside of a pack) is a biome colormap, like
+/// `grass.png` or `foliage.png`
+pub fn is_colormap(path: &Path) -> bool {
+    in_textures_dir(path, "colormap")
+}
+
+/// Apply the inner transform to everything except font textures, since
+/// filters like Invert or 1-bit make the in-game font unreadable
+#[derive(Clone, Copy)]
+pub struct ProtectFonts<'a>(pub &'a dyn Transform);
+
+impl Transform for ProtectFonts<'_> {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self.0.apply(image)
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        if is_font(path) {
+            image
+        } else {
+            self.0.apply_with_path(image, path)
+        }
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, path: &Path) -> Option<String> {
+        if is_font(path) {
+            None
+        } else {
+            self.0.wgsl(path)
+        }
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        self.0.includes(path)
+    }
+
+    fn describe(&self) -> Value {
+        json!({ "protect_fonts": true, "transform": self.0.describe() })
+    }
+}
+
+/// Only include the textures in `categories` (like `item`, see
+/// [`texture_category`]) in the pack, transformed by `inner`.  The game
+/// takes everything else from the packs below it.
+#[derive(Clone, Copy)]
+pub struct OnlyCategories<'a> {
+    pub categories: &'a [String],
+    pub inner: &'a dyn Transform,
+}
+
+impl Transform for OnlyCategories<'_> {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self.inner.apply(image)
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        self.inner.apply_with_path(image, path)
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, path: &Path) -> Option<String> {
+        self.inner.wgsl(path)
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        texture_category(path).is_some_and(|category| self.categories.iter().any(|c| c == category))
+            && self.inner.includes(path)
+    }
+
+    fn describe(&self) -> Value {
+        json!({ "only": self.categories, "transform": self.inner.describe() })
+    }
+}
@@ -0,0 +1,246 @@
+//! Font packs, either with the vanilla font textures run through a filter or
+//! with a TrueType font rendered into a bitmap font.
+//!
+//! Rendering TrueType fonts is only available with the `font` feature.
+
+use std::time::Instant;
+#[cfg(feature = "font")]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::ImageReader;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::{
+    GenerateOptions, GenerationReport, PackOutput,
+    error::{IoContext, Result},
+    filter::Transform,
+    finish_pack,
+    optimize::encode_png,
+};
+
+/// Where the font textures are, inside of a pack
+const FONT_TEXTURES: &str = "assets/minecraft/textures/font";
+
+/// Write a pack with only the vanilla font textures in
+/// `options.textures_dir`, transformed by `f`.  The vanilla font definitions
+/// still point at them, so no `default.json` is needed.
+pub fn generate_filtered_font_pack(
+    pack_name: &str,
+    description: &str,
+    f: &dyn Transform,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut sink = options.sink(pack_name)?;
+    let mut report = GenerationReport {
+        seed: options.seed,
+        ..Default::default()
+    };
+
+    let fonts_dir = options.textures_dir.join(FONT_TEXTURES);
+    for entry in WalkDir::new(&fonts_dir).sort_by_file_name() {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "png") {
+            continue;
+        }
+
+        let in_pack = path
+            .strip_prefix(&options.textures_dir)
+            .expect("path is in textures_dir");
+        let image = match ImageReader::open(path)
+            .io_context(|| format!("Opening {}", path.display()))?
+            .decode()
+        {
+            Ok(image) => image,
+            Err(e) => {
+                options.on_error.handle(&mut report, in_pack, e)?;
+                continue;
+            }
+        };
+
+        let data = encode_png(&f.apply_with_path(image, in_pack), options.optimize)?;
+        sink.add_file(in_pack, &data)?;
+        report.files_written += 1;
+        report.bytes_written += data.len() as u64;
+    }
+
+    finish_pack(sink, description, f.describe(), options, report, start)
+}
+
+/// `assets/minecraft/font/default.json`,
+/// see <https://minecraft.wiki/w/Font#Providers>
+#[derive(Clone, Debug, Serialize)]
+pub struct FontDefinition {
+    pub providers: Vec<FontProvider>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FontProvider {
+    Bitmap {
+        /// Like `minecraft:font/custom.png`
+        file: String,
+        height: i32,
+        ascent: i32,
+        /// One string per row of the texture, one character per glyph, with
+        /// `\0` for empty cells
+        chars: Vec<String>,
+    },
+    Space {
+        advances: std::collections::BTreeMap<char, f32>,
+    },
+    /// Another font definition, like `minecraft:include/default`
+    Reference { id: String },
+}
+
+/// A TrueType (or OpenType) font to render into a bitmap font
+#[cfg(feature = "font")]
+#[derive(Clone, Debug)]
+pub struct TtfFont {
+    pub path: PathBuf,
+    /// Size of each glyph's cell in the texture, in pixels.  The game always
+    /// shows it 8 GUI pixels tall, so anything above 8 is higher resolution.
+    pub cell_size: u32,
+    /// Keep the edges of glyphs smooth, instead of the crisp pixels of the
+    /// vanilla font
+    pub antialias: bool,
+}
+
+#[cfg(feature = "font")]
+impl TtfFont {
+    /// The first 256 code points, laid out like the vanilla `ascii.png` (16
+    /// cells by 16, the code point being `row * 16 + column`), and the
+    /// definition that goes with it.  Control characters, space and anything
+    /// the font doesn't have are left empty so the game uses its own.
+    pub fn render(&self) -> Result<(image::RgbaImage, FontDefinition)> {
+        use ab_glyph::{Font, FontVec, PxScale, ScaleFont, point};
+        use image::{Rgba, RgbaImage};
+
+        let data =
+            fs::read(&self.path).io_context(|| format!("Reading font {}", self.path.display()))?;
+        let font = FontVec::try_from_vec(data)
+            .map_err(|_| crate::error::GenRpError::InvalidFont(self.path.clone()))?;
+
+        let cell = self.cell_size.max(1);
+        let scaled = font.as_scaled(PxScale::from(cell as f32));
+        let ascent = scaled.ascent();
+
+        let mut atlas = RgbaImage::new(cell * 16, cell * 16);
+        let mut chars = Vec::with_capacity(16);
+        for row in 0..16 {
+            let mut line = String::with_capacity(16);
+            for column in 0..16 {
+                let c = char::from_u32(row * 16 + column).expect("below 256 is always valid");
+                let glyph_id = font.glyph_id(c);
+                if c.is_control() || c.is_whitespace() || glyph_id.0 == 0 {
+                    line.push('\0');
+                    continue;
+                }
+
+                let (x0, y0) = (column * cell, row * cell);
+                let glyph = glyph_id.with_scale_and_position(scaled.scale(), point(0., ascent));
+                if let Some(outlined) = font.outline_glyph(glyph) {
+                    let bounds = outlined.px_bounds();
+                    outlined.draw(|x, y, coverage| {
+                        let x = bounds.min.x as i32 + x as i32;
+                        let y = bounds.min.y as i32 + y as i32;
+                        if !(0..cell as i32).contains(&x) || !(0..cell as i32).contains(&y) {
+                            return;
+                        }
+                        let alpha = if self.antialias {
+                            (coverage * 255.).round() as u8
+                        } else if coverage >= 0.5 {
+                            255
+                        } else {
+                            0
+                        };
+                        if alpha > 0 {
+                            atlas.put_pixel(
+                                x0 + x as u32,
+                                y0 + y as u32,
+                                Rgba([255, 255, 255, alpha]),
+                            );
+                        }
+                    });
+                }
+                line.push(c);
+            }
+            chars.push(line);
+        }
+
+        // the game shows every glyph 8 pixels tall
+        let height = 8;
+        let ascent = (ascent / cell as f32 * height as f32).round() as i32;
+        let space = scaled.h_advance(font.glyph_id(' ')) / cell as f32 * height as f32;
+
+        let definition = FontDefinition {
+            providers: vec![
+                FontProvider::Space {
+                    advances: [(' ', space.round().max(1.))].into(),
+                },
+                FontProvider::Bitmap {
+                    file: "minecraft:font/custom.png".into(),
+                    height,
+                    ascent: ascent.min(height),
+                    chars,
+                },
+                // everything else falls back to the vanilla font
+                FontProvider::Reference {
+                    id: "minecraft:include/default".into(),
+                },
+                FontProvider::Reference {
+                    id: "minecraft:include/unifont".into(),
+                },
+            ],
+        };
+
+        Ok((atlas, definition))
+    }
+}
+
+/// Write a pack that replaces the default font with `font`
+#[cfg(feature = "font")]
+pub fn generate_ttf_font_pack(
+    pack_name: &str,
+    description: &str,
+    font: &TtfFont,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut sink = options.sink(pack_name)?;
+    let mut report = GenerationReport {
+        seed: options.seed,
+        ..Default::default()
+    };
+
+    let (atlas, definition) = font.render()?;
+    let files = [
+        (
+            Path::new(FONT_TEXTURES).join("custom.png"),
+            encode_png(&atlas.into(), options.optimize)?,
+        ),
+        (
+            PathBuf::from("assets/minecraft/font/default.json"),
+            serde_json::to_vec_pretty(&definition)?,
+        ),
+    ];
+    for (path, data) in files {
+        sink.add_file(&path, &data)?;
+        report.files_written += 1;
+        report.bytes_written += data.len() as u64;
+    }
+
+    finish_pack(
+        sink,
+        description,
+        serde_json::Value::Null,
+        options,
+        report,
+        start,
+    )
+}
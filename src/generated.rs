@@ -0,0 +1,242 @@
+//! `generated.json`, written into every pack to record what made it and how,
+//! so that it can be looked into or made again later (see [`Described`]):
+//!
+//! ```json
+//! {
+//!   "generator": "gen-rp-rs 0.1.0",
+//!   "description": "§6Rotates the hue of all textures\n§3By: funnyboy_roks",
+//!   "minecraft_version": "1.21.8",
+//!   "pack_format": 64,
+//!   "seed": 0,
+//!   "transform": { "protect_fonts": true, "transform": { "builtin": "Hue Shift" } },
+//!   "bleed_alpha": false,
+//!   "optimize": "none",
+//!   "preserve_formats": false
+//! }
+//! ```
+
+use std::{fmt, fs, path::Path};
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    GenerateOptions, GenerationReport, Pack,
+    builtin::PACKS,
+    config::PackConfig,
+    error::{GenRpError, IoContext, Result},
+    filter::{OnlyCategories, ProtectFonts, RandomHue, Transform},
+    optimize::OptLevel,
+    output::OutputSink,
+    pack_source::PackSource,
+    pbr::PbrOptions,
+};
+
+/// Where the file is in a pack
+pub const GENERATED_JSON: &str = "generated.json";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Generated {
+    /// Name and version of the crate that wrote the pack
+    pub generator: String,
+    /// The pack's description, as in its `pack.mcmeta`
+    #[serde(default)]
+    pub description: String,
+    /// The Minecraft version the textures came from, if known
+    #[serde(default)]
+    pub minecraft_version: Option<String>,
+    pub pack_format: u32,
+    /// The seed given to the random transforms
+    pub seed: u64,
+    /// The pack's transform and its parameters, see
+    /// [`Transform::describe`](crate::filter::Transform::describe).  `null`
+    /// for packs that aren't made by one, or whose transform can't describe
+    /// itself.
+    #[serde(default)]
+    pub transform: Value,
+    pub bleed_alpha: bool,
+    pub optimize: OptLevel,
+    pub preserve_formats: bool,
+    /// How the LabPBR maps were made, if they were
+    #[serde(default)]
+    pub pbr: Option<PbrOptions>,
+}
+
+impl Generated {
+    pub fn new(description: &str, transform: Value, options: &GenerateOptions) -> Self {
+        Self {
+            generator: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            description: description.to_string(),
+            minecraft_version: options.minecraft_version.clone(),
+            pack_format: options.pack_format,
+            seed: options.seed,
+            transform,
+            bleed_alpha: options.bleed_alpha,
+            optimize: options.optimize,
+            preserve_formats: options.preserve_formats,
+            pbr: options.pbr,
+        }
+    }
+
+    /// Read `generated.json` from a pack (zip or directory), or a
+    /// `generated.json` on its own
+    pub fn read(pack: impl AsRef<Path>) -> Result<Self> {
+        let pack = pack.as_ref();
+        if pack.is_file() && pack.extension().is_some_and(|ext| ext == "json") {
+            let data = fs::read(pack).io_context(|| format!("Reading {}", pack.display()))?;
+            return serde_json::from_slice(&data)
+                .map_err(|e| GenRpError::InvalidPack(format!("{}: {}", pack.display(), e)));
+        }
+
+        let mut source = PackSource::open(pack)?;
+        if !source
+            .files()?
+            .iter()
+            .any(|f| f == Path::new(GENERATED_JSON))
+        {
+            return Err(GenRpError::InvalidPack(format!(
+                "{} has no {}, it wasn't generated or is from an older version",
+                pack.display(),
+                GENERATED_JSON
+            )));
+        }
+
+        let data = source.read(Path::new(GENERATED_JSON))?;
+        serde_json::from_slice(&data).map_err(|e| {
+            GenRpError::InvalidPack(format!("{}: bad {}: {}", pack.display(), GENERATED_JSON, e))
+        })
+    }
+
+    /// Add the file to a pack that's being written
+    pub(crate) fn add_to(
+        &self,
+        sink: &mut dyn OutputSink,
+        report: &mut GenerationReport,
+    ) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        sink.add_file(Path::new(GENERATED_JSON), json.as_bytes())?;
+        report.files_written += 1;
+        report.bytes_written += json.len() as u64;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Generated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "generator:        {}", self.generator)?;
+        writeln!(f, "description:      {:?}", self.description)?;
+        match &self.minecraft_version {
+            Some(version) => writeln!(f, "minecraft:        {}", version)?,
+            None => writeln!(f, "minecraft:        unknown")?,
+        }
+        writeln!(f, "pack format:      {}", self.pack_format)?;
+        writeln!(f, "seed:             {}", self.seed)?;
+        writeln!(f, "transform:        {}", self.transform)?;
+        writeln!(f, "bleed alpha:      {}", self.bleed_alpha)?;
+        writeln!(f, "optimize:         {:?}", self.optimize)?;
+        writeln!(f, "preserve formats: {}", self.preserve_formats)?;
+        match &self.pbr {
+            Some(pbr) => write!(f, "pbr:              strength {}", pbr.normal_strength),
+            None => write!(f, "pbr:              no"),
+        }
+    }
+}
+
+/// A transform rebuilt from what its [`Transform::describe`] wrote to
+/// `generated.json`, to make the pack again (for another Minecraft version,
+/// say).  Only the built-in packs, config packs and the wrappers around them
+/// can be rebuilt.
+pub enum Described {
+    Builtin(&'static Pack<'static>),
+    RandomHue(RandomHue),
+    Config(PackConfig),
+    ProtectFonts(Box<Described>),
+    Only {
+        categories: Vec<String>,
+        inner: Box<Described>,
+    },
+}
+
+impl Described {
+    pub fn parse(description: &Value) -> Result<Self> {
+        let unknown =
+            || GenRpError::InvalidPack(format!("can't rebuild the transform {}", description));
+        let Value::Object(map) = description else {
+            return Err(match description {
+                Value::Null => GenRpError::InvalidPack(
+                    "the pack's transform wasn't recorded, so it can't be made again".into(),
+                ),
+                _ => unknown(),
+            });
+        };
+        let inner = || -> Result<Box<Self>> {
+            Ok(Box::new(Self::parse(
+                map.get("transform").ok_or_else(unknown)?,
+            )?))
+        };
+
+        if let Some(name) = map.get("builtin") {
+            let name = name.as_str().ok_or_else(unknown)?;
+            let pack = PACKS.iter().find(|p| p.name == name).ok_or_else(|| {
+                GenRpError::InvalidPack(format!("there's no built-in pack called {}", name))
+            })?;
+            Ok(Self::Builtin(pack))
+        } else if map.contains_key("protect_fonts") && map.contains_key("transform") {
+            Ok(Self::ProtectFonts(inner()?))
+        } else if let Some(categories) = map.get("only") {
+            Ok(Self::Only {
+                categories: serde_json::from_value(categories.clone())?,
+                inner: inner()?,
+            })
+        } else if map.get("type").is_some_and(|t| t == "random_hue") {
+            Ok(Self::RandomHue(serde_json::from_value(
+                description.clone(),
+            )?))
+        } else if map.contains_key("filters") {
+            let mut config: PackConfig = serde_json::from_value(description.clone())?;
+            // recorded already relative to where the pack was made
+            config.resolve(Path::new(""))?;
+            Ok(Self::Config(config))
+        } else {
+            Err(unknown())
+        }
+    }
+
+    /// Run `f` with this as the transform it describes
+    fn with<R>(&self, f: impl FnOnce(&dyn Transform) -> R) -> R {
+        match self {
+            Self::Builtin(pack) => f(*pack),
+            Self::RandomHue(random_hue) => f(random_hue),
+            Self::Config(config) => f(config),
+            Self::ProtectFonts(inner) => f(&ProtectFonts(&**inner)),
+            Self::Only { categories, inner } => f(&OnlyCategories {
+                categories,
+                inner: &**inner,
+            }),
+        }
+    }
+}
+
+impl Transform for Described {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self.with(|t| t.apply(image))
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        self.with(|t| t.apply_with_path(image, path))
+    }
+
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, path: &Path) -> Option<String> {
+        self.with(|t| t.wgsl(path))
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        self.with(|t| t.includes(path))
+    }
+
+    fn describe(&self) -> Value {
+        self.with(|t| t.describe())
+    }
+}
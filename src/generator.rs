@@ -0,0 +1,910 @@
+//! Generating several packs from the same textures at once
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, TryRecvError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use image::{DynamicImage, ImageReader, RgbaImage};
+use walkdir::{DirEntry, WalkDir};
+
+#[cfg(feature = "gpu")]
+use crate::gpu::{self, Gpu};
+use crate::{
+    GenerateOptions, GenerationReport, PackMcMeta, PackOutput,
+    animation::{Animation, map_frames},
+    error::{GenRpError, Result},
+    filter::{Transform, bleed_alpha},
+    generated::Generated,
+    icon::{PACK_PNG, PackColours, SWATCH_COLOURS, pack_icon},
+    is_image,
+    optimize::encode_png,
+    output::OutputSink,
+    output_path_in_pack, path_in_pack,
+    pbr::{self, PbrOptions},
+    progress::ProgressSink,
+    shadowed_by_png,
+    timings::{self, StageTimings},
+};
+
+/// Builds many packs from the same textures in one pass.  The textures are
+/// walked and decoded once, and each entry is handed to every pack to
+/// transform and write out, on [`GenerateOptions::threads`] threads.
+///
+/// With fewer threads than packs, each thread writes several packs, taking
+/// turns with each entry.  With more, the threads left over are shared out
+/// between the packs, which transform and encode their textures in batches
+/// on them.
+pub struct Generator<'a> {
+    options: &'a GenerateOptions,
+    packs: Vec<(&'a str, &'a str, &'a dyn Transform)>,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(options: &'a GenerateOptions) -> Self {
+        Self {
+            options,
+            packs: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: &'a str, description: &'a str, transform: &'a dyn Transform) {
+        self.packs.push((name, description, transform));
+    }
+
+    /// Generate every pack, calling `progress` once for each to get
+    /// somewhere to report its progress.
+    ///
+    /// Fails as a whole if the textures can't be walked or
+    /// [`GenerateOptions::cancel`] is set before they have been, otherwise
+    /// each pack succeeds or fails on its own.
+    pub fn run(
+        &self,
+        progress: impl Fn(&str) -> Box<dyn ProgressSink> + Sync,
+    ) -> Result<Vec<(&'a str, Result<PackOutput>)>> {
+        let start = Instant::now();
+        let progress = &progress;
+        let budget = &Budget::new(self.options.memory_budget);
+        let threads = self.options.threads().max(1);
+        let groups = threads.min(self.packs.len());
+        let per_pack = (threads / self.packs.len().max(1)).max(1);
+
+        thread::scope(|s| {
+            let (senders, threads): (Vec<_>, Vec<_>) = (0..groups)
+                .map(|group| {
+                    let (tx, rx) = mpsc::sync_channel::<Arc<Queued>>(QUEUE_LEN);
+                    let thread = s.spawn(move || {
+                        let packs = (group..self.packs.len())
+                            .step_by(groups)
+                            .map(|i| (i, self.packs[i]));
+                        write_packs(packs, rx, progress, per_pack, self.options, start)
+                    });
+                    (tx, thread)
+                })
+                .unzip();
+
+            let send = |entry: Entry| {
+                let bytes = match &entry {
+                    Entry::Image(_, Ok(image), _) => image.as_bytes().len() as u64,
+                    _ => 0,
+                };
+                // waits for the slowest packs to catch up before more images
+                // are decoded
+                let queued = Arc::new(Queued {
+                    _reservation: budget.reserve(bytes),
+                    entry,
+                });
+                for tx in &senders {
+                    // a pack that has already failed stops receiving, but
+                    // the rest carry on
+                    let _ = tx.send(Arc::clone(&queued));
+                }
+            };
+            let walked = read_entries(self.options, send);
+            if walked.is_ok() {
+                send(Entry::Done);
+            }
+            drop(senders);
+
+            let mut outputs: Vec<_> = threads
+                .into_iter()
+                .flat_map(|thread| thread.join().expect("pack thread panicked"))
+                .collect();
+            walked?;
+
+            // back in the order the packs were added
+            outputs.sort_by_key(|&(i, _)| i);
+            Ok(outputs
+                .into_iter()
+                .map(|(i, res)| {
+                    let output = res.map(|output| output.expect("walk succeeded"));
+                    (self.packs[i].0, output)
+                })
+                .collect())
+        })
+    }
+}
+
+/// How many entries can be waiting for each pack before the walk blocks
+const QUEUE_LEN: usize = 64;
+
+/// An entry waiting to be written by every pack, holding on to its share of
+/// the memory budget until the last pack is done with it
+struct Queued<'a> {
+    entry: Entry,
+    _reservation: Reservation<'a>,
+}
+
+/// Limits how many bytes of decoded images are held at once
+struct Budget {
+    limit: u64,
+    used: Mutex<u64>,
+    freed: Condvar,
+}
+
+impl Budget {
+    fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Block until `bytes` fit in the budget.  An image bigger than the whole
+    /// budget is let through once nothing else is held.
+    fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        if bytes > 0 {
+            let mut used = self.used.lock().expect("budget poisoned");
+            while *used > 0 && *used + bytes > self.limit {
+                used = self.freed.wait(used).expect("budget poisoned");
+            }
+            *used += bytes;
+        }
+
+        Reservation {
+            budget: self,
+            bytes,
+        }
+    }
+}
+
+struct Reservation<'a> {
+    budget: &'a Budget,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if self.bytes > 0 {
+            *self.budget.used.lock().expect("budget poisoned") -= self.bytes;
+            self.budget.freed.notify_all();
+        }
+    }
+}
+
+type Decoded = std::result::Result<Arc<DynamicImage>, String>;
+
+fn decode(path: &Path) -> Decoded {
+    ImageReader::open(path)
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map(Arc::new)
+        .map_err(|e| e.to_string())
+}
+
+/// One entry of the textures, as handed to each pack
+enum Entry {
+    /// A directory, shown as the progress status
+    Dir(String),
+    /// An image, and how long it took to decode
+    Image(DirEntry, Decoded, Duration),
+    File(DirEntry),
+    /// Every entry has been sent
+    Done,
+}
+
+/// Walk the textures, decoding each image once, and pass every entry to
+/// `send`
+fn read_entries(options: &GenerateOptions, mut send: impl FnMut(Entry)) -> Result<()> {
+    for entry in walk(options) {
+        let entry = entry?;
+        if options.cancelled() {
+            return Err(GenRpError::Cancelled);
+        }
+
+        if entry.path().is_dir() {
+            let full_path_str = entry
+                .path()
+                .strip_prefix(&options.textures_dir)
+                .expect("path is in textures_dir")
+                .to_str()
+                .expect("all asset paths are valid utf-8");
+            send(Entry::Dir(full_path_str.to_string()));
+        } else if entry.path().extension().is_some_and(|ext| ext == "png")
+            || !options.preserve_formats && is_image(entry.path())
+        {
+            let start = Instant::now();
+            let image = decode(entry.path());
+            send(Entry::Image(entry, image, start.elapsed()));
+        } else {
+            send(Entry::File(entry));
+        }
+    }
+
+    Ok(())
+}
+
+/// How many images each of a pack's threads is given at once, see
+/// [`Worker::add_images`]
+const IMAGES_PER_THREAD: usize = 8;
+
+/// Write `packs` (with their place in the [`Generator`]) from the entries
+/// received on `rx`, with `per_pack` threads for each to transform and encode
+/// textures on.  Packs still going when the walk fails are left as `Ok(None)`.
+fn write_packs<'a>(
+    packs: impl Iterator<Item = (usize, (&'a str, &'a str, &'a dyn Transform))>,
+    rx: mpsc::Receiver<Arc<Queued>>,
+    progress: &impl Fn(&str) -> Box<dyn ProgressSink>,
+    per_pack: usize,
+    options: &'a GenerateOptions,
+    start: Instant,
+) -> Vec<(usize, Result<Option<PackOutput>>)> {
+    let packs: Vec<_> = packs.collect();
+    let mut sinks: Vec<_> = packs
+        .iter()
+        .map(|&(_, (name, ..))| progress(name))
+        .collect();
+    let mut packs: Vec<_> = packs
+        .into_iter()
+        .zip(&mut sinks)
+        .map(|((index, (name, description, transform)), progress)| {
+            let span = tracing::info_span!("pack", name);
+            let (worker, result) = match span.in_scope(|| options.sink(name)) {
+                Ok(sink) => (
+                    Some(Worker::new(&mut **progress, name, transform, options, sink)),
+                    Ok(None),
+                ),
+                Err(e) => (None, Err(e)),
+            };
+            Grouped {
+                index,
+                description,
+                span,
+                worker,
+                result,
+            }
+        })
+        .collect();
+
+    let mut batch = Vec::new();
+    while packs.iter().any(|pack| pack.worker.is_some()) {
+        // batched images are written whenever nothing is waiting, so they
+        // don't hold on to the memory budget the walk is waiting for
+        let queued = match rx.try_recv() {
+            Ok(queued) => queued,
+            Err(TryRecvError::Empty) => {
+                write_batch(&mut packs, &mut batch, per_pack);
+                match rx.recv() {
+                    Ok(queued) => queued,
+                    Err(_) => break,
+                }
+            }
+            Err(TryRecvError::Disconnected) => break,
+        };
+        if options.cancelled() {
+            for pack in &mut packs {
+                if pack.worker.take().is_some() {
+                    pack.result = Err(GenRpError::Cancelled);
+                }
+            }
+            break;
+        }
+
+        match &queued.entry {
+            Entry::Image(..) if per_pack > 1 => {
+                batch.push(queued);
+                if batch.len() >= per_pack * IMAGES_PER_THREAD {
+                    write_batch(&mut packs, &mut batch, per_pack);
+                }
+            }
+            Entry::Done => {
+                write_batch(&mut packs, &mut batch, per_pack);
+                for pack in &mut packs {
+                    if let Some(worker) = pack.worker.take() {
+                        let _span = pack.span.enter();
+                        pack.result = worker.finish(pack.description, start).map(Some);
+                    }
+                }
+            }
+            entry => {
+                // keeps the files in the order they were walked in
+                write_batch(&mut packs, &mut batch, per_pack);
+                for pack in &mut packs {
+                    pack.run(|worker| worker.add(entry));
+                }
+            }
+        }
+    }
+
+    packs
+        .into_iter()
+        .map(|pack| (pack.index, pack.result))
+        .collect()
+}
+
+/// Write the images in `batch` to every pack still going, transforming and
+/// encoding them on `threads` threads
+fn write_batch(packs: &mut [Grouped], batch: &mut Vec<Arc<Queued>>, threads: usize) {
+    if batch.is_empty() {
+        return;
+    }
+    let entries: Vec<_> = batch.iter().map(|queued| &queued.entry).collect();
+    for pack in packs {
+        pack.run(|worker| worker.add_images(&entries, threads));
+    }
+    batch.clear();
+}
+
+/// One of the packs written on a thread of a [`Generator`]
+struct Grouped<'a> {
+    index: usize,
+    description: &'a str,
+    span: tracing::Span,
+    /// Taken once the pack has finished or failed
+    worker: Option<Worker<'a>>,
+    result: Result<Option<PackOutput>>,
+}
+
+impl<'a> Grouped<'a> {
+    /// Run `f` on the pack if it's still going, failing the pack if `f` does
+    fn run(&mut self, f: impl FnOnce(&mut Worker<'a>) -> Result<()>) {
+        let Some(worker) = &mut self.worker else {
+            return;
+        };
+        let _span = self.span.enter();
+        if let Err(e) = f(worker) {
+            self.worker = None;
+            self.result = Err(e);
+        }
+    }
+}
+
+/// Generate a single pack on the current thread, with
+/// [`GenerateOptions::threads`] threads to transform and encode textures on
+pub(crate) fn generate(
+    name: &str,
+    description: &str,
+    progress: &mut dyn ProgressSink,
+    f: &dyn Transform,
+    options: &GenerateOptions,
+    sink: Box<dyn OutputSink>,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut worker = Worker::new(progress, name, f, options, sink);
+    let threads = options.threads();
+
+    fn add_batch(worker: &mut Worker, batch: &mut Vec<Entry>, threads: usize) -> Result<()> {
+        let entries: Vec<_> = batch.iter().collect();
+        let res = worker.add_images(&entries, threads);
+        batch.clear();
+        res
+    }
+
+    let mut batch = Vec::new();
+    let mut res = Ok(());
+    read_entries(options, |entry| {
+        if res.is_err() {
+            return;
+        }
+        if threads > 1 && matches!(entry, Entry::Image(..)) {
+            batch.push(entry);
+            if batch.len() >= threads * IMAGES_PER_THREAD {
+                res = add_batch(&mut worker, &mut batch, threads);
+            }
+        } else {
+            // keeps the files in the order they were walked in
+            res = add_batch(&mut worker, &mut batch, threads).and_then(|()| worker.add(&entry));
+        }
+    })?;
+    res?;
+    add_batch(&mut worker, &mut batch, threads)?;
+
+    worker.finish(description, start)
+}
+
+/// Writes one pack, reporting progress as it goes
+struct Worker<'a> {
+    progress: &'a mut dyn ProgressSink,
+    writer: PackWriter<'a>,
+    count: usize,
+}
+
+impl<'a> Worker<'a> {
+    fn new(
+        progress: &'a mut dyn ProgressSink,
+        name: &'a str,
+        transform: &'a dyn Transform,
+        options: &'a GenerateOptions,
+        sink: Box<dyn OutputSink>,
+    ) -> Self {
+        Self {
+            progress,
+            writer: PackWriter::new(name, transform, options, sink),
+            count: 0,
+        }
+    }
+
+    fn set_file(&mut self, entry: &DirEntry) {
+        self.progress
+            .set_file(&path_in_pack(&self.writer.options.textures_dir, entry));
+    }
+
+    fn tick(&mut self) {
+        if self.count.is_multiple_of(32) {
+            self.progress.update(self.count);
+        }
+        self.count += 1;
+    }
+
+    fn add(&mut self, entry: &Entry) -> Result<()> {
+        if let Entry::Image(entry, ..) | Entry::File(entry) = entry {
+            self.set_file(entry);
+        }
+        self.tick();
+        match entry {
+            Entry::Dir(path) => self.progress.set_status(path),
+            Entry::Image(entry, image, decoding) => {
+                self.writer.add_image(entry, image, *decoding)?
+            }
+            Entry::File(entry) => self.writer.add_file(entry)?,
+            Entry::Done => {}
+        }
+
+        Ok(())
+    }
+
+    /// Add each of the images in `entries`, transforming and encoding them on
+    /// `threads` threads first and writing them in order
+    fn add_images(&mut self, entries: &[&Entry], threads: usize) -> Result<()> {
+        let images: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Image(entry, image, decoding) => Some((entry, image, *decoding)),
+                _ => None,
+            })
+            .collect();
+        let Some(&(first, ..)) = images.first() else {
+            return Ok(());
+        };
+        // the whole batch is rendered before any of it is counted, so say
+        // where it starts
+        self.set_file(first);
+        self.progress.update(self.count);
+        let renderer = self.writer.renderer();
+        let next = AtomicUsize::new(0);
+        let mut rendered: Vec<_> = thread::scope(|s| {
+            let workers: Vec<_> = (0..threads.min(images.len()))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut done = Vec::new();
+                        let mut timings = StageTimings::default();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(&(entry, image, decoding)) = images.get(i) else {
+                                return (done, timings);
+                            };
+                            done.push((i, renderer.render(entry, image, decoding, &mut timings)));
+                        }
+                    })
+                })
+                .collect();
+            let mut rendered = Vec::new();
+            for worker in workers {
+                let (done, timings) = worker.join().expect("render thread panicked");
+                rendered.extend(done);
+                self.writer.report.timings += timings;
+            }
+            rendered
+        });
+        rendered.sort_by_key(|&(i, _)| i);
+
+        for (_, rendered) in rendered {
+            self.tick();
+            self.writer.commit(rendered)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(self, description: &str, start: Instant) -> Result<PackOutput> {
+        let output = self.writer.finish(description, start)?;
+
+        self.progress.update(self.count);
+        self.progress.set_status(&format!(
+            "\x1b[32mDone!\x1b[0m in {:?}",
+            Duration::from_millis(output.report.duration.as_millis() as u64)
+        ));
+
+        Ok(output)
+    }
+}
+
+fn walk(options: &GenerateOptions) -> walkdir::IntoIter {
+    let mut walk = WalkDir::new(&options.textures_dir);
+    if options.reproducible {
+        walk = walk.sort_by_file_name();
+    }
+    walk.into_iter()
+}
+
+/// Transforms textures and writes them to a pack's [`OutputSink`]
+struct PackWriter<'a> {
+    name: &'a str,
+    options: &'a GenerateOptions,
+    transform: &'a dyn Transform,
+    sink: Box<dyn OutputSink>,
+    report: GenerationReport,
+    /// The transformed `pack.png`, held back to be labelled once every
+    /// texture has been written, see [`GenerateOptions::pack_icon`]
+    icon: Option<RgbaImage>,
+    colours: PackColours,
+    /// Textures waiting to be transformed on the GPU together, with the
+    /// WGSL for each
+    #[cfg(feature = "gpu")]
+    batch: Vec<(PathBuf, String, RgbaImage)>,
+    #[cfg(feature = "gpu")]
+    batch_bytes: usize,
+}
+
+impl<'a> PackWriter<'a> {
+    fn new(
+        name: &'a str,
+        transform: &'a dyn Transform,
+        options: &'a GenerateOptions,
+        sink: Box<dyn OutputSink>,
+    ) -> Self {
+        Self {
+            name,
+            options,
+            transform,
+            sink,
+            report: GenerationReport::default(),
+            icon: None,
+            colours: PackColours::default(),
+            #[cfg(feature = "gpu")]
+            batch: Vec::new(),
+            #[cfg(feature = "gpu")]
+            batch_bytes: 0,
+        }
+    }
+
+    fn renderer(&self) -> Renderer<'a> {
+        Renderer {
+            options: self.options,
+            transform: self.transform,
+        }
+    }
+
+    /// Transform the (already decoded) texture at `entry` and add it
+    fn add_image(&mut self, entry: &DirEntry, image: &Decoded, decoding: Duration) -> Result<()> {
+        let renderer = self.renderer();
+        let rendered = renderer.render(entry, image, decoding, &mut self.report.timings);
+        self.commit(rendered)
+    }
+
+    /// Add a texture once it's been transformed and encoded
+    fn commit(&mut self, rendered: Rendered) -> Result<()> {
+        match rendered {
+            Rendered::Skipped => {}
+            Rendered::Icon(icon) => self.icon = Some(icon),
+            #[cfg(feature = "gpu")]
+            Rendered::Batched(path, body, image) => {
+                self.batch_bytes += image.as_raw().len();
+                self.batch.push((path, body, image));
+                if self.batch_bytes >= gpu::MAX_BATCH {
+                    self.flush_batch()?;
+                }
+            }
+            Rendered::Encoded(encoded) => self.write(encoded)?,
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, encoded: Encoded) -> Result<()> {
+        self.report.warnings.extend(encoded.warnings);
+        self.colours.merge(encoded.colours);
+        for (path, reason) in encoded.failed {
+            self.options
+                .on_error
+                .handle(&mut self.report, &path, reason)?;
+        }
+        for (path, data) in encoded.files {
+            timings::time(&mut self.report.timings.write, || {
+                self.sink.add_file(&path, &data)
+            })?;
+            self.report.bytes_written += data.len() as u64;
+            self.report.files_written += 1;
+        }
+
+        Ok(())
+    }
+
+    fn write_image(&mut self, path: &Path, image: DynamicImage) -> Result<()> {
+        let mut encoded = Encoded::default();
+        let renderer = self.renderer();
+        renderer.encode(path, image, &mut encoded, &mut self.report.timings);
+        self.write(encoded)
+    }
+
+    /// Transform the batched textures, on the CPU if the GPU can't run
+    /// their WGSL, and write them
+    #[cfg(feature = "gpu")]
+    fn flush_batch(&mut self) -> Result<()> {
+        let mut batch = std::mem::take(&mut self.batch);
+        self.batch_bytes = 0;
+
+        let gpu = Gpu::get().expect("textures are only batched with a GPU");
+        let start = Instant::now();
+        for run in batch.chunk_by_mut(|(_, a, _), (_, b, _)| a == b) {
+            let body = run[0].1.clone();
+            let mut images: Vec<_> = run.iter_mut().map(|(_, _, image)| image).collect();
+            if !gpu.run(&body, &mut images) {
+                for (path, _, image) in run.iter_mut() {
+                    *image = self
+                        .transform
+                        .apply_with_path(DynamicImage::ImageRgba8(std::mem::take(image)), path)
+                        .into_rgba8();
+                }
+            }
+        }
+        self.report.timings.transform += start.elapsed();
+
+        for (path, _, image) in batch {
+            self.write_image(&path, DynamicImage::ImageRgba8(image))?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy the file at `entry` as-is
+    fn add_file(&mut self, entry: &DirEntry) -> Result<()> {
+        let path = path_in_pack(&self.options.textures_dir, entry);
+        if !self.transform.includes(&path) {
+            return Ok(());
+        }
+
+        let mut file = match File::open(entry.path()) {
+            Ok(file) => file,
+            Err(e) => return self.options.on_error.handle(&mut self.report, &path, e),
+        };
+        self.report.bytes_written += timings::time(&mut self.report.timings.write, || {
+            self.sink.copy_file(&path, &mut file)
+        })?;
+        self.report.files_written += 1;
+
+        Ok(())
+    }
+
+    /// Write the icon, shaders, `pack.mcmeta` and finish the pack
+    fn finish(mut self, description: &str, start: Instant) -> Result<PackOutput> {
+        #[cfg(feature = "gpu")]
+        self.flush_batch()?;
+
+        if self.options.pack_icon {
+            let palette = self.colours.palette(SWATCH_COLOURS, self.options.seed);
+            let icon = pack_icon(self.icon.as_ref(), self.name, &palette);
+            self.write_image(Path::new(PACK_PNG), icon.into())?;
+        }
+
+        let writing = Instant::now();
+        let (shaders, warnings) = self
+            .options
+            .shaders
+            .render(self.transform, self.options.seed);
+        self.report.warnings.extend(warnings);
+        for (path, shader) in shaders {
+            self.sink.add_file(path, shader.as_bytes())?;
+            self.report.files_written += 1;
+            self.report.bytes_written += shader.len() as u64;
+        }
+
+        let pack_mcmeta =
+            serde_json::to_string_pretty(&PackMcMeta::new(description, self.options.pack_format))?;
+
+        self.sink
+            .add_file(Path::new("pack.mcmeta"), pack_mcmeta.as_bytes())?;
+        self.report.files_written += 1;
+        self.report.bytes_written += pack_mcmeta.len() as u64;
+        Generated::new(description, self.transform.describe(), self.options)
+            .add_to(&mut *self.sink, &mut self.report)?;
+
+        let data = self.sink.finish()?;
+        let sha1 = data.sha1()?;
+        self.report.timings.write += writing.elapsed();
+        self.report.duration = start.elapsed();
+        self.report.seed = self.options.seed;
+
+        Ok(PackOutput {
+            data,
+            sha1,
+            report: self.report,
+        })
+    }
+}
+
+/// What a texture becomes in a pack, see [`Renderer::render`]
+enum Rendered {
+    /// Not in the pack
+    Skipped,
+    /// The transformed `pack.png`, see [`PackWriter::icon`]
+    Icon(RgbaImage),
+    /// Waiting to be transformed on the GPU, see [`PackWriter::batch`]
+    #[cfg(feature = "gpu")]
+    Batched(PathBuf, String, RgbaImage),
+    Encoded(Encoded),
+}
+
+/// Files ready to be added to a pack
+#[derive(Default)]
+struct Encoded {
+    files: Vec<(PathBuf, Vec<u8>)>,
+    /// Files that couldn't be read, transformed or encoded, and why
+    failed: Vec<(PathBuf, String)>,
+    warnings: Vec<String>,
+    colours: PackColours,
+}
+
+/// The part of a [`PackWriter`] that transforms and encodes textures, which
+/// doesn't touch the pack so it can be shared between threads
+#[derive(Clone, Copy)]
+struct Renderer<'a> {
+    options: &'a GenerateOptions,
+    transform: &'a dyn Transform,
+}
+
+impl Renderer<'_> {
+    /// Transform the (already decoded) texture at `entry` and encode it
+    fn render(
+        self,
+        entry: &DirEntry,
+        image: &Decoded,
+        decoding: Duration,
+        timings: &mut StageTimings,
+    ) -> Rendered {
+        let path = output_path_in_pack(
+            path_in_pack(&self.options.textures_dir, entry),
+            self.options,
+        );
+        let f = self.transform;
+        if !f.includes(&path) {
+            return Rendered::Skipped;
+        }
+        timings.decode += decoding;
+
+        let mut encoded = Encoded::default();
+        if shadowed_by_png(entry.path(), self.options) {
+            let source = path_in_pack(&self.options.textures_dir, entry);
+            encoded.warnings.push(format!(
+                "skipped {}: {} is already in the pack",
+                source.display(),
+                path.display()
+            ));
+            return Rendered::Encoded(encoded);
+        }
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => {
+                encoded.failed.push((path, e.clone()));
+                return Rendered::Encoded(encoded);
+            }
+        };
+
+        if self.options.pack_icon && path == Path::new(PACK_PNG) {
+            let icon = timings::time(&mut timings.transform, || {
+                f.apply_with_path(DynamicImage::clone(image), &path)
+            });
+            return Rendered::Icon(icon.into_rgba8());
+        }
+
+        #[cfg(feature = "gpu")]
+        if self.options.gpu
+            && Gpu::get().is_some()
+            && let Some(body) = f.wgsl(&path)
+        {
+            // per-pixel, so animation frames don't need separating
+            return Rendered::Batched(path, body, image.to_rgba8());
+        }
+
+        let warnings = &mut encoded.warnings;
+        let image = DynamicImage::clone(image);
+        let animation = Animation::load(entry.path()).unwrap_or_else(|e| {
+            warnings.push(format!("ignoring animation of {}: {}", path.display(), e));
+            None
+        });
+        let transforming = Instant::now();
+        let image = match animation {
+            Some(animation) => {
+                if !animation.fits(image.width(), image.height()) {
+                    warnings.push(format!(
+                        "{} doesn't divide into animation frames, transforming it whole",
+                        path.display()
+                    ));
+                }
+                map_frames(image, &animation, |frame| f.apply_with_path(frame, &path))
+            }
+            None => f.apply_with_path(image, &path),
+        };
+        timings.transform += transforming.elapsed();
+
+        self.encode(&path, image, &mut encoded, timings);
+        Rendered::Encoded(encoded)
+    }
+
+    /// Encode `image`, and its normal and specular maps if there are any, to
+    /// be written at `path`
+    fn encode(
+        self,
+        path: &Path,
+        image: DynamicImage,
+        encoded: &mut Encoded,
+        timings: &mut StageTimings,
+    ) {
+        let start = Instant::now();
+        let image = if self.options.bleed_alpha && image.color().has_alpha() {
+            let mut image = image.into_rgba8();
+            bleed_alpha(&mut image);
+            image.into()
+        } else {
+            image
+        };
+        if self.options.pack_icon {
+            encoded.colours.add(path, &image);
+        }
+        if let Some(pbr) = &self.options.pbr
+            && let Some(companions) = pbr::companion_paths(path)
+        {
+            self.encode_pbr_maps(path, &image, pbr, companions, encoded);
+        }
+
+        self.encode_png(path, &image, encoded);
+        timings.encode += start.elapsed();
+    }
+
+    /// Encode the normal and specular maps of `image`, unless the textures
+    /// already have them
+    fn encode_pbr_maps(
+        self,
+        path: &Path,
+        image: &DynamicImage,
+        options: &PbrOptions,
+        companions: [PathBuf; 2],
+        encoded: &mut Encoded,
+    ) {
+        let textures_dir = &self.options.textures_dir;
+        if companions.iter().any(|c| textures_dir.join(c).exists()) {
+            return;
+        }
+
+        let animation = Animation::load(&textures_dir.join(path)).unwrap_or(None);
+        let maps = pbr::maps(image, path, animation.as_ref(), options);
+        for (companion, map) in companions.iter().zip(maps) {
+            self.encode_png(companion, &map, encoded);
+        }
+    }
+
+    fn encode_png(self, path: &Path, image: &DynamicImage, encoded: &mut Encoded) {
+        match encode_png(image, self.options.optimize) {
+            Ok(data) => encoded.files.push((path.to_path_buf(), data)),
+            Err(e) => encoded.failed.push((path.to_path_buf(), e.to_string())),
+        }
+    }
+}
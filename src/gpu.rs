@@ -0,0 +1,262 @@
+//! Running per-pixel transforms as WGSL compute shaders, many textures at a
+//! time.
+//!
+//! Transforms opt in with [`Transform::wgsl`], and the generator gathers the
+//! textures they return it for into batches that are sent to the GPU in one
+//! dispatch.  Anything that can't run there (no adapter, a shader that
+//! doesn't compile) falls back to the CPU.
+//!
+//! Only available with the `gpu` feature.
+//!
+//! [`Transform::wgsl`]: crate::filter::Transform::wgsl
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, mpsc},
+};
+
+use image::RgbaImage;
+use lazy_static::lazy_static;
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BufferDescriptor, BufferUsages, ComputePipeline,
+    ComputePipelineDescriptor, Device, DeviceDescriptor, ErrorFilter, Instance, InstanceDescriptor,
+    MapMode, PollType, Queue, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource,
+    util::{BufferInitDescriptor, DeviceExt},
+};
+
+lazy_static! {
+    static ref GPU: Option<Gpu> = Gpu::new();
+}
+
+/// Largest batch of textures sent to the GPU at once, in bytes
+pub(crate) const MAX_BATCH: usize = 32 << 20;
+
+const WORKGROUP_SIZE: u32 = 64;
+/// Most workgroups that can be dispatched in one dimension
+const MAX_WORKGROUPS: u32 = 65535;
+
+/// Shared by every transform body, which updates `px` in place
+const PRELUDE: &str = r#"
+@group(0) @binding(0) var<storage, read_write> pixels: array<u32>;
+
+/// Hue in degrees (0-360), saturation and value, like `colour::rgb_to_hsv`
+fn rgb_to_hsv(c: vec3<f32>) -> vec3<f32> {
+    let c_max = max(max(c.r, c.g), c.b);
+    let delta = c_max - min(min(c.r, c.g), c.b);
+    var h = 0.0;
+    if delta == 0.0 {
+        h = 0.0;
+    } else if c_max == c.r {
+        h = 60.0 * ((c.g - c.b) / delta);
+    } else if c_max == c.g {
+        h = 60.0 * ((c.b - c.r) / delta + 2.0);
+    } else {
+        h = 60.0 * ((c.r - c.g) / delta + 4.0);
+    }
+    h = h - 360.0 * floor(h / 360.0);
+    var s = 0.0;
+    if c_max != 0.0 {
+        s = delta / c_max;
+    }
+    return vec3(h, s, c_max);
+}
+
+fn hsv_to_rgb(hsv: vec3<f32>) -> vec3<f32> {
+    let h = (hsv.x - 360.0 * floor(hsv.x / 360.0)) / 60.0;
+    let c = hsv.z * hsv.y;
+    let x = c * (1.0 - abs(h - 2.0 * floor(h / 2.0) - 1.0));
+    var rgb = vec3(c, 0.0, x);
+    if h < 1.0 {
+        rgb = vec3(c, x, 0.0);
+    } else if h < 2.0 {
+        rgb = vec3(x, c, 0.0);
+    } else if h < 3.0 {
+        rgb = vec3(0.0, c, x);
+    } else if h < 4.0 {
+        rgb = vec3(0.0, x, c);
+    } else if h < 5.0 {
+        rgb = vec3(x, 0.0, c);
+    }
+    return rgb + (hsv.z - c);
+}
+
+/// Rec. 601 luma, like `filter::luma`
+fn luma(c: vec3<f32>) -> f32 {
+    return dot(c, vec3(0.299, 0.587, 0.114));
+}
+
+/// `c` with its luma changed to `l`, like `blend::set_lum`
+fn set_lum(c: vec3<f32>, l: f32) -> vec3<f32> {
+    let d = c + (l - luma(c));
+    let dl = luma(d);
+    let n = min(min(d.r, d.g), d.b);
+    let x = max(max(d.r, d.g), d.b);
+    var out = d;
+    if n < 0.0 {
+        out = dl + (out - dl) * dl / (dl - n);
+    }
+    if x > 1.0 {
+        out = dl + (out - dl) * (1.0 - dl) / (x - dl);
+    }
+    return out;
+}
+"#;
+
+fn shader(body: &str) -> String {
+    format!(
+        r#"{}
+@compute @workgroup_size({})
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(num_workgroups) groups: vec3<u32>) {{
+    let i = id.x + id.y * groups.x * {}u;
+    if i >= arrayLength(&pixels) {{
+        return;
+    }}
+    var px = unpack4x8unorm(pixels[i]);
+    {}
+    pixels[i] = pack4x8unorm(px);
+}}
+"#,
+        PRELUDE, WORKGROUP_SIZE, WORKGROUP_SIZE, body
+    )
+}
+
+pub struct Gpu {
+    device: Device,
+    queue: Queue,
+    /// Compiled shaders by transform body, `None` if it failed to compile
+    pipelines: Mutex<HashMap<String, Option<ComputePipeline>>>,
+}
+
+impl Gpu {
+    /// The GPU shared by every pack, or `None` if there isn't one
+    pub fn get() -> Option<&'static Gpu> {
+        GPU.as_ref()
+    }
+
+    fn new() -> Option<Self> {
+        let instance = Instance::new(InstanceDescriptor::new_without_display_handle_from_env());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&RequestAdapterOptions::default())).ok()?;
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&DeviceDescriptor::default())).ok()?;
+
+        Some(Self {
+            device,
+            queue,
+            pipelines: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn pipeline(&self, body: &str) -> Option<ComputePipeline> {
+        let mut pipelines = self.pipelines.lock().expect("pipeline cache poisoned");
+        pipelines
+            .entry(body.to_string())
+            .or_insert_with(|| self.compile(body))
+            .clone()
+    }
+
+    fn compile(&self, body: &str) -> Option<ComputePipeline> {
+        let scope = self.device.push_error_scope(ErrorFilter::Validation);
+        let module = self.device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(shader(body).into()),
+        });
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&ComputePipelineDescriptor {
+                label: None,
+                layout: None,
+                module: &module,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            });
+
+        match pollster::block_on(scope.pop()) {
+            Some(e) => {
+                tracing::warn!(
+                    "WGSL transform failed to compile, using the CPU instead: {}",
+                    e
+                );
+                None
+            }
+            None => Some(pipeline),
+        }
+    }
+
+    /// Run the transform `body` (see [`Transform::wgsl`]) over all of
+    /// `images` in one dispatch.  Returns `false`, leaving `images` alone,
+    /// if it can't be run on the GPU.
+    ///
+    /// [`Transform::wgsl`]: crate::filter::Transform::wgsl
+    pub fn run(&self, body: &str, images: &mut [&mut RgbaImage]) -> bool {
+        let Some(pipeline) = self.pipeline(body) else {
+            return false;
+        };
+        let data: Vec<u8> = images
+            .iter()
+            .flat_map(|image| image.as_raw())
+            .copied()
+            .collect();
+        if data.is_empty() {
+            return true;
+        }
+
+        let storage = self.device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: &data,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        });
+        let readback = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: data.len() as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: storage.as_entire_binding(),
+            }],
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let groups = (data.len() as u32 / 4).div_ceil(WORKGROUP_SIZE);
+            let x = groups.min(MAX_WORKGROUPS);
+            pass.dispatch_workgroups(x, groups.div_ceil(x), 1);
+        }
+        encoder.copy_buffer_to_buffer(&storage, 0, &readback, 0, data.len() as u64);
+        self.queue.submit([encoder.finish()]);
+
+        let (tx, rx) = mpsc::channel();
+        readback.map_async(MapMode::Read, .., move |res| {
+            let _ = tx.send(res);
+        });
+        if let Err(e) = self.device.poll(PollType::wait_indefinitely()) {
+            tracing::warn!("GPU transform failed, using the CPU instead: {}", e);
+            return false;
+        }
+        let view = match rx.recv() {
+            Ok(Ok(())) => readback.get_mapped_range(..),
+            _ => return false,
+        };
+        let Ok(view) = view else {
+            return false;
+        };
+
+        let mut offset = 0;
+        for image in images.iter_mut() {
+            let len = image.as_raw().len();
+            image.copy_from_slice(&view[offset..offset + len]);
+            offset += len;
+        }
+
+        true
+    }
+}
@@ -0,0 +1,209 @@
+//! Textures printed like a newspaper or a comic: each is covered in a grid of
+//! dots, turned by `angle`, which are bigger where the texture is darker.  In
+//! colour, there's a grid of cyan, magenta, yellow and black dots, each at
+//! its own angle so they make rosettes rather than moiré:
+//!
+//! ```toml
+//! filters = [
+//!     { type = "resize", target = 64, filter = "nearest" },
+//!     { type = "halftone", cell = 4, mode = "cmyk" },
+//! ]
+//! ```
+
+use std::{collections::HashMap, f32::consts::PI};
+
+use image::{DynamicImage, Rgb, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::filter::{HexColour, Transform, luma, mean_colour};
+
+/// Whether there's one grid of `ink` dots, or one of each of the process
+/// colours
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HalftoneMode {
+    #[default]
+    Mono,
+    Cmyk,
+}
+
+/// Print every texture as dots `cell` pixels apart on `paper`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Halftone {
+    /// Pixels between the centres of dots
+    #[serde(default = "default_cell")]
+    pub cell: f32,
+    /// Degrees the grid is turned by, which is the black grid's in colour
+    #[serde(default = "default_angle")]
+    pub angle: f32,
+    #[serde(default)]
+    pub mode: HalftoneMode,
+    /// The colour of the dots in `mono`
+    #[serde(default = "default_ink")]
+    pub ink: HexColour,
+    #[serde(default = "default_paper")]
+    pub paper: HexColour,
+}
+
+fn default_cell() -> f32 {
+    4.
+}
+
+fn default_angle() -> f32 {
+    45.
+}
+
+fn default_ink() -> HexColour {
+    HexColour(Rgb([0x1a, 0x1a, 0x1a]))
+}
+
+fn default_paper() -> HexColour {
+    HexColour(Rgb([0xf0, 0xea, 0xd6]))
+}
+
+impl Default for Halftone {
+    fn default() -> Self {
+        Self {
+            cell: default_cell(),
+            angle: default_angle(),
+            mode: HalftoneMode::default(),
+            ink: default_ink(),
+            paper: default_paper(),
+        }
+    }
+}
+
+/// One grid of dots, in one ink
+struct Plate {
+    sin: f32,
+    cos: f32,
+    ink: [f32; 3],
+    /// How much of a cell of this colour is inked, from 0 to 1
+    amount: fn([f32; 3]) -> f32,
+}
+
+impl Plate {
+    fn new(angle: f32, ink: [f32; 3], amount: fn([f32; 3]) -> f32) -> Self {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Self {
+            sin,
+            cos,
+            ink,
+            amount,
+        }
+    }
+
+    /// A point of the image in cells along the turned grid
+    fn to_grid(&self, x: f32, y: f32, cell: f32) -> (f32, f32) {
+        (
+            (x * self.cos + y * self.sin) / cell,
+            (y * self.cos - x * self.sin) / cell,
+        )
+    }
+
+    /// Inverse of [`Plate::to_grid`]
+    fn to_image(&self, u: f32, v: f32, cell: f32) -> (f32, f32) {
+        let (u, v) = (u * cell, v * cell);
+        (u * self.cos - v * self.sin, u * self.sin + v * self.cos)
+    }
+}
+
+/// Cyan, magenta, yellow and key (black) from RGB
+fn cmyk([r, g, b]: [f32; 3]) -> [f32; 4] {
+    let k = 1. - r.max(g).max(b);
+    if k >= 1. {
+        return [0., 0., 0., 1.];
+    }
+    [
+        (1. - r - k) / (1. - k),
+        (1. - g - k) / (1. - k),
+        (1. - b - k) / (1. - k),
+        k,
+    ]
+}
+
+impl Halftone {
+    fn plates(&self) -> Vec<Plate> {
+        let to_f32 = |HexColour(rgb): HexColour| rgb.0.map(|c| c as f32 / 255.);
+        match self.mode {
+            HalftoneMode::Mono => vec![Plate::new(self.angle, to_f32(self.ink), |rgb| {
+                let [r, g, b] = rgb.map(|c| (c * 255.).round() as u8);
+                1. - luma(Rgba([r, g, b, 255]))
+            })],
+            // the usual screen angles, turned so black is at `angle`
+            HalftoneMode::Cmyk => vec![
+                Plate::new(self.angle - 30., [0., 1., 1.], |rgb| cmyk(rgb)[0]),
+                Plate::new(self.angle + 30., [1., 0., 1.], |rgb| cmyk(rgb)[1]),
+                Plate::new(self.angle - 45., [1., 1., 0.], |rgb| cmyk(rgb)[2]),
+                Plate::new(self.angle, [0., 0., 0.], |rgb| cmyk(rgb)[3]),
+            ],
+        }
+    }
+}
+
+impl Transform for Halftone {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = image.into_rgba8();
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return image.into();
+        }
+        let cell = self.cell.max(1.);
+        let plates = self.plates();
+        let paper = self.paper.0.0.map(|c| c as f32 / 255.);
+
+        // the mean colour of the texture around the centre of a cell
+        let sample = |cx: f32, cy: f32| {
+            let range = |c: f32, size: u32| {
+                let start = ((c - cell / 2.).floor().max(0.) as u32).min(size - 1);
+                let end = ((c + cell / 2.).ceil().max(0.) as u32).clamp(start + 1, size);
+                start..end
+            };
+            mean_colour(&image, range(cx, width), range(cy, height))
+        };
+        let mut amounts: HashMap<(usize, i64, i64), f32> = HashMap::new();
+
+        let mut out = RgbaImage::new(width, height);
+        for (x, y, px) in out.enumerate_pixels_mut() {
+            let alpha = image.get_pixel(x, y)[3];
+            if alpha == 0 {
+                continue;
+            }
+            let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+
+            let mut rgb = paper;
+            for (p, plate) in plates.iter().enumerate() {
+                let (u, v) = plate.to_grid(x, y, cell);
+                let mut coverage = 0f32;
+                // dots covering more than π/4 of their cell spill into the
+                // cells next to them, so those are checked too
+                for (di, dj) in (-1..=1).flat_map(|di| (-1..=1).map(move |dj| (di, dj))) {
+                    let (i, j) = (u.floor() as i64 + di, v.floor() as i64 + dj);
+                    let (cx, cy) = plate.to_image(i as f32 + 0.5, j as f32 + 0.5, cell);
+                    let amount = *amounts.entry((p, i, j)).or_insert_with(|| {
+                        let colour = sample(cx, cy);
+                        if colour[3] == 0 {
+                            return 0.;
+                        }
+                        let rgb = [colour[0], colour[1], colour[2]].map(|c| c as f32 / 255.);
+                        (plate.amount)(rgb).clamp(0., 1.)
+                    });
+                    // a dot with `amount` of the area of its cell, with its
+                    // edge antialiased and ones smaller than a pixel faded
+                    let radius = cell * (amount / PI).sqrt();
+                    let distance = (x - cx).hypot(y - cy);
+                    let edge = (radius - distance + 0.5).clamp(0., 1.);
+                    coverage = coverage.max(edge * (2. * radius).min(1.));
+                }
+                for (c, ink) in rgb.iter_mut().zip(plate.ink) {
+                    *c *= 1. - coverage * (1. - ink);
+                }
+            }
+
+            let [r, g, b] = rgb.map(|c| (c.clamp(0., 1.) * 255.).round() as u8);
+            *px = Rgba([r, g, b, alpha]);
+        }
+
+        out.into()
+    }
+}
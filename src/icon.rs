@@ -0,0 +1,142 @@
+//! `pack.png`s labelled with the pack's name and a swatch of its colours, so
+//! that packs made from the same textures can be told apart on the pack
+//! selection screen.
+
+use std::{collections::HashMap, path::Path};
+
+use image::{DynamicImage, Rgb, Rgba, RgbaImage, imageops};
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::{
+    bitmap_font::{GLYPH_HEIGHT, GLYPH_WIDTH, draw_text, text_width},
+    blend::{BlendMode, blend_image},
+    filter::{luma, texture_category},
+    k_means::k_means_weighted,
+};
+
+/// Where the icon is in a pack
+pub const PACK_PNG: &str = "pack.png";
+/// Colours in the swatch
+pub const SWATCH_COLOURS: usize = 6;
+
+/// The icon is drawn at this size and scaled up to the size of the base
+/// image, if it's bigger
+const SIZE: u32 = 64;
+/// Most lines the name is wrapped onto, the rest is cut off
+const MAX_LINES: usize = 2;
+const BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const LABEL: Rgba<u8> = Rgba([255, 255, 255, 255]);
+/// Behind the name, to keep it readable on any base image
+const SHADE: Rgba<u8> = Rgba([0, 0, 0, 160]);
+
+/// Counts the colours of the textures written to a pack, to find its
+/// palette once they've all been written
+#[derive(Debug, Default)]
+pub struct PackColours {
+    counts: HashMap<Rgb<u8>, u64>,
+}
+
+impl PackColours {
+    /// Count the colours of the texture at `path`, if it's a block or an
+    /// item, which are most of what's seen in game
+    pub fn add(&mut self, path: &Path, image: &DynamicImage) {
+        if !matches!(texture_category(path), Some("block" | "item")) {
+            return;
+        }
+        for px in image.to_rgba8().pixels().filter(|px| px[3] >= 128) {
+            *self.counts.entry(Rgb([px[0], px[1], px[2]])).or_default() += 1;
+        }
+    }
+
+    /// Add the colours counted by `other`
+    pub fn merge(&mut self, other: PackColours) {
+        for (colour, n) in other.counts {
+            *self.counts.entry(colour).or_default() += n;
+        }
+    }
+
+    /// The `k` (or fewer) colours that best stand for the ones counted,
+    /// darkest first
+    pub fn palette(&self, k: usize, seed: u64) -> Vec<Rgb<u8>> {
+        let points: Vec<_> = self.counts.iter().map(|(&c, &n)| (c, n)).collect();
+        let mut palette = k_means_weighted(k, &points, &mut StdRng::seed_from_u64(seed));
+        palette.sort_by(|&Rgb([r1, g1, b1]), &Rgb([r2, g2, b2])| {
+            luma(Rgba([r1, g1, b1, 255])).total_cmp(&luma(Rgba([r2, g2, b2, 255])))
+        });
+        palette
+    }
+}
+
+/// `text` split into lines of at most `width` characters, between words where
+/// it can be
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+        while line.chars().count() > width {
+            let rest = line.chars().skip(width).collect();
+            lines.push(line.chars().take(width).collect());
+            line = rest;
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// `base` (like the transformed vanilla `pack.png`, or a dark square if
+/// there isn't one) with `name` across the top and a strip of `palette` along
+/// the bottom, at least 64 pixels square
+pub fn pack_icon(base: Option<&RgbaImage>, name: &str, palette: &[Rgb<u8>]) -> RgbaImage {
+    let size = base
+        .map_or(SIZE, |base| base.width().max(base.height()))
+        .max(SIZE);
+    let mut icon = match base {
+        Some(base) => imageops::resize(base, size, size, imageops::FilterType::Nearest),
+        None => RgbaImage::from_pixel(size, size, BACKGROUND),
+    };
+
+    let per_line = ((SIZE - 2) / (GLYPH_WIDTH + 1)) as usize;
+    let lines: Vec<_> = wrap(name, per_line).into_iter().take(MAX_LINES).collect();
+    if !lines.is_empty() {
+        let line_height = GLYPH_HEIGHT + 1;
+        let mut label = RgbaImage::from_pixel(SIZE, lines.len() as u32 * line_height + 1, SHADE);
+        for (i, line) in lines.iter().enumerate() {
+            let x = (SIZE - text_width(line)) / 2;
+            draw_text(&mut label, line, x, 1 + i as u32 * line_height, SIZE, LABEL);
+        }
+        let label = imageops::resize(
+            &label,
+            size,
+            label.height() * size / SIZE,
+            imageops::FilterType::Nearest,
+        );
+        blend_image(&mut icon, &label, 0, 0, BlendMode::Normal, 1.);
+    }
+
+    if !palette.is_empty() {
+        let height = size / 8;
+        let swatch = RgbaImage::from_fn(size, height, |x, _| {
+            let Rgb([r, g, b]) = palette[(x as usize * palette.len()) / size as usize];
+            Rgba([r, g, b, 255])
+        });
+        blend_image(
+            &mut icon,
+            &swatch,
+            0,
+            (size - height) as i64,
+            BlendMode::Normal,
+            1.,
+        );
+    }
+
+    icon
+}
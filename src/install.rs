@@ -0,0 +1,53 @@
+//! Copying generated packs into the game's `resourcepacks` directory
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{GenRpError, IoContext, Result};
+
+/// The default `.minecraft` directory for the current platform
+pub fn minecraft_dir() -> Result<PathBuf> {
+    let dir = if cfg!(windows) {
+        // %APPDATA%\.minecraft
+        dirs::config_dir().map(|dir| dir.join(".minecraft"))
+    } else if cfg!(target_os = "macos") {
+        // ~/Library/Application Support/minecraft
+        dirs::data_dir().map(|dir| dir.join("minecraft"))
+    } else {
+        dirs::home_dir().map(|dir| dir.join(".minecraft"))
+    };
+
+    dir.ok_or(GenRpError::NoMinecraftDir)
+}
+
+pub fn resourcepacks_dir() -> Result<PathBuf> {
+    Ok(minecraft_dir()?.join("resourcepacks"))
+}
+
+/// Copy `zip` into `resourcepacks`, returning where it ended up.  An existing
+/// pack with the same name is only overwritten if `replace` is set, in which
+/// case it is swapped out atomically so the game never sees half a zip.
+pub fn install_pack(zip: &Path, resourcepacks: &Path, replace: bool) -> Result<PathBuf> {
+    let file_name = zip.file_name().expect("pack is a file");
+    let dest = resourcepacks.join(file_name);
+
+    if !replace && dest.exists() {
+        return Err(GenRpError::AlreadyInstalled(dest));
+    }
+
+    fs::create_dir_all(resourcepacks)
+        .io_context(|| format!("Creating {}", resourcepacks.display()))?;
+
+    // copy next to the destination first, so the rename stays on one
+    // filesystem
+    let mut tmp = dest.clone().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::copy(zip, &tmp).io_context(|| format!("Copying {} to {}", zip.display(), tmp.display()))?;
+    fs::rename(&tmp, &dest)
+        .io_context(|| format!("Moving {} to {}", tmp.display(), dest.display()))?;
+
+    Ok(dest)
+}
@@ -1,11 +1,20 @@
 use core::f64;
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::RangeInclusive,
+};
 
-use image::Rgb;
+use image::{Rgb, RgbaImage};
+use rand::Rng;
 
 type Point = Rgb<u8>;
 
-fn rand_point() -> Point {
-    Rgb::<u8>([rand::random(), rand::random(), rand::random()])
+/// Pixels with less alpha than this are left out of [`k_means_rgba`] by
+/// default, as their colour is never seen in game
+pub const DEFAULT_MIN_ALPHA: u8 = 1;
+
+fn rand_point(rng: &mut impl Rng) -> Point {
+    Rgb::<u8>(rng.random())
 }
 
 pub fn dist_sq(p1: Point, p2: Point) -> f64 {
@@ -18,34 +27,21 @@ pub fn dist(p1: Point, p2: Point) -> f64 {
     dist_sq(p1, p2).sqrt()
 }
 
-fn calculate_centroid(points: Vec<Point>) -> Option<Point> {
-    if points.is_empty() {
-        eprintln!("no points");
-        if rand::random_bool(0.25) {
-            return Some(rand_point());
+/// Mean of a cluster, given the sum of its points and how many there are
+fn calculate_centroid(sum: [u64; 3], count: u64, rng: &mut impl Rng) -> Option<Point> {
+    if count == 0 {
+        tracing::trace!("empty cluster");
+        if rng.random_bool(0.25) {
+            return Some(rand_point(rng));
         }
         return None;
     }
 
-    let mut r = 0u32;
-    let mut g = 0u32;
-    let mut b = 0u32;
-
-    for p in &points {
-        r += p[0] as u32;
-        g += p[1] as u32;
-        b += p[2] as u32;
-    }
-
-    let r = (r / (points.len() as u32)) as u8;
-    let g = (g / (points.len() as u32)) as u8;
-    let b = (b / (points.len() as u32)) as u8;
-
-    Some(Rgb::<u8>([r, g, b]))
+    Some(Rgb(sum.map(|c| (c / count) as u8)))
 }
 
-pub fn closest(p1: Point, points: &[Point]) -> Point {
-    let mut min_dist = 100000.;
+fn closest_index(p1: Point, points: &[Point]) -> usize {
+    let mut min_dist = f64::INFINITY;
     let mut min_i = 0;
 
     for (i, p2) in points.iter().enumerate() {
@@ -56,36 +52,197 @@ pub fn closest(p1: Point, points: &[Point]) -> Point {
         }
     }
 
-    points[min_i]
+    min_i
+}
+
+pub fn closest(p1: Point, points: &[Point]) -> Point {
+    points[closest_index(p1, points)]
+}
+
+/// Each distinct colour in `points` with how many times it appears.  Most
+/// textures only use a handful of colours, so clustering these instead of
+/// every pixel is much faster.
+pub fn count_colours(points: &[Point]) -> Vec<(Point, u64)> {
+    let mut counts = HashMap::new();
+    for &point in points {
+        *counts.entry(point).or_insert(0) += 1;
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable_by_key(|(point, _)| point.0);
+    counts
+}
+
+/// The colours of the pixels in `image` with at least `min_alpha` alpha.
+/// Transparent pixels often hold leftover colours that would otherwise pull
+/// centroids towards them.
+pub fn visible_pixels(image: &RgbaImage, min_alpha: u8) -> Vec<Point> {
+    image
+        .pixels()
+        .filter(|px| px[3] >= min_alpha)
+        .map(|px| Rgb([px[0], px[1], px[2]]))
+        .collect()
+}
+
+/// [`k_means`] over the [`visible_pixels`] of `image`
+pub fn k_means_rgba(k: usize, image: &RgbaImage, min_alpha: u8, rng: &mut impl Rng) -> Vec<Point> {
+    k_means(k, &visible_pixels(image, min_alpha), rng)
+}
+
+/// Replace each pixel with at least `min_alpha` alpha with its closest colour
+/// in `palette`, keeping its alpha
+pub fn recolour(image: &mut RgbaImage, palette: &[Point], min_alpha: u8) {
+    if palette.is_empty() {
+        return;
+    }
+
+    for px in image.pixels_mut() {
+        if px[3] >= min_alpha {
+            let next = closest(Rgb([px[0], px[1], px[2]]), palette);
+            px.0[..3].copy_from_slice(&next.0);
+        }
+    }
+}
+
+/// Cluster `points` into up to `k` colours.  Centroids start at random
+/// colours from `rng`, so seeding it gives the same clusters every time.
+pub fn k_means(k: usize, points: &[Point], rng: &mut impl Rng) -> Vec<Point> {
+    k_means_weighted(k, &count_colours(points), rng)
 }
 
-pub fn k_means(k: usize, points: &[Point]) -> Vec<Point> {
-    let mut centroids = (0..k).map(|_| rand_point()).collect::<Vec<_>>();
+/// The result of [`k_means_labelled`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clustering {
+    pub centroids: Vec<Point>,
+    /// The index into `centroids` of the cluster each point is in
+    pub labels: Vec<usize>,
+    /// The sum of squared distances from each point to its centroid, lower
+    /// is a closer fit
+    pub inertia: f64,
+}
+
+impl Clustering {
+    /// Label `points` with their closest centroid
+    pub fn new(centroids: Vec<Point>, points: &[Point]) -> Self {
+        let mut labels = Vec::with_capacity(points.len());
+        let mut inertia = 0.;
+        if !centroids.is_empty() {
+            // textures repeat colours a lot, so only search for each once
+            let mut cache = HashMap::new();
+            for &point in points {
+                let (label, d) = *cache.entry(point).or_insert_with(|| {
+                    let i = closest_index(point, &centroids);
+                    (i, dist_sq(point, centroids[i]))
+                });
+                labels.push(label);
+                inertia += d;
+            }
+        }
+
+        Self {
+            centroids,
+            labels,
+            inertia,
+        }
+    }
+
+    /// How many points are in each cluster
+    pub fn sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.centroids.len()];
+        for &label in &self.labels {
+            sizes[label] += 1;
+        }
+        sizes
+    }
+}
+
+/// [`k_means`], also returning which cluster each point ended up in
+pub fn k_means_labelled(k: usize, points: &[Point], rng: &mut impl Rng) -> Clustering {
+    Clustering::new(k_means(k, points, rng), points)
+}
+
+/// [`k_means_labelled`] with the `k` from `ks` at the "elbow" of the inertia
+/// curve, where adding more clusters stops paying off: the point furthest
+/// below the line between its ends.  Simple textures end up with only a
+/// couple of colours and detailed ones with many.
+pub fn k_means_auto(points: &[Point], ks: RangeInclusive<usize>, rng: &mut impl Rng) -> Clustering {
+    let colours = count_colours(points);
+    // there's no point in more clusters than colours
+    let end = (*ks.end()).min(colours.len()).max(1);
+    let start = (*ks.start()).clamp(1, end);
+
+    let runs: Vec<_> = (start..=end)
+        .map(|k| {
+            let centroids = k_means_weighted(k, &colours, rng);
+            let inertia: f64 = if centroids.is_empty() {
+                0.
+            } else {
+                colours
+                    .iter()
+                    .map(|&(p, count)| dist_sq(p, closest(p, &centroids)) * count as f64)
+                    .sum()
+            };
+            (centroids, inertia)
+        })
+        .collect();
+
+    let first = runs[0].1;
+    let last = runs[runs.len() - 1].1;
+    let best = if runs.len() == 2 && last == 0. && first > 0. {
+        // one more cluster than the fewest fits every colour exactly, like
+        // 2 clusters for a texture with only 2 colours
+        1
+    } else if runs.len() < 3 || first <= last {
+        // nothing to choose between, so the fewest clusters
+        0
+    } else {
+        // with both axes scaled to 0-1, the line is x + y = 1
+        let scaled = |i: usize, inertia: f64| {
+            i as f64 / (runs.len() - 1) as f64 + (inertia - last) / (first - last)
+        };
+        (0..runs.len())
+            .min_by(|&a, &b| scaled(a, runs[a].1).total_cmp(&scaled(b, runs[b].1)))
+            .expect("there is at least one run")
+    };
+
+    let (centroids, _) = runs.into_iter().nth(best).expect("best is a run");
+    Clustering::new(centroids, points)
+}
+
+/// [`k_means`] over points that each stand for `count` identical points,
+/// see [`count_colours`]
+pub fn k_means_weighted(k: usize, points: &[(Point, u64)], rng: &mut impl Rng) -> Vec<Point> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+    // with a cluster to spare for each colour, they're each their own, which
+    // random centroids don't always find
+    let colours: BTreeSet<_> = points.iter().map(|(point, _)| point.0).collect();
+    if k >= colours.len() {
+        return colours.into_iter().map(Rgb).collect();
+    }
+
+    let mut centroids = (0..k).map(|_| rand_point(rng)).collect::<Vec<_>>();
     let mut converged = false;
 
     while !converged {
-        let mut clusters = (0..k)
-            .map(|_| Vec::<Point>::new())
-            .collect::<Vec<_>>()
-            .into_boxed_slice();
-
-        for point in points {
-            let mut closest_index = 0;
-            let mut min_dist = dist_sq(*point, centroids[0]);
-            for (j, centroid) in centroids.iter().enumerate().skip(1) {
-                let d = dist_sq(*point, *centroid);
-                if d < min_dist {
-                    min_dist = d;
-                    closest_index = j;
+        // the sum and number of the points in each cluster
+        let mut clusters = vec![([0u64; 3], 0u64); k];
+
+        if !centroids.is_empty() {
+            for &(point, count) in points {
+                let (sum, n) = &mut clusters[closest_index(point, &centroids)];
+                for (sum, c) in sum.iter_mut().zip(point.0) {
+                    *sum += c as u64 * count;
                 }
+                *n += count;
             }
-            clusters[closest_index].push(*point);
         }
 
         let mut new_centroids = Vec::new();
 
-        for cluster in clusters {
-            if let Some(new_centroid) = calculate_centroid(cluster) {
+        for (sum, count) in clusters {
+            if let Some(new_centroid) = calculate_centroid(sum, count, rng) {
                 new_centroids.push(new_centroid);
             }
         }
@@ -100,6 +257,59 @@ pub fn k_means(k: usize, points: &[Point]) -> Vec<Point> {
     centroids
 }
 
+/// Mini-batch k-means (<https://doi.org/10.1145/1772690.1772862>): rather
+/// than assigning every point each iteration, centroids are nudged towards
+/// `batch_size` randomly sampled points, with a learning rate that shrinks as
+/// more points are assigned to them.  Much faster than [`k_means`] over
+/// millions of pixels, e.g. for one palette shared by every texture, at the
+/// cost of a slightly worse fit.
+pub fn k_means_mini_batch(
+    k: usize,
+    points: &[Point],
+    batch_size: usize,
+    iterations: usize,
+    rng: &mut impl Rng,
+) -> Vec<Point> {
+    if points.is_empty() || k == 0 {
+        return Vec::new();
+    }
+
+    // start from colours that are actually used, so no centroid is stranded
+    // far away from every point
+    let mut centroids: Vec<[f64; 3]> = (0..k)
+        .map(|_| points[rng.random_range(0..points.len())].0.map(f64::from))
+        .collect();
+    let mut counts = vec![0u64; k];
+    let mut batch = Vec::with_capacity(batch_size);
+
+    for _ in 0..iterations {
+        batch.clear();
+        batch.extend((0..batch_size).map(|_| points[rng.random_range(0..points.len())]));
+
+        let current: Vec<_> = centroids.iter().map(|c| Rgb(c.map(|c| c as u8))).collect();
+        let assigned: Vec<_> = batch
+            .iter()
+            .map(|&point| closest_index(point, &current))
+            .collect();
+
+        for (&point, i) in batch.iter().zip(assigned) {
+            counts[i] += 1;
+            let rate = 1.0 / counts[i] as f64;
+            for (c, p) in centroids[i].iter_mut().zip(point.0) {
+                *c += rate * (p as f64 - *c);
+            }
+        }
+    }
+
+    let mut centroids: Vec<_> = centroids
+        .into_iter()
+        .map(|c| Rgb(c.map(|c| c.round() as u8)))
+        .collect();
+    centroids.sort_unstable_by_key(|c| c.0);
+    centroids.dedup();
+    centroids
+}
+
 // function kmeans(k, points) is
 //     // Initialize centroids
 //     centroids ← list of k starting centroids
@@ -134,3 +344,115 @@ pub fn k_means(k: usize, points: &[Point]) -> Vec<Point> {
 //             centroids ← newCentroids
 //
 //     return clusters
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    const DARK: Point = Rgb([40, 70, 30]);
+    const LIGHT: Point = Rgb([200, 220, 150]);
+
+    /// `n` points around `centre`, each channel off by up to 3
+    fn around(centre: Point, n: usize, rng: &mut StdRng) -> Vec<Point> {
+        (0..n)
+            .map(|_| {
+                Rgb(centre
+                    .0
+                    .map(|c| c.saturating_add_signed(rng.random_range(-3..=3))))
+            })
+            .collect()
+    }
+
+    /// A texture's worth of pixels in a handful of colours
+    fn pixels(rng: &mut StdRng) -> Vec<Point> {
+        let colours = [DARK, LIGHT, Rgb([120, 90, 60]), Rgb([20, 20, 200])];
+        (0..256)
+            .map(|_| colours[rng.random_range(0..colours.len())])
+            .collect()
+    }
+
+    #[test]
+    fn counting_colours_gives_the_same_clusters() {
+        let points = pixels(&mut StdRng::seed_from_u64(1));
+        let expanded: Vec<_> = points.iter().map(|&p| (p, 1)).collect();
+        for k in 1..=6 {
+            for seed in 0..8 {
+                assert_eq!(
+                    k_means_weighted(k, &count_colours(&points), &mut StdRng::seed_from_u64(seed)),
+                    k_means_weighted(k, &expanded, &mut StdRng::seed_from_u64(seed)),
+                    "k = {}, seed = {}",
+                    k,
+                    seed
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn counted_colours_add_up() {
+        let points = pixels(&mut StdRng::seed_from_u64(1));
+        let counts = count_colours(&points);
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts.iter().map(|&(_, n)| n).sum::<u64>(), 256);
+    }
+
+    #[test]
+    fn every_point_is_labelled() {
+        let points = pixels(&mut StdRng::seed_from_u64(1));
+        for k in 1..=6 {
+            let clustering = k_means_labelled(k, &points, &mut StdRng::seed_from_u64(0));
+            assert_eq!(clustering.labels.len(), points.len());
+            assert_eq!(clustering.sizes().iter().sum::<usize>(), points.len());
+            for (&point, &label) in points.iter().zip(&clustering.labels) {
+                assert_eq!(
+                    clustering.centroids[label],
+                    closest(point, &clustering.centroids)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inertia_is_zero_with_a_cluster_for_each_colour() {
+        let points = pixels(&mut StdRng::seed_from_u64(1));
+        for k in 4..=8 {
+            let clustering = k_means_labelled(k, &points, &mut StdRng::seed_from_u64(0));
+            assert_eq!(clustering.inertia, 0., "k = {}", k);
+        }
+    }
+
+    #[test]
+    fn inertia_is_the_squared_distance_to_centroids() {
+        let points = [Rgb([0, 0, 0]), Rgb([10, 0, 0]), Rgb([0, 0, 0])];
+        let clustering = Clustering::new(vec![Rgb([0, 0, 0])], &points);
+        assert_eq!(clustering.inertia, 100.);
+        assert_eq!(clustering.sizes(), [3]);
+    }
+
+    #[test]
+    fn auto_picks_two_for_two_colours() {
+        let mut points = vec![DARK; 30];
+        points.extend([LIGHT; 70]);
+        let clustering = k_means_auto(&points, 1..=16, &mut StdRng::seed_from_u64(0));
+
+        let mut centroids = clustering.centroids;
+        centroids.sort_unstable_by_key(|c| c.0);
+        assert_eq!(centroids, [DARK, LIGHT]);
+        assert_eq!(clustering.inertia, 0.);
+    }
+
+    #[test]
+    fn auto_picks_two_for_two_tones() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut points = around(DARK, 200, &mut rng);
+        points.extend(around(LIGHT, 200, &mut rng));
+        let clustering = k_means_auto(&points, 1..=8, &mut rng);
+
+        assert_eq!(clustering.centroids.len(), 2);
+        for centroid in clustering.centroids {
+            assert!(dist(centroid, DARK).min(dist(centroid, LIGHT)) < 4.);
+        }
+    }
+}
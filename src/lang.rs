@@ -0,0 +1,359 @@
+//! Language packs, which rewrite the text of the game rather than its
+//! textures.
+//!
+//! Only the text between format codes is transformed: placeholders like `%s`
+//! and `%1$s` and colour codes like `§a` are left alone, as the game needs
+//! them to stay exactly as they are.
+
+use std::{
+    collections::BTreeMap,
+    io::{BufReader, Read, Seek},
+    path::Path,
+    time::Instant,
+};
+
+use serde_json::Value;
+use zip::ZipArchive;
+
+use crate::{
+    GenerateOptions, GenerationReport, PackOutput,
+    error::{GenRpError, Result},
+    finish_pack,
+};
+
+/// The language every pack is generated from, the only one in the jar
+pub const LANG_PATH: &str = "assets/minecraft/lang/en_us.json";
+
+/// Translation keys to text, like `"block.minecraft.stone": "Stone"`
+pub type Lang = BTreeMap<String, String>;
+
+/// Read `en_us.json` from a client jar
+pub fn read_jar_lang(jar: impl Read + Seek) -> Result<Lang> {
+    let mut dec = ZipArchive::new(BufReader::new(jar))?;
+    let file = dec
+        .by_name(LANG_PATH)
+        .map_err(|_| GenRpError::InvalidJar(format!("missing {}", LANG_PATH)))?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment<'a> {
+    Text(&'a str),
+    /// A placeholder or colour code, kept as-is
+    Code(&'a str),
+}
+
+/// Split `s` into text and the codes between it
+fn segments(s: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let end = match c {
+            '§' => chars.next().map(|(j, c)| j + c.len_utf8()),
+            '%' => {
+                // `%%`, `%s` or `%1$s`, leaving anything else (like the `§`
+                // of `%§a`) to be read on its own
+                let mut end = None;
+                while let Some(&(j, c)) = chars.peek() {
+                    if c.is_ascii_digit() || c == '$' {
+                        chars.next();
+                        continue;
+                    }
+                    if c.is_ascii_alphabetic() || c == '%' {
+                        chars.next();
+                        end = Some(j + 1);
+                    }
+                    break;
+                }
+                end
+            }
+            _ => continue,
+        };
+
+        if let Some(end) = end {
+            if text_start < i {
+                segments.push(Segment::Text(&s[text_start..i]));
+            }
+            segments.push(Segment::Code(&s[i..end]));
+            text_start = end;
+        }
+    }
+
+    if text_start < s.len() {
+        segments.push(Segment::Text(&s[text_start..]));
+    }
+    segments
+}
+
+/// Apply `f` to the text in `s`, keeping the codes in between
+fn map_text(s: &str, mut f: impl FnMut(&str) -> String) -> String {
+    segments(s)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => f(text),
+            Segment::Code(code) => code.to_string(),
+        })
+        .collect()
+}
+
+pub trait TextTransform: Sync {
+    fn apply(&self, text: &str) -> String;
+}
+
+impl<F> TextTransform for F
+where
+    F: Fn(&str) -> String + Sync,
+{
+    fn apply(&self, text: &str) -> String {
+        self(text)
+    }
+}
+
+/// EVERYTHING IN CAPITALS
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Uppercase;
+
+impl TextTransform for Uppercase {
+    fn apply(&self, text: &str) -> String {
+        map_text(text, str::to_uppercase)
+    }
+}
+
+/// aLtErNaTiNg CaSe, counting only letters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MockingCase;
+
+impl TextTransform for MockingCase {
+    fn apply(&self, text: &str) -> String {
+        let mut upper = false;
+        map_text(text, |text| {
+            text.chars()
+                .flat_map(|c| {
+                    if !c.is_alphabetic() {
+                        return vec![c];
+                    }
+                    upper = !upper;
+                    if upper {
+                        c.to_lowercase().collect()
+                    } else {
+                        c.to_uppercase().collect()
+                    }
+                })
+                .collect()
+        })
+    }
+}
+
+/// sdrawkcab txet llA.  Placeholders are numbered first so that they still
+/// get the right arguments once they're in a different order, and colour
+/// codes stay in front of the text they colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Reversed;
+
+impl TextTransform for Reversed {
+    fn apply(&self, text: &str) -> String {
+        let mut next_arg = 1;
+        let mut colour = String::new();
+        let mut reversed = Vec::new();
+        for segment in segments(text) {
+            match segment {
+                Segment::Code(code) if code.starts_with('§') => colour.push_str(code),
+                Segment::Code(code) => {
+                    let code = if code == "%%" || code.contains('$') {
+                        code.to_string()
+                    } else {
+                        let numbered = format!("%{}${}", next_arg, &code[1..]);
+                        next_arg += 1;
+                        numbered
+                    };
+                    reversed.push(std::mem::take(&mut colour) + code.as_str());
+                }
+                Segment::Text(text) => {
+                    let text: String = text.chars().rev().collect();
+                    reversed.push(std::mem::take(&mut colour) + text.as_str());
+                }
+            }
+        }
+        reversed.push(colour);
+        reversed.reverse();
+        reversed.concat()
+    }
+}
+
+/// Replace whole words with `replacements` (all lowercase), keeping the
+/// capitalisation of the original's first letter
+fn replace_words(text: &str, replacements: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let word_len = rest
+            .find(|c: char| !c.is_alphabetic() && c != '\'')
+            .unwrap_or(rest.len());
+        if word_len == 0 {
+            let c = rest.chars().next().expect("rest isn't empty");
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let word = &rest[..word_len];
+        let lower = word.to_lowercase();
+        match replacements.iter().find(|(from, _)| *from == lower) {
+            Some((_, to)) => {
+                let mut chars = to.chars();
+                if word.starts_with(char::is_uppercase)
+                    && let Some(first) = chars.next()
+                {
+                    out.extend(first.to_uppercase());
+                    out.push_str(chars.as_str());
+                } else {
+                    out.push_str(to);
+                }
+            }
+            None => out.push_str(word),
+        }
+        rest = &rest[word_len..];
+    }
+    out
+}
+
+/// Arr, the game as spoken by pirates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pirate;
+
+const PIRATE_WORDS: &[(&str, &str)] = &[
+    ("hello", "ahoy"),
+    ("hi", "ahoy"),
+    ("yes", "aye"),
+    ("you", "ye"),
+    ("your", "yer"),
+    ("you're", "ye be"),
+    ("my", "me"),
+    ("is", "be"),
+    ("are", "be"),
+    ("am", "be"),
+    ("friend", "matey"),
+    ("friends", "hearties"),
+    ("player", "scallywag"),
+    ("players", "scallywags"),
+    ("inventory", "booty"),
+    ("money", "doubloons"),
+    ("boat", "ship"),
+    ("boats", "ships"),
+    ("stop", "avast"),
+    ("the", "th'"),
+    ("of", "o'"),
+    ("quit", "abandon ship"),
+    ("died", "walked the plank"),
+];
+
+impl TextTransform for Pirate {
+    fn apply(&self, text: &str) -> String {
+        map_text(text, |text| replace_words(text, PIRATE_WORDS))
+    }
+}
+
+/// Evewything sounds cutew
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Uwu;
+
+impl TextTransform for Uwu {
+    fn apply(&self, text: &str) -> String {
+        map_text(text, |text| {
+            let mut out = String::with_capacity(text.len());
+            let mut chars = text.chars().peekable();
+            while let Some(c) = chars.next() {
+                match c {
+                    'r' | 'l' => out.push('w'),
+                    'R' | 'L' => out.push('W'),
+                    'n' | 'N' if chars.peek().is_some_and(|c| "aeiou".contains(*c)) => {
+                        out.push(c);
+                        out.push('y');
+                    }
+                    c => out.push(c),
+                }
+            }
+            out
+        })
+    }
+}
+
+/// Write a pack with every string in `lang` transformed by `f`, in
+/// `options.format`
+pub fn generate_lang_pack(
+    pack_name: &str,
+    description: &str,
+    f: &dyn TextTransform,
+    lang: &Lang,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut sink = options.sink(pack_name)?;
+    let mut report = GenerationReport {
+        seed: options.seed,
+        ..Default::default()
+    };
+
+    let transformed: Lang = lang
+        .iter()
+        .map(|(key, text)| (key.clone(), f.apply(text)))
+        .collect();
+    let json = serde_json::to_string_pretty(&transformed)?;
+    sink.add_file(Path::new(LANG_PATH), json.as_bytes())?;
+    report.files_written += 1;
+    report.bytes_written += json.len() as u64;
+
+    finish_pack(sink, description, Value::Null, options, report, start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Segment::{Code, Text};
+
+    #[test]
+    fn splits_placeholders_and_colour_codes() {
+        assert_eq!(segments("Hello %s"), [Text("Hello "), Code("%s")]);
+        assert_eq!(
+            segments("%1$s and %2$s"),
+            [Code("%1$s"), Text(" and "), Code("%2$s")]
+        );
+        assert_eq!(segments("50%% off"), [Text("50"), Code("%%"), Text(" off")]);
+        assert_eq!(segments("§aGreen"), [Code("§a"), Text("Green")]);
+        assert_eq!(
+            segments("§a%s§r%%"),
+            [Code("§a"), Code("%s"), Code("§r"), Code("%%")]
+        );
+        assert_eq!(segments("100%"), [Text("100%")]);
+    }
+
+    #[test]
+    fn percent_before_colour_code_keeps_the_code() {
+        assert_eq!(segments("%§a"), [Text("%"), Code("§a")]);
+        assert_eq!(
+            segments("5%§aGreen"),
+            [Text("5%"), Code("§a"), Text("Green")]
+        );
+        assert_eq!(segments("%1§a"), [Text("%1"), Code("§a")]);
+        assert_eq!(Uppercase.apply("5%§aGreen"), "5%§aGREEN");
+    }
+
+    #[test]
+    fn transforms_only_text() {
+        assert_eq!(Uppercase.apply("§aHello %s, %1$s%%"), "§aHELLO %s, %1$s%%");
+    }
+
+    #[test]
+    fn reverses_around_codes() {
+        assert_eq!(Reversed.apply("Hello %s"), "%1$s olleH");
+        assert_eq!(Reversed.apply("%s gave %s"), "%2$s evag %1$s");
+        assert_eq!(Reversed.apply("%s%s"), "%2$s%1$s");
+        assert_eq!(Reversed.apply("%1$s and %2$s"), "%2$s dna %1$s");
+        assert_eq!(Reversed.apply("50%% off"), "ffo %%05");
+        // colour codes stay in front of the text they colour
+        assert_eq!(Reversed.apply("§aab§rcd"), "§rdc§aba");
+        assert_eq!(Reversed.apply("§a%s§rxy"), "§ryx§a%1$s");
+    }
+}
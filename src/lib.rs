@@ -4,24 +4,92 @@ use std::{
     ffi::OsStr,
     fmt::Display,
     fs::{self, File},
-    io::{self, BufReader, BufWriter, Cursor, Read, Seek, Write},
+    io::{self, BufReader, Read, Seek},
     path::{Path, PathBuf},
-    sync::Arc,
-    thread::{self, JoinHandle},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
     time::{Duration, Instant},
 };
 
-use anyhow::{Context, bail, ensure};
-use image::{DynamicImage, ImageReader};
+use cache::CacheDir;
+use error::{GenRpError, IoContext, Result};
+use filter::{ProtectFonts, Transform};
+use generated::Generated;
+use generator::Generator;
+use image::{DynamicImage, ImageFormat};
+use optimize::OptLevel;
+use output::{DirSink, OutputFormat, OutputSink, PackData};
+use pbr::PbrOptions;
 use prog::{Progress, ProgressGroup};
+use progress::ProgressSink;
 use reqwest::blocking as reqwest;
 use serde::{Deserialize, Serialize};
-use walkdir::WalkDir;
-use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
-
+use shaders::ShaderTemplates;
+use timings::StageTimings;
+use walkdir::{DirEntry, WalkDir};
+use zip::ZipArchive;
+
+pub mod analyze;
+pub mod animation;
+pub mod ascii;
+pub mod assets;
+#[cfg(feature = "async")]
+pub mod async_download;
+pub mod bitmap_font;
+pub mod blend;
+pub mod builtin;
+pub mod cache;
 pub mod colour;
+pub mod config;
+pub mod curseforge;
+pub mod datapack;
+pub mod diff;
+pub mod dither;
+pub mod download;
+pub mod emissive;
+pub mod error;
+pub mod extrude;
+pub mod filter;
+pub mod font;
+pub mod generated;
+pub mod generator;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod halftone;
+pub mod icon;
+pub mod install;
 pub mod k_means;
+pub mod lang;
+pub mod lut;
+pub mod merge;
+pub mod models;
 pub mod modrinth;
+pub mod mosaic;
+pub mod optimize;
+pub mod output;
+pub mod pack_source;
+pub mod palette;
+pub mod pbr;
+pub mod preview;
+pub mod progress;
+pub mod quantize;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod serve;
+pub mod shaders;
+#[cfg(feature = "sound")]
+pub mod sound;
+pub mod timings;
+#[cfg(feature = "upload")]
+pub mod upload;
+pub mod upscale;
+pub mod variants;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watermark;
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -124,7 +192,7 @@ pub struct PackMcMeta<'a> {
 }
 
 impl<'a> PackMcMeta<'a> {
-    fn new(description: &'a str, pack_format: u32) -> Self {
+    pub(crate) fn new(description: &'a str, pack_format: u32) -> Self {
         Self {
             pack: PackMcMetaPack {
                 description,
@@ -166,12 +234,20 @@ pub struct VersionJson {
     pub use_editor: bool,
 }
 
+impl VersionJson {
+    /// Read `version.json` from a client jar
+    pub fn from_jar(jar: impl Read + Seek) -> Result<Self> {
+        let mut dec = ZipArchive::new(BufReader::new(jar))?;
+        let file = dec
+            .by_name("version.json")
+            .map_err(|_| GenRpError::InvalidJar("missing version.json".into()))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
 impl Manifest {
-    pub fn get() -> anyhow::Result<Self> {
-        reqwest::get(MANIFEST_URL)?
-            .error_for_status()?
-            .json()
-            .context("Parsing response json")
+    pub fn get() -> Result<Self> {
+        Ok(reqwest::get(MANIFEST_URL)?.error_for_status()?.json()?)
     }
 
     pub fn latest_version(&self) -> &Version {
@@ -186,143 +262,418 @@ impl Manifest {
 }
 
 impl Version {
-    pub fn get_latest() -> anyhow::Result<Self> {
+    pub fn get_latest() -> Result<Self> {
         Ok(Manifest::get()?.versions.pop_last().unwrap())
     }
 
-    pub fn get_by_id(id: &str) -> anyhow::Result<Self> {
+    pub fn get_by_id(id: &str) -> Result<Self> {
         Manifest::get()?
             .versions
             .into_iter()
             .find(|v| v.id == id)
-            .with_context(|| format!("Unknown version id: {}", id))
+            .ok_or_else(|| GenRpError::UnknownVersion(id.to_string()))
     }
 
-    pub fn download_jar(&self, clients_dir: impl AsRef<Path>) -> anyhow::Result<File> {
-        let clients_dir = clients_dir.as_ref();
+    /// The version's json, with its downloads and asset index
+    pub fn meta(&self) -> Result<VersionMeta> {
+        Ok(reqwest::get(&self.url)?.error_for_status()?.json()?)
+    }
 
-        let jar_path = clients_dir.join(&self.id).with_added_extension("jar");
-        if jar_path.try_exists()? {
-            println!("{} already exists, skipping download.", jar_path.display());
-            return Ok(File::open(&jar_path)?);
+    /// Download the client jar to the cache, unless it's already there and
+    /// matches the SHA-1 in the version's json
+    pub fn download_jar(&self, cache: &CacheDir) -> Result<File> {
+        let jar_path = cache.jar_path(self);
+        let meta = self.meta()?;
+        let client = &meta.downloads.client;
+        if jar_path
+            .try_exists()
+            .io_context(|| format!("Checking for {}", jar_path.display()))?
+        {
+            if download::sha1_file(&jar_path)? == client.sha1 {
+                tracing::info!("{} already exists, skipping download.", jar_path.display());
+                return File::open(&jar_path)
+                    .io_context(|| format!("Opening {}", jar_path.display()));
+            }
+            tracing::warn!(
+                "{} doesn't match its SHA-1, downloading it again",
+                jar_path.display()
+            );
         }
 
-        fs::create_dir_all(clients_dir)
-            .with_context(|| format!("Creating {} directory", clients_dir.display()))?;
+        let version_dir = cache.version_dir(&self.id);
+        fs::create_dir_all(&version_dir)
+            .io_context(|| format!("Creating {} directory", version_dir.display()))?;
+
+        tracing::info!("Getting version {}", self.id);
 
-        let res = reqwest::get(&self.url)?;
-        let meta: VersionMeta = res.json()?;
-        println!("Getting version {}", self.id);
+        download::download_file(&client.url, &jar_path, &client.sha1)?;
+        tracing::info!("Downloaded to {}", jar_path.display());
 
-        let mut res = reqwest::get(&meta.downloads.client.url)?;
-        let mut jar_file = File::create_new(&jar_path)?;
+        File::open(&jar_path).io_context(|| format!("Opening {}", jar_path.display()))
+    }
+}
 
-        io::copy(&mut res, &mut jar_file)
-            .with_context(|| format!("Downloading client to {}", jar_path.display()))?;
-        println!("Downloaded to {}", jar_path.display());
-        drop(jar_file);
+/// Whether `path` is an image that can be decoded, going by its extension.
+/// Besides PNGs, some mods have `.tga` or `.jpg` textures.
+pub fn is_image(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| format.reading_enabled())
+}
 
-        Ok(File::open(&jar_path)?)
+/// Where the texture at `path` ends up in a pack: images that aren't PNGs
+/// are converted to one, unless [`GenerateOptions::preserve_formats`] is set
+pub(crate) fn output_path_in_pack(path: PathBuf, options: &GenerateOptions) -> PathBuf {
+    if !options.preserve_formats && is_image(&path) {
+        path.with_extension("png")
+    } else {
+        path
     }
 }
 
-#[allow(clippy::too_many_arguments)]
-pub fn generate_pack(
-    pack_name: impl AsRef<str>,
-    description: impl AsRef<str>,
-    progress: &mut Progress<usize>,
-    textures_dir: impl AsRef<Path>,
-    out_dir: impl AsRef<Path>,
-    pack_format: u32,
-    f: fn(DynamicImage) -> DynamicImage,
-) -> anyhow::Result<()> {
-    let start = Instant::now();
+/// Whether the texture at `path` would be converted to a PNG that's already
+/// next to it, like `foo.tga` beside `foo.png`, which would put two files with
+/// the same name in the pack
+pub(crate) fn shadowed_by_png(path: &Path, options: &GenerateOptions) -> bool {
+    !options.preserve_formats
+        && is_image(path)
+        && path.extension().is_some_and(|ext| ext != "png")
+        && path.with_extension("png").exists()
+}
 
-    let pack_name = pack_name.as_ref();
-    let description = description.as_ref();
-    let textures_dir = textures_dir.as_ref();
-    let out_dir = out_dir.as_ref();
+/// Path of a file from `textures_dir` inside of the pack zip
+/// `textures_dir` is laid out like a pack, so this is just the path relative
+/// to it
+fn path_in_pack(textures_dir: &Path, entry: &DirEntry) -> PathBuf {
+    entry
+        .path()
+        .strip_prefix(textures_dir)
+        .expect("Path is in textures_dir")
+        .to_path_buf()
+}
 
-    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+/// What [`generate_pack`] would write, as returned by [`dry_run_pack`]
+#[derive(Clone, Debug)]
+pub struct DryRun {
+    /// Where the pack would be written
+    pub path: PathBuf,
+    /// Paths inside of the pack
+    pub files: Vec<PathBuf>,
+    /// Sum of the source file sizes.  Entries are stored uncompressed, so
+    /// this is close to the final size, though transformed images will
+    /// re-encode to a slightly different size.
+    pub estimated_size: u64,
+    pub pack_mcmeta: String,
+}
 
-    let zip_file = out_dir.join(pack_name).with_added_extension("zip");
-    let zip_file =
-        File::create(&zip_file).with_context(|| format!("Creating file {}", zip_file.display()))?;
-    let mut writer = ZipWriter::new(BufWriter::new(zip_file));
+/// Default for [`GenerateOptions::memory_budget`], 512MiB
+pub const DEFAULT_MEMORY_BUDGET: u64 = 512 * 1024 * 1024;
+
+/// Where [`generate_pack`] reads textures from and writes packs to
+#[derive(Clone, Debug)]
+pub struct GenerateOptions {
+    /// Extracted textures, laid out like a pack, see [`extract_jar`]
+    pub textures_dir: PathBuf,
+    /// Where packs are written, e.g. `.minecraft/resourcepacks`
+    pub out_dir: PathBuf,
+    pub format: OutputFormat,
+    pub pack_format: u32,
+    /// Produce byte-identical zips for the same input, by sorting entries
+    /// and fixing timestamps and permissions
+    pub reproducible: bool,
+    /// Keep the files of a directory pack from a run that didn't finish,
+    /// only writing the ones that are missing or different, see
+    /// [`DirSink::resume`](output::DirSink::resume).  Zips and `.tar.gz`s
+    /// are always written whole.
+    pub resume: bool,
+    /// Roughly how many bytes of decoded textures may be held at once while
+    /// generating several packs.  Reading pauses until the slowest pack
+    /// catches up, so large modded texture sets don't run out of memory.
+    pub memory_budget: u64,
+    /// How many threads to generate with, or one for each core if 0.  With
+    /// fewer threads than packs each thread writes several packs, with more
+    /// the spare ones transform and encode a pack's textures together, see
+    /// [`Generator`].
+    pub jobs: usize,
+    /// Fill the colour of fully transparent pixels from their neighbours
+    /// after transforming, see [`filter::bleed_alpha`]
+    pub bleed_alpha: bool,
+    /// The seed the pack's random transforms were given, recorded in its
+    /// [`GenerationReport`] so the pack can be reproduced
+    pub seed: u64,
+    /// Shaders added to every pack, filled in for its transform
+    pub shaders: ShaderTemplates,
+    /// How hard to try to shrink the PNGs written to the pack
+    pub optimize: OptLevel,
+    /// Copy images that aren't PNGs (like `.tga`s from mods) as they are,
+    /// for mods that load them by their original name, instead of
+    /// transforming them and writing them as PNGs
+    pub preserve_formats: bool,
+    /// What to do when a single texture can't be read, transformed or
+    /// encoded
+    pub on_error: ErrorPolicy,
+    /// Set from another thread (like a ctrl-C handler) to stop generating.
+    /// Packs that aren't finished fail with [`GenRpError::Cancelled`], and
+    /// their temporary files are removed.
+    pub cancel: Arc<AtomicBool>,
+    /// The Minecraft version the textures are from, recorded in each pack's
+    /// [`generated.json`](generated)
+    pub minecraft_version: Option<String>,
+    /// Label each pack's `pack.png` with its name and a swatch of its
+    /// colours, see [`icon`]
+    pub pack_icon: bool,
+    /// Also write LabPBR normal and specular maps for each block texture,
+    /// see [`pbr`]
+    pub pbr: Option<PbrOptions>,
+    /// Run transforms that support it on the GPU, when there is one
+    #[cfg(feature = "gpu")]
+    pub gpu: bool,
+}
 
-    let mut image_buf = Vec::new();
-    let mut i = 0;
-    for entry in WalkDir::new(textures_dir) {
-        if i % 32 == 0 {
-            progress.update(i);
+impl GenerateOptions {
+    pub fn new(
+        textures_dir: impl Into<PathBuf>,
+        out_dir: impl Into<PathBuf>,
+        pack_format: u32,
+    ) -> Self {
+        Self {
+            textures_dir: textures_dir.into(),
+            out_dir: out_dir.into(),
+            pack_format,
+            format: OutputFormat::default(),
+            reproducible: false,
+            resume: false,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+            jobs: 0,
+            bleed_alpha: false,
+            seed: 0,
+            shaders: ShaderTemplates::default(),
+            optimize: OptLevel::default(),
+            preserve_formats: false,
+            on_error: ErrorPolicy::default(),
+            cancel: Arc::default(),
+            minecraft_version: None,
+            pack_icon: true,
+            pbr: None,
+            #[cfg(feature = "gpu")]
+            gpu: true,
         }
-        i += 1;
+    }
 
-        let entry = entry?;
+    fn output_path(&self, pack_name: &str) -> PathBuf {
+        self.format.path(&self.out_dir, pack_name)
+    }
+
+    /// How many threads to generate with, see [`Self::jobs`]
+    pub fn threads(&self) -> usize {
+        match self.jobs {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs,
+        }
+    }
+
+    /// Whether [`Self::cancel`] has been set
+    pub fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+
+    /// Where to write a pack called `pack_name`, in [`Self::format`]
+    pub fn sink(&self, pack_name: &str) -> Result<Box<dyn OutputSink>> {
+        output::check_pack_name(pack_name)?;
+        let path = self.output_path(pack_name);
+        if self.resume && self.format == OutputFormat::Dir {
+            return Ok(Box::new(DirSink::resume(path)?));
+        }
+        self.format.sink(&path, self.reproducible)
+    }
+}
 
+/// What to do when a single file of a pack fails, as one bad texture in a mod
+/// jar shouldn't stop the rest of the pack
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Fail the whole pack
+    FailFast,
+    /// Leave the file out of the pack and carry on, listing it in
+    /// [`GenerationReport::skipped`] and the warnings
+    #[default]
+    SkipAndWarn,
+}
+
+impl ErrorPolicy {
+    /// Handle the file at `path` in the pack failing because of `reason`
+    pub fn handle(
+        self,
+        report: &mut GenerationReport,
+        path: &Path,
+        reason: impl Display,
+    ) -> Result<()> {
+        match self {
+            Self::FailFast => Err(GenRpError::File {
+                path: path.to_path_buf(),
+                reason: reason.to_string(),
+            }),
+            Self::SkipAndWarn => {
+                report
+                    .warnings
+                    .push(format!("skipped {}: {}", path.display(), reason));
+                report.skipped.push(path.to_path_buf());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Walk the textures the same way [`generate_pack`] does, but only report
+/// what would be written instead of writing anything.  `f` is only asked
+/// which files it [includes](Transform::includes).
+pub fn dry_run_pack(
+    pack_name: impl AsRef<str>,
+    description: impl AsRef<str>,
+    f: &dyn Transform,
+    options: &GenerateOptions,
+) -> Result<DryRun> {
+    let textures_dir = &*options.textures_dir;
+
+    let pack_mcmeta =
+        serde_json::to_string_pretty(&PackMcMeta::new(description.as_ref(), options.pack_format))?;
+
+    let mut files = Vec::new();
+    let mut estimated_size = pack_mcmeta.len() as u64;
+    for entry in WalkDir::new(textures_dir) {
+        let entry = entry?;
         if entry.path().is_dir() {
-            let full_path_str = entry
-                .path()
-                .strip_prefix(textures_dir)
-                .expect("path is in textures_dir")
-                .to_str()
-                .expect("all asset paths are valid utf-8");
-            progress.set_status(
-                full_path_str
-                    .strip_prefix(textures_dir.to_str().expect("textures_dir is valid utf-8"))
-                    .map(|s| s.trim_start_matches('/'))
-                    .unwrap_or(full_path_str),
-            );
             continue;
         }
 
-        let path = if entry.file_name() == "pack.png" {
-            PathBuf::from_iter(["pack.png"])
-        } else {
-            PathBuf::from_iter(["assets", "minecraft", "textures"]).join(
-                entry
-                    .path()
-                    .strip_prefix(textures_dir)
-                    .expect("Path is in textures_dir"),
-            )
-        };
-
-        if entry.path().extension().is_some_and(|ext| ext == "png") {
-            let image = ImageReader::open(entry.path())
-                .with_context(|| format!("Reading image {}", entry.path().display()))?
-                .decode()
-                .context("Decoding image")?;
-
-            let image = f(image);
-
-            writer.start_file_from_path(path, options)?;
-            let mut cursor = Cursor::new(&mut image_buf);
-            image.write_to(&mut cursor, image::ImageFormat::Png)?;
-            writer.write_all(&image_buf)?;
-            image_buf.clear();
-        } else {
-            writer.start_file_from_path(path, options)?;
-            io::copy(&mut File::open(entry.path())?, &mut writer)?;
+        let path = output_path_in_pack(path_in_pack(textures_dir, &entry), options);
+        if !f.includes(&path) || shadowed_by_png(entry.path(), options) {
+            continue;
         }
+        estimated_size += entry.metadata()?.len();
+        let companions = options
+            .pbr
+            .and_then(|_| pbr::companion_paths(&path))
+            .filter(|companions| !companions.iter().any(|c| textures_dir.join(c).exists()));
+        files.push(path);
+        files.extend(companions.into_iter().flatten());
+    }
+    files.extend(options.shaders.paths().map(Path::to_path_buf));
+    if options.pack_icon && !files.iter().any(|f| f == Path::new(icon::PACK_PNG)) {
+        files.push(PathBuf::from(icon::PACK_PNG));
     }
+    files.push(PathBuf::from("pack.mcmeta"));
+    files.push(PathBuf::from(generated::GENERATED_JSON));
+
+    Ok(DryRun {
+        path: options.output_path(pack_name.as_ref()),
+        files,
+        estimated_size,
+        pack_mcmeta,
+    })
+}
 
-    let pack_mcmeta = serde_json::to_string_pretty(&PackMcMeta::new(description, pack_format))?;
+/// A pack written by [`generate_pack`]
+#[derive(Clone, Debug)]
+pub struct PackOutput {
+    pub data: PackData,
+    /// Hex-encoded SHA-1 of the zip (or `.tar.gz`), as needed for
+    /// `resource-pack-sha1` in `server.properties`.  Directories don't have
+    /// one.
+    pub sha1: Option<String>,
+    pub report: GenerationReport,
+}
 
-    writer.start_file("pack.mcmeta", options)?;
-    writer.write_all(pack_mcmeta.as_bytes())?;
+/// What happened while generating a pack
+#[derive(Clone, Debug, Default)]
+pub struct GenerationReport {
+    /// Files in the pack, including `pack.mcmeta`
+    pub files_written: usize,
+    /// Uncompressed size of all of the entries
+    pub bytes_written: u64,
+    /// Files (in the pack) that couldn't be read, transformed or encoded,
+    /// and so were left out of it, see [`ErrorPolicy`]
+    pub skipped: Vec<PathBuf>,
+    pub duration: Duration,
+    /// How long was spent decoding, transforming, encoding and writing, for
+    /// packs of textures made by [`generate_pack`] or the [`Generator`]
+    pub timings: StageTimings,
+    /// See [`GenerateOptions::seed`]
+    pub seed: u64,
+    /// Problems that didn't stop the pack from being generated
+    pub warnings: Vec<String>,
+}
 
-    writer.finish()?;
+impl PackOutput {
+    /// Write the hash next to the pack as `<pack>.zip.sha1` (or
+    /// `<pack>.tar.gz.sha1`), if it was written to a file
+    pub fn write_sha1_file(&self) -> Result<Option<PathBuf>> {
+        let (PackData::File(file), Some(sha1)) = (&self.data, &self.sha1) else {
+            return Ok(None);
+        };
+        let path = file.with_added_extension("sha1");
+        fs::write(&path, sha1).io_context(|| format!("Writing {}", path.display()))?;
+        Ok(Some(path))
+    }
+}
 
-    progress.update(i);
-    progress.set_status(format!(
-        "\x1b[32mDone!\x1b[0m in {:?}",
-        Duration::from_millis(start.elapsed().as_millis() as u64)
-    ));
+pub fn generate_pack(
+    pack_name: impl AsRef<str>,
+    description: impl AsRef<str>,
+    progress: &mut dyn ProgressSink,
+    f: &dyn Transform,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let _span = tracing::info_span!("pack", name = pack_name.as_ref()).entered();
+    let sink = options.sink(pack_name.as_ref())?;
+    generate_pack_to(pack_name, description, progress, f, options, sink)
+}
 
-    Ok(())
+/// Like [`generate_pack`], but writing to `sink` instead of `options.out_dir`,
+/// e.g. a [`MemorySink`](output::MemorySink) to get the zip as bytes.
+/// `pack_name` is only drawn on the [pack icon](GenerateOptions::pack_icon).
+pub fn generate_pack_to(
+    pack_name: impl AsRef<str>,
+    description: impl AsRef<str>,
+    progress: &mut dyn ProgressSink,
+    f: &dyn Transform,
+    options: &GenerateOptions,
+    sink: Box<dyn OutputSink>,
+) -> Result<PackOutput> {
+    generator::generate(
+        pack_name.as_ref(),
+        description.as_ref(),
+        progress,
+        f,
+        options,
+        sink,
+    )
 }
 
-pub fn extract_jar(jar: impl Read + Seek, textures_dir: impl AsRef<Path>) -> anyhow::Result<u32> {
+/// Write `pack.mcmeta` and `generated.json` (describing the pack with
+/// `transform`) and finish a pack that isn't made by the [`Generator`], like
+/// the sound and language packs
+pub(crate) fn finish_pack(
+    mut sink: Box<dyn OutputSink>,
+    description: &str,
+    transform: serde_json::Value,
+    options: &GenerateOptions,
+    mut report: GenerationReport,
+    start: Instant,
+) -> Result<PackOutput> {
+    let pack_mcmeta =
+        serde_json::to_string_pretty(&PackMcMeta::new(description, options.pack_format))?;
+    sink.add_file(Path::new("pack.mcmeta"), pack_mcmeta.as_bytes())?;
+    report.files_written += 1;
+    report.bytes_written += pack_mcmeta.len() as u64;
+    Generated::new(description, transform, options).add_to(&mut *sink, &mut report)?;
+
+    let data = sink.finish()?;
+    let sha1 = data.sha1()?;
+    report.duration = start.elapsed();
+
+    Ok(PackOutput { data, sha1, report })
+}
+
+pub fn extract_jar(jar: impl Read + Seek, textures_dir: impl AsRef<Path>) -> Result<u32> {
     let textures_dir = textures_dir.as_ref();
     let mut dec = ZipArchive::new(BufReader::new(jar))?;
     let mut pack_format = None::<u32>;
@@ -331,15 +682,15 @@ pub fn extract_jar(jar: impl Read + Seek, textures_dir: impl AsRef<Path>) -> any
         let mut file = dec.by_index(i)?;
         let path1 = file
             .enclosed_name()
-            .with_context(|| format!("Malformed path in jar: {}", file.name()))?;
+            .ok_or_else(|| GenRpError::InvalidJar(format!("malformed path {}", file.name())))?;
 
         if path1 == *"version.json" {
             let version_json: VersionJson = serde_json::from_reader(file)?;
             pack_format = Some(version_json.pack_version.resource_major);
-            ensure!(
-                version_json.pack_version.resource_minor == 0,
-                "resource_minor must be 0" // we assume this
-            );
+            // we assume this
+            if version_json.pack_version.resource_minor != 0 {
+                return Err(GenRpError::InvalidJar("resource_minor must be 0".into()));
+            }
 
             continue;
         }
@@ -353,60 +704,159 @@ pub fn extract_jar(jar: impl Read + Seek, textures_dir: impl AsRef<Path>) -> any
             continue;
         }
 
-        let path = if path1 != Path::new("pack.png") {
-            if !path1.starts_with("assets/minecraft/textures") {
-                continue;
-            }
-            let mut path = textures_dir.to_path_buf();
-            path.extend(path1.components().skip(3));
-            path
-        } else {
-            textures_dir.join("pack.png")
-        };
+        if path1 != Path::new("pack.png") && !path1.starts_with("assets/minecraft/textures") {
+            continue;
+        }
+
+        save_zip_file(&mut file, &textures_dir.join(&path1))?;
+    }
+
+    pack_format.ok_or_else(|| GenRpError::InvalidJar("unable to determine pack format".into()))
+}
+
+/// Lay the textures of an existing resource pack over the ones extracted by
+/// [`extract_jar`], so that the pack is transformed instead of vanilla.
+///
+/// OptiFine/Continuity connected textures (`assets/*/optifine/**`) are kept
+/// too, so connected glass and bookshelves still work in the output.
+pub fn extract_pack(pack: impl Read + Seek, textures_dir: impl AsRef<Path>) -> Result<()> {
+    let textures_dir = textures_dir.as_ref();
+    let mut dec = ZipArchive::new(BufReader::new(pack))?;
 
-        let parent = path
-            .parent()
-            .with_context(|| format!("path contains no parent: {}", path.display()))?;
-        fs::create_dir_all(parent).with_context(|| format!("Making dir {}", parent.display()))?;
+    for i in 0..dec.len() {
+        let mut file = dec.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = file
+            .enclosed_name()
+            .ok_or_else(|| GenRpError::InvalidPack(format!("malformed path {}", file.name())))?;
+
+        let allowed_extensions = ["png", "mcmeta", "properties"].map(OsStr::new);
+        if path
+            .extension()
+            .is_none_or(|ext| !allowed_extensions.contains(&ext))
+            && !is_image(&path)
+            || path == Path::new("pack.mcmeta")
+        {
+            continue;
+        }
 
-        let mut out =
-            File::create(&path).with_context(|| format!("Creating file {}", path.display()))?;
+        // assets/<namespace>/<kind>/...
+        let kind = path.iter().nth(2);
+        if path != Path::new("pack.png")
+            && !(path.starts_with("assets")
+                && kind.is_some_and(|kind| kind == "textures" || kind == "optifine"))
+        {
+            continue;
+        }
 
-        io::copy(&mut file, &mut out).with_context(|| format!("Saving {}", path.display()))?;
+        save_zip_file(&mut file, &textures_dir.join(&path))?;
     }
 
-    let Some(pack_format) = pack_format else {
-        bail!("Unable to determine pack format");
-    };
+    Ok(())
+}
 
-    Ok(pack_format)
+/// Extract the textures of a Fabric/Forge mod jar (`assets/<modid>/textures`)
+/// next to the vanilla ones, so modded blocks and items are transformed too.
+///
+/// Textures keep their namespace, so the generated packs override them the
+/// same way they override vanilla.  Returns the namespaces that were found.
+pub fn extract_mod_jar(
+    jar: impl Read + Seek,
+    textures_dir: impl AsRef<Path>,
+) -> Result<BTreeSet<String>> {
+    let textures_dir = textures_dir.as_ref();
+    let mut dec = ZipArchive::new(BufReader::new(jar))?;
+    let mut namespaces = BTreeSet::new();
+
+    for i in 0..dec.len() {
+        let mut file = dec.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let path = file
+            .enclosed_name()
+            .ok_or_else(|| GenRpError::InvalidJar(format!("malformed path {}", file.name())))?;
+
+        if path.extension().is_none_or(|ext| ext != "mcmeta") && !is_image(&path) {
+            continue;
+        }
+
+        // assets/<namespace>/textures/...
+        let mut components = path.iter();
+        if components.next().is_none_or(|c| c != "assets") {
+            continue;
+        }
+        let Some(namespace) = components.next().and_then(OsStr::to_str) else {
+            continue;
+        };
+        if components.next().is_none_or(|c| c != "textures") {
+            continue;
+        }
+
+        namespaces.insert(namespace.to_string());
+        save_zip_file(&mut file, &textures_dir.join(&path))?;
+    }
+
+    Ok(namespaces)
+}
+
+fn save_zip_file(file: &mut impl Read, path: &Path) -> Result<()> {
+    let parent = path.parent().expect("path is inside textures_dir");
+    fs::create_dir_all(parent).io_context(|| format!("Making dir {}", parent.display()))?;
+
+    let mut out = File::create(path).io_context(|| format!("Creating file {}", path.display()))?;
+
+    io::copy(file, &mut out).io_context(|| format!("Saving {}", path.display()))?;
+    Ok(())
 }
 
 pub struct Pack<'a> {
     pub name: &'a str,
     pub desc: &'a str,
     pub slug: &'a str,
+    /// Words in the names of the only textures the pack changes (see
+    /// [`filter::name_has_word`]), along with the biome colormaps, or every
+    /// texture if it's empty
+    pub only: &'a [&'a str],
     pub func: fn(DynamicImage) -> DynamicImage,
 }
 
+impl Transform for Pack<'_> {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        (self.func)(image)
+    }
+
+    /// Built-in packs are described by their name, see [`builtin::PACKS`]
+    fn describe(&self) -> serde_json::Value {
+        serde_json::json!({ "builtin": self.name })
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        self.only.is_empty() || filter::is_colormap(path) || filter::name_has_word(path, self.only)
+    }
+}
+
 pub fn build_packs(
     version: &Version,
     packs: &'static [Pack<'static>],
-    textures_dir: Arc<Path>,
-    out_dir: Arc<Path>,
-) -> anyhow::Result<()> {
-    fs::remove_dir_all(&textures_dir).context("Removing textures dir")?;
-    fs::create_dir_all(&textures_dir).context("Creating textures dir")?;
-
-    let jar_file = version.download_jar("clients")?;
-    let pack_format = extract_jar(jar_file, &textures_dir).context("Extracting JAR")?;
-
-    let mut threads = Vec::new();
+    cache: &CacheDir,
+    out_dir: &Path,
+) -> Result<()> {
+    let textures_dir = cache.fresh_textures_dir(version)?;
+
+    let jar_file = version.download_jar(cache)?;
+    let pack_format = extract_jar(jar_file, &textures_dir)?;
+    let options = GenerateOptions {
+        minecraft_version: Some(version.id.clone()),
+        ..GenerateOptions::new(textures_dir, out_dir, pack_format)
+    };
 
-    fs::create_dir_all(&out_dir)
-        .with_context(|| format!("Creating directory {}", out_dir.display()))?;
+    fs::create_dir_all(out_dir)
+        .io_context(|| format!("Creating directory {}", out_dir.display()))?;
 
-    let num_files = WalkDir::new(&textures_dir).into_iter().count();
+    let num_files = WalkDir::new(&options.textures_dir).into_iter().count();
     let prog_group = ProgressGroup::builder()
         .width(130)
         .progress_width(80)
@@ -416,40 +866,28 @@ pub fn build_packs(
         })
         .build();
 
-    for pack in packs {
-        let prog_group = Arc::clone(&prog_group);
-        let textures_dir = textures_dir.clone();
-        let out_dir = out_dir.clone();
-        threads.push(thread::spawn(move || {
-            let mut p = Progress::builder(prog_group)
-                .label(&pack.name)
+    let transforms: Vec<_> = packs.iter().map(|pack| ProtectFonts(pack)).collect();
+    let mut generator = Generator::new(&options);
+    for (pack, transform) in packs.iter().zip(&transforms) {
+        generator.add(pack.name, pack.desc, transform);
+    }
+
+    let outputs = generator.run(|name| {
+        Box::new(
+            Progress::builder(Arc::clone(&prog_group))
+                .label(name)
                 .init(0)
                 .max(num_files - 1)
                 .build()
-                .unwrap();
-            let res = generate_pack(
-                pack.name,
-                pack.desc,
-                &mut p,
-                &textures_dir,
-                &out_dir,
-                pack_format,
-                pack.func,
-            );
-            match res {
-                Ok(()) => {}
-                Err(e) => {
-                    eprintln!("Error while generating pack \"{}\": {:?}", pack.name, e);
-                }
-            }
-        }));
+                .unwrap(),
+        )
+    })?;
+    for (name, res) in outputs {
+        if let Err(e) = res {
+            tracing::error!("Error while generating pack \"{}\": {:?}", name, e);
+        }
     }
 
-    threads
-        .into_iter()
-        .try_for_each(JoinHandle::join)
-        .expect("Waiting for threads to finish");
-
     prog_group.draw();
 
     Ok(())
@@ -0,0 +1,246 @@
+//! Colour grades from 3D lookup tables in `.cube` files, which most photo and
+//! video editors can export, so a look designed on a screenshot can be given
+//! to every texture:
+//!
+//! ```toml
+//! filters = [{ type = "lut", path = "grades/teal-orange.cube" }]
+//! ```
+//!
+//! The format is Adobe's Cube LUT Specification 1.0, of which only 3D tables
+//! are supported.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use image::DynamicImage;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{GenRpError, IoContext, Result},
+    filter::Transform,
+};
+
+/// A table, and when its file was modified
+type Cached = (Option<SystemTime>, Arc<Lut>);
+
+lazy_static! {
+    /// Tables already read, by path, so that an edited one is read again
+    static ref CACHE: Mutex<HashMap<PathBuf, Cached>> = Mutex::default();
+}
+
+/// Most entries along each side of a table, as in the spec
+const MAX_SIZE: usize = 256;
+
+/// A 3D lookup table, mapping each colour (with channels from 0 to 1) to
+/// another
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut {
+    /// Entries along each side
+    pub size: usize,
+    pub domain_min: [f32; 3],
+    pub domain_max: [f32; 3],
+    /// `size³` colours, with red changing fastest, then green, then blue
+    pub table: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Parse the text of a `.cube` file
+    pub fn parse(s: &str) -> std::result::Result<Self, String> {
+        let mut size = None;
+        let mut domain_min = [0.; 3];
+        let mut domain_max = [1.; 3];
+        let mut table = Vec::new();
+
+        let floats = |parts: &[&str], line: &str| {
+            parts
+                .iter()
+                .map(|p| p.parse::<f32>())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|_| format!("bad line: {}", line))
+        };
+        let triple = |parts: &[&str], line: &str| match floats(parts, line)?[..] {
+            [r, g, b] => Ok([r, g, b]),
+            _ => Err(format!("bad line: {}", line)),
+        };
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<_> = line.split_whitespace().collect();
+            match parts[0] {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => return Err("1D LUTs aren't supported".into()),
+                "LUT_3D_SIZE" => {
+                    let n = parts
+                        .get(1)
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .filter(|n| (2..=MAX_SIZE).contains(n))
+                        .ok_or_else(|| format!("bad size: {}", line))?;
+                    size = Some(n);
+                }
+                "DOMAIN_MIN" => domain_min = triple(&parts[1..], line)?,
+                "DOMAIN_MAX" => domain_max = triple(&parts[1..], line)?,
+                // DaVinci Resolve's way of setting the domain
+                "LUT_3D_INPUT_RANGE" => match floats(&parts[1..], line)?[..] {
+                    [min, max] => {
+                        domain_min = [min; 3];
+                        domain_max = [max; 3];
+                    }
+                    _ => return Err(format!("bad line: {}", line)),
+                },
+                _ => table.push(triple(&parts, line)?),
+            }
+        }
+
+        let size = size.ok_or("missing LUT_3D_SIZE")?;
+        if table.len() != size.pow(3) {
+            return Err(format!(
+                "expected {} entries for a size of {}, found {}",
+                size.pow(3),
+                size,
+                table.len()
+            ));
+        }
+        if (0..3).any(|c| domain_max[c] <= domain_min[c]) {
+            return Err("DOMAIN_MAX must be above DOMAIN_MIN".into());
+        }
+
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Read the `.cube` file at `path`, or the copy read before if it hasn't
+    /// changed since
+    pub fn load(path: &Path) -> Result<Arc<Self>> {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut cache = CACHE.lock().expect("LUT cache poisoned");
+        if let Some((when, lut)) = cache.get(path)
+            && modified.is_some()
+            && *when == modified
+        {
+            return Ok(lut.clone());
+        }
+
+        let text =
+            fs::read_to_string(path).io_context(|| format!("Reading LUT {}", path.display()))?;
+        let lut = Arc::new(Self::parse(&text).map_err(|reason| GenRpError::File {
+            path: path.to_path_buf(),
+            reason,
+        })?);
+        cache.insert(path.to_path_buf(), (modified, lut.clone()));
+        Ok(lut)
+    }
+
+    fn get(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[r + self.size * (g + self.size * b)]
+    }
+
+    /// Look up `rgb`, interpolating between the 8 entries around it
+    pub fn sample(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+        let pos: [f32; 3] = std::array::from_fn(|c| {
+            let t = (rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c]);
+            t.clamp(0., 1.) * max
+        });
+        let lo = pos.map(|p| (p.floor() as usize).min(self.size - 2));
+        let t: [f32; 3] = std::array::from_fn(|c| pos[c] - lo[c] as f32);
+
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| -> [f32; 3] {
+            std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t)
+        };
+        let corner = |dr, dg, db| self.get(lo[0] + dr, lo[1] + dg, lo[2] + db);
+        let g0 = lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), t[0]),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), t[0]),
+            t[1],
+        );
+        let g1 = lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), t[0]),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), t[0]),
+            t[1],
+        );
+        lerp(g0, g1, t[2])
+    }
+}
+
+/// Grade every texture with the table in the `.cube` file at `path`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LutFilter {
+    /// The `.cube` file, relative to the config file
+    pub path: PathBuf,
+    /// How much of the graded colour to use, from 0 to 1
+    #[serde(default = "default_strength")]
+    pub strength: f32,
+    #[serde(skip)]
+    lut: Option<Arc<Lut>>,
+}
+
+fn default_strength() -> f32 {
+    1.
+}
+
+impl LutFilter {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        Ok(Self {
+            lut: Some(Lut::load(&path)?),
+            path,
+            strength: default_strength(),
+        })
+    }
+
+    /// Read the table, with `path` relative to `base`
+    pub(crate) fn resolve(&mut self, base: &Path) -> Result<()> {
+        self.path = base.join(&self.path);
+        self.lut = Some(Lut::load(&self.path)?);
+        Ok(())
+    }
+}
+
+impl Transform for LutFilter {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let lut = self
+            .lut
+            .as_ref()
+            .expect("LUTs are read when their filter is loaded");
+        let mut image = image.into_rgba8();
+
+        for px in image.pixels_mut() {
+            let rgb = [px[0], px[1], px[2]].map(|c| c as f32 / 255.);
+            let graded = lut.sample(rgb);
+            for c in 0..3 {
+                let v = rgb[c] + (graded[c] - rgb[c]) * self.strength;
+                px[c] = (v.clamp(0., 1.) * 255.).round() as u8;
+            }
+        }
+
+        image.into()
+    }
+}
+
+impl fmt::Debug for LutFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LutFilter")
+            .field("path", &self.path)
+            .field("strength", &self.strength)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for LutFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.strength == other.strength
+    }
+}
@@ -1,229 +1,1205 @@
-use std::{path::Path, sync::Arc, thread::JoinHandle};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
 
 use anyhow::Context;
 use clap::Parser;
 use gen_rp_rs::{
-    Pack, Version,
-    colour::{hsv_to_rgb, rgb_to_hsv, to_8bit},
-    extract_jar, generate_pack,
+    ErrorPolicy, GenerateOptions, PackOutput, Version, VersionJson, analyze, builtin,
+    cache::CacheDir,
+    config::Config,
+    curseforge::{self, ReleaseType, UploadFileReq},
+    datapack::DataPack,
+    diff::{self, ChangeKind},
+    dither::Dither,
+    download::sha1_file,
+    dry_run_pack,
+    emissive::{self, EmissiveOptions},
+    error::GenRpError,
+    extract_jar, extract_mod_jar, extract_pack,
+    extrude::{self, ExtrudeOptions, ItemModels},
+    filter::{OnlyCategories, ProtectFonts, RandomHue, Transform},
+    generated::{Described, Generated},
+    generator::Generator,
+    install,
+    lang::{self, MockingCase, Pirate, Reversed, TextTransform, Uppercase, Uwu},
+    merge,
+    models::BlockModels,
+    modrinth::{self, CreateVersionReq, ModifyVersionReq, VersionStatus, VersionType},
+    optimize::OptLevel,
+    output::{OutputFormat, PackData, check_pack_name},
+    palette::{Palette, PaletteMap},
+    pbr::PbrOptions,
+    preview::{ContactSheet, Gallery},
+    progress::{JsonProgress, ProgressEvent, ProgressSink},
+    serve,
+    shaders::ShaderTemplates,
+    timings,
+    variants::{self, VariantOptions},
 };
-use image::Rgba;
+use notify::{RecursiveMode, Watcher};
 use prog::{Progress, ProgressGroup};
-use tempfile::TempDir;
+use rand::{SeedableRng, rngs::StdRng};
+use tracing::{debug, error, info, info_span, warn};
+use tracing_subscriber::EnvFilter;
 use walkdir::WalkDir;
 
 #[derive(clap::Parser)]
 struct Cli {
     #[clap(short, long)]
     slug: Option<String>,
+    /// Where to keep downloaded jars and extracted textures, defaults to the
+    /// platform cache directory
+    #[clap(long, global = true)]
+    cache_dir: Option<PathBuf>,
+    /// Where to write the packs
+    #[clap(short, long, default_value = "out")]
+    out_dir: PathBuf,
+    /// List what would be written to each pack without writing anything
+    #[clap(long)]
+    dry_run: bool,
+    /// Produce byte-identical zips for the same input, by sorting entries
+    /// and fixing timestamps and permissions
+    #[clap(long)]
+    reproducible: bool,
+    /// Write packs as zips, unzipped directories, or `.tar.gz`s
+    #[clap(long, value_enum, default_value = "zip")]
+    output_format: OutputKind,
+    /// With `--output-format dir`, keep the files of packs from a run that
+    /// didn't finish and only write the ones that are missing or changed
+    #[clap(long)]
+    resume: bool,
+    /// How many MiB of decoded textures to hold at once while generating
+    #[clap(long, default_value_t = 512)]
+    memory_budget: u64,
+    /// How many threads to generate with, one for each core if 0
+    #[clap(short, long, default_value_t = 0)]
+    jobs: usize,
+    /// Copy textures that aren't PNGs (like `.tga`s from mods) as they are,
+    /// instead of transforming them and writing them as PNGs
+    #[clap(long)]
+    preserve_formats: bool,
+    /// Show progress as terminal progress bars, or as a line of JSON for
+    /// each update and finished pack, for tools running the generator
+    #[clap(long, value_enum, default_value = "bars")]
+    progress: ProgressKind,
+    /// Only log warnings, or just errors with `-qq`
+    #[clap(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "verbose")]
+    quiet: u8,
+    /// Log more of what is going on, or everything with `-vv`.  `RUST_LOG`
+    /// overrides both this and `--quiet`
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Fail a pack when one of its textures can't be read or encoded,
+    /// instead of leaving the texture out with a warning
+    #[clap(long)]
+    fail_fast: bool,
+    /// Fill fully transparent pixels with the colour of their neighbours,
+    /// so they don't darken the edges of leaves and grass in mipmaps
+    #[clap(long)]
+    bleed_alpha: bool,
+    /// Shrink the PNGs in each pack with oxipng.  `max` is much slower for
+    /// a few percent more.
+    #[clap(long, value_enum, default_value = "none")]
+    optimize: OptimizeKind,
+    /// Also build a "Natural Variation" pack with random variants of these
+    /// block textures (like `block/stone`), or of some stone, dirt and
+    /// sand-like ones if none are given
+    #[clap(long, value_delimiter = ',', num_args = 0.., value_name = "TEXTURES")]
+    variants: Option<Vec<String>>,
+    /// Variants of each texture in the "Natural Variation" pack
+    #[clap(long, default_value_t = 3, requires = "variants")]
+    variant_count: usize,
+    /// Also build a "Glowing" pack of OptiFine emissive textures for the
+    /// block and item textures with these words in their names, or for ores,
+    /// torches and other light sources if none are given
+    #[clap(long, value_delimiter = ',', num_args = 0.., value_name = "KEYWORDS")]
+    emissive: Option<Vec<String>>,
+    /// How bright (from 0 to 1) pixels must be to glow in the "Glowing" pack
+    #[clap(long, default_value_t = EmissiveOptions::default().threshold, requires = "emissive")]
+    emissive_threshold: f32,
+    /// Also build a "3D Items" pack, with item models extruded from their
+    /// textures so they look thicker
+    #[clap(long)]
+    extrude_items: bool,
+    /// How thick items are in the "3D Items" pack, in pixels
+    #[clap(long, default_value_t = ExtrudeOptions::default().thickness, requires = "extrude_items")]
+    item_thickness: f32,
+    /// Also write `<pack>.contact.png` next to each pack, with every block
+    /// and item texture before and after
+    #[clap(long)]
+    contact_sheet: bool,
+    /// Textures across each contact sheet
+    #[clap(long, default_value_t = 16, requires = "contact_sheet")]
+    sheet_columns: u32,
+    /// Write the name of each texture on the contact sheets
+    #[clap(long, requires = "contact_sheet")]
+    sheet_labels: bool,
+    /// Also write `<pack>.preview/index.html` next to each pack, a page with
+    /// every block and item texture before and after
+    #[clap(long)]
+    gallery: bool,
+    /// Put the images in the gallery pages, instead of next to them
+    #[clap(long, requires = "gallery")]
+    gallery_embed: bool,
+    /// Also build a data pack from the templates in this directory (laid out
+    /// like a data pack's `data` directory), filling in `${SEED}` and
+    /// `${VERSION}`
+    #[clap(long, value_name = "DIR")]
+    datapack: Option<PathBuf>,
+    /// Add the shaders in this directory (laid out like
+    /// `assets/minecraft/shaders`) to every pack, filling in `${TINT}` and
+    /// `${SEED}` for each
+    #[clap(long, value_name = "DIR")]
+    shaders: Option<PathBuf>,
+    /// Run every transform on the CPU, even with a GPU available
+    #[cfg(feature = "gpu")]
+    #[clap(long)]
+    no_gpu: bool,
+    /// Also build the language packs (UPPERCASE, mOcKiNg, Reversed, Pirate
+    /// and Uwu)
+    #[clap(long)]
+    lang: bool,
+    /// Also build the sound packs (Nightcore, 8-bit Audio and Backwards),
+    /// downloading the sounds on first use
+    #[cfg(feature = "sound")]
+    #[clap(long)]
+    sounds: bool,
+    /// Also build a "Custom Font" pack with the game's text in this TrueType
+    /// or OpenType font
+    #[cfg(feature = "font")]
+    #[clap(long, value_name = "TTF")]
+    font: Option<PathBuf>,
+    /// Size of each glyph in the "Custom Font" pack, in pixels
+    #[cfg(feature = "font")]
+    #[clap(long, default_value_t = 16, requires = "font")]
+    font_size: u32,
+    /// Keep the edges of glyphs in the "Custom Font" pack smooth
+    #[cfg(feature = "font")]
+    #[clap(long, requires = "font")]
+    font_antialias: bool,
+    /// Exit with an error if generating any pack gave warnings
+    #[clap(long)]
+    deny_warnings: bool,
+    /// Print how long each pack spent decoding, transforming, encoding and
+    /// writing, to find what's slowing it down
+    #[clap(long)]
+    timings: bool,
+    /// Also write the SHA-1 of each pack next to it, like `<pack>.zip.sha1`
+    #[clap(long)]
+    write_sha1: bool,
+    /// Also build a "Palette" pack mapping textures to the colours in this
+    /// file (a list of hex colours, a GIMP `.gpl`, or a `.png` strip)
+    #[clap(long)]
+    palette: Option<PathBuf>,
+    /// Dither the "Palette" pack
+    #[clap(long, requires = "palette")]
+    palette_dither: bool,
+    /// Seed for the randomness in the built-in packs (like "Chaos"), the same
+    /// seed always gives the same packs
+    #[clap(long, default_value_t = 0)]
+    seed: u64,
+    /// Also transform font textures, which usually makes them unreadable
+    #[clap(long)]
+    no_protect_fonts: bool,
+    /// Keep the (transformed) vanilla `pack.png` as it is, instead of
+    /// labelling it with the pack's name and colours
+    #[clap(long)]
+    no_pack_icon: bool,
+    /// Also write LabPBR normal and specular maps (`_n` and `_s`) for each
+    /// block texture, for shader packs
+    #[clap(long)]
+    pbr: bool,
+    /// How steep the normals of `--pbr` are
+    #[clap(long, default_value_t = PbrOptions::default().normal_strength, requires = "pbr")]
+    pbr_strength: f32,
+    /// Only include textures in these categories (like `item` or `block`,
+    /// the directories under `textures`) in each pack
+    #[clap(long, value_delimiter = ',', value_name = "CATEGORIES")]
+    only: Vec<String>,
+    /// Transform this resource pack (a zip, laid over the vanilla textures)
+    /// instead of just the vanilla textures
+    #[clap(long)]
+    input: Option<PathBuf>,
+    /// Also transform the textures of this mod jar, can be given multiple
+    /// times
+    #[clap(long = "mod", value_name = "JAR")]
+    mods: Vec<PathBuf>,
+    /// Copy the packs into `.minecraft/resourcepacks`
+    #[clap(long)]
+    install: bool,
+    /// Where to install packs, defaults to the platform's `.minecraft`
+    #[clap(long, requires = "install")]
+    minecraft_dir: Option<PathBuf>,
+    /// Replace packs of the same name when installing
+    #[clap(long, requires = "install")]
+    replace: bool,
+    /// Upload each pack to `s3://bucket/prefix` (credentials from the
+    /// `AWS_*` environment variables) or `PUT` it under an http(s) url
+    #[cfg(feature = "upload")]
+    #[clap(long, value_name = "URL")]
+    upload: Option<String>,
+    /// Also build the packs defined in this TOML file
+    #[clap(long)]
+    config: Option<PathBuf>,
     version: Option<String>,
+    /// Build every pack for each of these versions instead, into
+    /// `<out-dir>/<version>`
+    #[clap(long, value_delimiter = ',', conflicts_with = "version")]
+    versions: Vec<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-const PACKS: &[Pack] = &[
-    Pack {
-        name: "Saturation",
-        desc: "§6Saturates all textures\n§3By: funnyboy_roks",
-        slug: "unused",
-        func: |image| {
-            let mut image = image.into_rgba8();
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Manage the download cache
+    #[clap(subcommand)]
+    Cache(CacheCommand),
+    /// Build the packs in `--config`, then rebuild them whenever it (or
+    /// `--input`) changes
+    Watch,
+    /// Host the packs in `--out-dir` over HTTP, for testing with a local
+    /// server
+    Serve {
+        #[clap(long, default_value = "127.0.0.1:8000")]
+        addr: String,
+    },
+    /// Combine packs (zips or directories) into `<out-dir>/<name>.zip`, later
+    /// packs overriding earlier ones
+    Merge {
+        #[clap(required = true, num_args = 2..)]
+        packs: Vec<PathBuf>,
+        #[clap(long)]
+        name: String,
+        /// Description of the merged pack, defaults to the last pack's
+        #[clap(long)]
+        description: Option<String>,
+    },
+    /// Write statistics about every texture (size, colours, alpha usage)
+    Analyze {
+        #[clap(long, value_enum, default_value = "json")]
+        format: ReportFormat,
+        /// How many dominant colours to find for each texture
+        #[clap(short, default_value_t = 4)]
+        k: usize,
+        /// Where to write the report, defaults to stdout
+        #[clap(long)]
+        output: Option<PathBuf>,
+    },
+    /// Release a pack on Modrinth and/or CurseForge, with tokens from
+    /// `MODRINTH_TOKEN` and `CURSEFORGE_TOKEN`
+    Publish(PublishArgs),
+    /// Show which textures differ between two packs (zips or directories)
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        /// Write an image comparing each changed texture to this directory
+        #[clap(long)]
+        images: Option<PathBuf>,
+    },
+    /// Show how a pack was generated, from its `generated.json`
+    Describe {
+        pack: PathBuf,
+        /// Print the `generated.json` as it is
+        #[clap(long)]
+        json: bool,
+    },
+    /// Make a pack again from its `generated.json` (or the pack with it),
+    /// for the version given to the command or else the one it was made for
+    Regenerate {
+        input: PathBuf,
+        /// Name of the new pack, defaults to the name of `input` (which is
+        /// needed for a `generated.json` on its own)
+        #[clap(long)]
+        name: Option<String>,
+    },
+    /// Find a palette for each image (or each PNG in a directory) with
+    /// k-means, writing the recoloured image, a swatch of the palette and its
+    /// hex colours to `--output`
+    Palette {
+        #[clap(required = true)]
+        inputs: Vec<PathBuf>,
+        /// How many colours to find for each image
+        #[clap(short, default_value_t = 4)]
+        k: usize,
+        #[clap(long, default_value = "palettes")]
+        output: PathBuf,
+    },
+}
 
-            let (width, height) = image.dimensions();
-            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
-                let px = image.get_pixel_mut(x, y);
+#[derive(clap::Args)]
+#[clap(group(clap::ArgGroup::new("site").required(true).multiple(true)))]
+struct PublishArgs {
+    zip: PathBuf,
+    /// Modrinth project id or slug
+    #[clap(long, group = "site")]
+    modrinth: Option<String>,
+    /// CurseForge project id
+    #[clap(long, group = "site")]
+    curseforge: Option<String>,
+    /// Defaults to the first game version.  An existing Modrinth version
+    /// with this number gets the zip as its primary file instead of creating
+    /// a new one.
+    #[clap(long)]
+    version_number: Option<String>,
+    #[clap(long, default_value = "")]
+    changelog: String,
+    #[clap(long, value_enum, default_value = "release")]
+    release_type: ReleaseKind,
+    /// Game versions the pack works with, defaults to the version given to
+    /// the command (or the latest)
+    #[clap(long = "game-version")]
+    game_versions: Vec<String>,
+}
 
-                let mut hsv = rgb_to_hsv(&[px[0], px[1], px[2]]);
-                hsv[1] = (hsv[1] * 2.).min(1.);
-                let rgb = hsv_to_rgb(hsv);
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ReleaseKind {
+    Release,
+    Beta,
+    Alpha,
+}
 
-                px.0[..3].copy_from_slice(&rgb);
-            }
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputKind {
+    Zip,
+    Dir,
+    TarGz,
+}
 
-            image.into()
-        },
-    },
-    Pack {
-        name: "Greyscale",
-        desc: "§7All Textures are Greyscale\n§3By: funnyboy_roks",
-        slug: "unused",
-        func: |image| image.grayscale(),
-    },
-    Pack {
-        name: "Invert",
-        desc: "§6All Textures are Inverted\n§3By: funnyboy_roks",
-        slug: "unused",
-        func: |mut image| {
-            image.invert();
-            image
-        },
-    },
-    Pack {
-        name: "1-bit",
-        desc: "§6Convert all textures to 1-bit\n§3By: funnyboy_roks",
-        slug: "unused",
-        func: |image| {
-            let mut image = image.into_rgba8();
-
-            let (width, height) = image.dimensions();
-            let (width, height) = (width as usize, height as usize);
-            let mut px: Vec<_> = image
-                .pixels()
-                .map(|p| Rgba::<i32>([p.0[0] as _, p.0[1] as _, p.0[2] as _, p.0[3] as _]))
-                .collect();
-
-            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
-                let old = px[y * width + x];
-                let new = to_8bit(old);
-                px[y * width + x] = new;
-                let quant = [
-                    old.0[0] - new.0[0],
-                    old.0[1] - new.0[1],
-                    old.0[2] - new.0[2],
-                    old.0[3] - new.0[3],
-                ];
-                // dbg!(old, new, diff);
-
-                let mut add = |dx, dy, numerator, denominator| {
-                    let x = x.checked_add_signed(dx)?;
-                    if x >= width {
-                        return None;
-                    };
-                    let y = y.checked_add_signed(dy)?;
-                    if y >= height {
-                        return None;
-                    };
-                    let a = &mut px[y * width + x];
-                    a.0[0] += quant[0] * numerator / denominator;
-                    a.0[1] += quant[1] * numerator / denominator;
-                    a.0[2] += quant[2] * numerator / denominator;
-                    a.0[3] += quant[3] * numerator / denominator;
-                    Some(())
-                };
-
-                add(1, 0, 7, 16);
-                add(-1, 1, 3, 16);
-                add(0, 1, 5, 16);
-                add(1, 1, 1, 16);
-            }
+impl From<OutputKind> for OutputFormat {
+    fn from(kind: OutputKind) -> Self {
+        match kind {
+            OutputKind::Zip => OutputFormat::Zip,
+            OutputKind::Dir => OutputFormat::Dir,
+            OutputKind::TarGz => OutputFormat::TarGz,
+        }
+    }
+}
 
-            image.pixels_mut().zip(px).for_each(|(old, new)| {
-                old.0[0] = new.0[0].clamp(0, 255) as u8;
-                old.0[1] = new.0[1].clamp(0, 255) as u8;
-                old.0[2] = new.0[2].clamp(0, 255) as u8;
-                old.0[3] = new.0[3].clamp(0, 255) as u8;
-            });
+#[derive(Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ProgressKind {
+    Bars,
+    /// A line of JSON for each update, see [`ProgressEvent`]
+    Json,
+}
 
-            image.into()
-        },
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum OptimizeKind {
+    None,
+    Fast,
+    Max,
+}
+
+impl From<OptimizeKind> for OptLevel {
+    fn from(kind: OptimizeKind) -> Self {
+        match kind {
+            OptimizeKind::None => OptLevel::None,
+            OptimizeKind::Fast => OptLevel::Fast,
+            OptimizeKind::Max => OptLevel::Max,
+        }
+    }
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ReportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(clap::Subcommand)]
+enum CacheCommand {
+    /// Remove cached jars and textures
+    Clean {
+        /// Only remove the cache for this version
+        version: Option<String>,
     },
-    Pack {
-        name: "Average",
-        desc: "§6Averages all textures\n§3By: funnyboy_roks",
-        slug: "unused",
-        func: |image| {
-            let mut image = image.into_rgba8();
-
-            let (mut r, mut g, mut b) = (0u32, 0u32, 0u32);
-            let mut i = 0u32;
-
-            let (width, height) = image.dimensions();
-
-            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
-                let px = image.get_pixel(x, y);
-
-                if px[3] > 0 {
-                    r += px[0] as u32;
-                    g += px[1] as u32;
-                    b += px[2] as u32;
-                    i += 1;
-                }
-            }
+}
 
-            if i == 0 {
-                return image.into();
-            }
+/// Log to stderr, so logs stay out of the way of output like `diff` and
+/// `--progress json`
+fn init_logging(cli: &Cli) {
+    let level = match i16::from(cli.verbose) - i16::from(cli.quiet) {
+        ..=-2 => "error",
+        -1 => "warn",
+        0 => "info",
+        1 => "debug",
+        _ => "trace",
+    };
+    // other crates (wgpu especially) are very chatty below warn
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,gen_rp_rs={}", level)));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    init_logging(&cli);
 
-            let r = (r / i) as u8;
-            let g = (g / i) as u8;
-            let b = (b / i) as u8;
+    let cache = match &cli.cache_dir {
+        Some(dir) => CacheDir::new(dir),
+        None => CacheDir::default_location()?,
+    };
 
-            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
-                let px = image.get_pixel_mut(x, y);
+    if let Some(Command::Cache(CacheCommand::Clean { version })) = &cli.command {
+        match version {
+            Some(id) => cache.clean_version(id)?,
+            None => cache.clean()?,
+        }
+        info!("Cleaned cache at {}", cache.root().display());
+        return Ok(());
+    }
+
+    if cli.install && cli.output_format != OutputKind::Zip {
+        anyhow::bail!("--install only works with zip packs");
+    }
+    #[cfg(feature = "upload")]
+    if cli.upload.is_some() && cli.output_format != OutputKind::Zip {
+        anyhow::bail!("--upload only works with zip packs");
+    }
+    let format = OutputFormat::from(cli.output_format);
+
+    let resourcepacks = if cli.install {
+        Some(match &cli.minecraft_dir {
+            Some(dir) => dir.join("resourcepacks"),
+            None => install::resourcepacks_dir()?,
+        })
+    } else {
+        None
+    };
 
-                if px[3] > 0 {
-                    px[0] = r;
-                    px[1] = g;
-                    px[2] = b;
+    if let Some(Command::Merge {
+        packs,
+        name,
+        description,
+    }) = &cli.command
+    {
+        check_pack_name(name)?;
+        let sink = format.sink(&format.path(&cli.out_dir, name), cli.reproducible)?;
+        let output =
+            merge::merge_packs(packs, sink, description.as_deref()).context("Merging packs")?;
+        return finish_packs(&cli, vec![(name, output)], resourcepacks.as_deref());
+    }
+
+    if let Some(Command::Diff { old, new, images }) = &cli.command {
+        let diff = diff::diff_packs(old, new, images.as_deref()).context("Comparing packs")?;
+        for path in &diff.added {
+            println!("+ {}", path.display());
+        }
+        for path in &diff.removed {
+            println!("- {}", path.display());
+        }
+        for change in &diff.changed {
+            match change.kind {
+                ChangeKind::Pixels { changed, total } => {
+                    println!("~ {} ({}/{} pixels)", change.path.display(), changed, total)
                 }
+                ChangeKind::Resized { old, new } => println!(
+                    "~ {} (resized {}x{} -> {}x{})",
+                    change.path.display(),
+                    old.0,
+                    old.1,
+                    new.0,
+                    new.1
+                ),
+                ChangeKind::Contents => println!("~ {}", change.path.display()),
             }
+        }
+        println!(
+            "{} added, {} removed, {} changed, {} unchanged",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.changed.len(),
+            diff.unchanged
+        );
+        return Ok(());
+    }
+
+    if let Some(Command::Describe { pack, json }) = &cli.command {
+        let generated =
+            Generated::read(pack).with_context(|| format!("Reading {}", pack.display()))?;
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&generated)?);
+        } else {
+            println!("{}", generated);
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Publish(args)) = &cli.command {
+        return publish(&cli, args);
+    }
+
+    if let Some(Command::Palette { inputs, k, output }) = &cli.command {
+        return extract_palettes(inputs, *k, output, cli.seed);
+    }
+
+    if let Some(Command::Serve { addr }) = &cli.command {
+        info!("Serving {} on http://{}", cli.out_dir.display(), addr);
+        serve::serve(addr, &cli.out_dir)?;
+        return Ok(());
+    }
+
+    if !cli.versions.is_empty() {
+        if cli.command.is_some() {
+            anyhow::bail!("--versions only works when building packs");
+        }
+        if cli.install {
+            anyhow::bail!("--install only works with a single version");
+        }
+        return build_matrix(&cli, &cache);
+    }
+
+    if let Some(Command::Regenerate { input, name }) = &cli.command {
+        return regenerate(
+            &cli,
+            &cache,
+            input,
+            name.as_deref(),
+            resourcepacks.as_deref(),
+        );
+    }
+
+    let version = if let Some(id) = &cli.version {
+        Version::get_by_id(id).context("Fetching version")?
+    } else {
+        Version::get_latest().context("Getting latest version")?
+    };
+
+    let (textures_dir, pack_format) = prepare_textures(&cli, &cache, &version)?;
+
+    let options = generate_options(&cli, &version, textures_dir, &cli.out_dir, pack_format)?;
+
+    if let Some(Command::Analyze { format, k, output }) = &cli.command {
+        let stats = analyze::analyze_textures(&options.textures_dir, *k)?;
+        let out: Box<dyn std::io::Write> = match output {
+            Some(path) => Box::new(BufWriter::new(
+                File::create(path).with_context(|| format!("Creating {}", path.display()))?,
+            )),
+            None => Box::new(std::io::stdout().lock()),
+        };
+        match format {
+            ReportFormat::Json => analyze::write_json(&stats, out)?,
+            ReportFormat::Csv => analyze::write_csv(&stats, out)?,
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Watch) = &cli.command {
+        return watch(&cli, &cache, &version, &options, resourcepacks.as_deref());
+    }
+
+    stop_on_ctrl_c(&options.cancel)?;
+    build(&cli, &cache, &version, &options, resourcepacks.as_deref())
+}
+
+/// Rebuild the pack described by the `generated.json` at `input` (or in the
+/// pack at `input`), with the seed and options it was made with
+fn regenerate(
+    cli: &Cli,
+    cache: &CacheDir,
+    input: &Path,
+    name: Option<&str>,
+    resourcepacks: Option<&Path>,
+) -> anyhow::Result<()> {
+    let generated =
+        Generated::read(input).with_context(|| format!("Reading {}", input.display()))?;
+    let transform = Described::parse(&generated.transform)
+        .with_context(|| format!("Rebuilding the transform of {}", input.display()))?;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None if input.extension().is_some_and(|ext| ext == "json") => {
+            anyhow::bail!("--name is needed to regenerate from a generated.json on its own")
+        }
+        None => {
+            let file_name = input.file_name().context("Pack has no file name")?;
+            let file_name = file_name.to_string_lossy();
+            [".zip", ".tar.gz"]
+                .iter()
+                .find_map(|ext| file_name.strip_suffix(ext))
+                .unwrap_or(&file_name)
+                .to_string()
+        }
+    };
+
+    let version = match cli
+        .version
+        .as_ref()
+        .or(generated.minecraft_version.as_ref())
+    {
+        Some(id) => Version::get_by_id(id).context("Fetching version")?,
+        None => Version::get_latest().context("Getting latest version")?,
+    };
+    let (textures_dir, pack_format) = prepare_textures(cli, cache, &version)?;
+    let options = GenerateOptions {
+        seed: generated.seed,
+        bleed_alpha: generated.bleed_alpha,
+        optimize: generated.optimize,
+        preserve_formats: generated.preserve_formats,
+        pbr: generated.pbr,
+        ..generate_options(cli, &version, textures_dir, &cli.out_dir, pack_format)?
+    };
+    info!(
+        "Regenerating {} for {} (pack format {})",
+        name, version.id, pack_format
+    );
 
-            image.into()
+    stop_on_ctrl_c(&options.cancel)?;
+    let packs: [(&str, &str, &dyn Transform); 1] = [(&name, &generated.description, &transform)];
+    let outputs = generate_packs(&packs, &options, cli.progress)?;
+    if options.cancelled() {
+        interrupted(&outputs);
+    }
+    finish_packs(cli, outputs, resourcepacks)
+}
+
+/// Build every pack for each of `--versions`, into `<out-dir>/<version>`.
+/// The jars are downloaded and extracted in parallel, then the versions are
+/// built one at a time, as each build already has a thread per pack and uses
+/// the whole memory budget.
+fn build_matrix(cli: &Cli, cache: &CacheDir) -> anyhow::Result<()> {
+    let ids: BTreeSet<_> = cli.versions.iter().collect();
+    let prepared = thread::scope(|s| {
+        let threads: Vec<_> = ids
+            .into_iter()
+            .map(|id| {
+                s.spawn(move || -> anyhow::Result<_> {
+                    let version = Version::get_by_id(id)
+                        .with_context(|| format!("Fetching version {}", id))?;
+                    let (textures_dir, pack_format) = prepare_textures(cli, cache, &version)
+                        .with_context(|| format!("Preparing textures for {}", id))?;
+                    Ok((version, textures_dir, pack_format))
+                })
+            })
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| thread.join().expect("version thread panicked"))
+            .collect::<anyhow::Result<Vec<_>>>()
+    })?;
+
+    let cancel = Arc::default();
+    stop_on_ctrl_c(&cancel)?;
+    for (version, textures_dir, pack_format) in prepared {
+        let _span = info_span!("version", id = %version.id).entered();
+        info!("{} (pack format {})", version.id, pack_format);
+        let out_dir = cli.out_dir.join(&version.id);
+        let options = GenerateOptions {
+            cancel: Arc::clone(&cancel),
+            ..generate_options(cli, &version, textures_dir, &out_dir, pack_format)?
+        };
+        build(cli, cache, &version, &options, None)?;
+    }
+
+    Ok(())
+}
+
+/// Stop generating on the first ctrl-C, so that [`build`] can list the packs
+/// that were finished, and quit straight away on the second
+fn stop_on_ctrl_c(cancel: &Arc<AtomicBool>) -> anyhow::Result<()> {
+    let cancel = Arc::clone(cancel);
+    ctrlc::set_handler(move || {
+        if cancel.swap(true, Ordering::Relaxed) {
+            std::process::exit(130);
+        }
+    })
+    .context("Setting ctrl-C handler")?;
+    Ok(())
+}
+
+fn generate_options(
+    cli: &Cli,
+    version: &Version,
+    textures_dir: PathBuf,
+    out_dir: &Path,
+    pack_format: u32,
+) -> anyhow::Result<GenerateOptions> {
+    Ok(GenerateOptions {
+        format: OutputFormat::from(cli.output_format),
+        reproducible: cli.reproducible,
+        resume: cli.resume,
+        preserve_formats: cli.preserve_formats,
+        on_error: if cli.fail_fast {
+            ErrorPolicy::FailFast
+        } else {
+            ErrorPolicy::SkipAndWarn
         },
-    },
-    Pack {
-        name: "8bit",
-        desc: "§6All textures are 8-bit\n§3By: funnyboy_roks",
-        slug: "unused",
-        func: |image| {
-            let mut image = image.into_rgba8();
-
-            let (width, height) = image.dimensions();
-            for (x, y) in (0..height).flat_map(|y| (0..width).map(move |x| (x, y))) {
-                let px = image.get_pixel_mut(x, y);
-
-                px[0] = (px[0] / 32) * 32;
-                px[1] = (px[1] / 32) * 32;
-                px[2] = (px[2] / 64) * 64;
+        memory_budget: cli
+            .memory_budget
+            .checked_mul(1024 * 1024)
+            .context("--memory-budget is too large")?,
+        jobs: cli.jobs,
+        bleed_alpha: cli.bleed_alpha,
+        optimize: cli.optimize.into(),
+        seed: cli.seed,
+        minecraft_version: Some(version.id.clone()),
+        pack_icon: !cli.no_pack_icon,
+        pbr: cli.pbr.then_some(PbrOptions {
+            normal_strength: cli.pbr_strength,
+        }),
+        shaders: match &cli.shaders {
+            Some(dir) => ShaderTemplates::load(dir)
+                .with_context(|| format!("Loading shaders from {}", dir.display()))?,
+            None => ShaderTemplates::default(),
+        },
+        #[cfg(feature = "gpu")]
+        gpu: !cli.no_gpu,
+        ..GenerateOptions::new(textures_dir, out_dir, pack_format)
+    })
+}
+
+/// Build every pack for `version`, from the textures in `options`
+fn build(
+    cli: &Cli,
+    cache: &CacheDir,
+    version: &Version,
+    options: &GenerateOptions,
+    resourcepacks: Option<&Path>,
+) -> anyhow::Result<()> {
+    let palette_map = cli
+        .palette
+        .as_ref()
+        .map(|path| -> anyhow::Result<_> {
+            Ok(PaletteMap {
+                palette: Palette::load(path)
+                    .with_context(|| format!("Loading palette {}", path.display()))?,
+                dither: if cli.palette_dither {
+                    Dither::FloydSteinberg
+                } else {
+                    Dither::None
+                },
+            })
+        })
+        .transpose()?;
+
+    let config = cli
+        .config
+        .as_ref()
+        .map(|path| {
+            Config::load(path).with_context(|| format!("Loading config {}", path.display()))
+        })
+        .transpose()?;
+
+    let mut builtin: Vec<(&str, &str, &dyn Transform)> = builtin::PACKS
+        .iter()
+        .map(|p| (p.name, p.desc, p as &dyn Transform))
+        .collect();
+    let chaos = RandomHue { seed: cli.seed };
+    builtin.push((
+        "Chaos",
+        "§dEvery texture has a random hue\n§3By: funnyboy_roks",
+        &chaos,
+    ));
+    if let Some(palette_map) = &palette_map {
+        builtin.push((
+            "Palette",
+            "§6All textures use a custom palette\n§3By: funnyboy_roks",
+            palette_map,
+        ));
+    }
+    let protected: Vec<_> = builtin
+        .iter()
+        .map(|&(name, desc, func)| (name, desc, ProtectFonts(func)))
+        .collect();
+
+    let mut packs: Vec<(&str, &str, &dyn Transform)> = if cli.no_protect_fonts {
+        builtin
+    } else {
+        protected
+            .iter()
+            .map(|(name, desc, func)| (*name, *desc, func as &dyn Transform))
+            .collect()
+    };
+    // config packs have their own `protect_fonts`
+    if let Some(config) = &config {
+        packs.extend(config_packs(config));
+    }
+    let only: Vec<_> = packs
+        .iter()
+        .map(|&(name, desc, inner)| {
+            let categories = &*cli.only;
+            (name, desc, OnlyCategories { categories, inner })
+        })
+        .collect();
+    if !cli.only.is_empty() {
+        packs = only
+            .iter()
+            .map(|(name, desc, func)| (*name, *desc, func as &dyn Transform))
+            .collect();
+    }
+
+    if cli.dry_run {
+        for &(name, desc, func) in &packs {
+            let dry_run = dry_run_pack(name, desc, func, options)
+                .with_context(|| format!("Listing files for {}", name))?;
+            println!("{} -> {}", name, dry_run.path.display());
+            for file in &dry_run.files {
+                println!("  {}", file.display());
             }
+            println!(
+                "{} files, ~{:.1} MiB",
+                dry_run.files.len(),
+                dry_run.estimated_size as f64 / (1024. * 1024.)
+            );
+            println!("pack.mcmeta:\n{}\n", dry_run.pack_mcmeta);
+        }
+        return Ok(());
+    }
 
-            image.into()
-        },
-    },
-    // Pack {
-    //     name: "K-Means",
-    //     desc: "§6K-Means or something\n§3By: funnyboy_roks",
-    //     slug: "unused",
-    //     func: |image| {
-    //         let clusters = k_means(
-    //             4,
-    //             &image
-    //                 .pixels()
-    //                 .map(|(_, _, x)| Rgb::<u8>([x[0], x[1], x[2]]))
-    //                 .collect::<Vec<_>>(),
-    //         );
-    //         let mut image = image.into_rgba8();
-
-    //         for px in image.pixels_mut() {
-    //             if px[3] > 0 {
-    //                 let next = closest(Rgb::<u8>([px[0], px[1], px[2]]), &clusters);
-    //                 px.0[..3].copy_from_slice(&next.0);
-    //             }
-    //         }
-
-    //         image.into()
-    //     },
-    // },
-];
+    let mut outputs = generate_packs(&packs, options, cli.progress)?;
+    if options.cancelled() {
+        interrupted(&outputs);
+    }
+    if cli.contact_sheet {
+        let sheet = ContactSheet {
+            columns: cli.sheet_columns,
+            labels: cli.sheet_labels,
+            ..Default::default()
+        };
+        for &(name, _, func) in &packs {
+            match sheet.write(name, func, options) {
+                Ok(path) => info!("{}: contact sheet {}", name, path.display()),
+                Err(e) => error!("Error writing contact sheet for \"{}\": {:?}", name, e),
+            }
+        }
+    }
+    if cli.gallery {
+        let gallery = Gallery {
+            embed: cli.gallery_embed,
+        };
+        for &(name, desc, func) in &packs {
+            match gallery.write(name, desc, func, options) {
+                Ok(path) => info!("{}: gallery {}", name, path.display()),
+                Err(e) => error!("Error writing gallery for \"{}\": {:?}", name, e),
+            }
+        }
+    }
+    if cli.lang {
+        outputs.extend(generate_lang_packs(cache, version, options)?);
+    }
+    #[cfg(feature = "sound")]
+    if cli.sounds {
+        outputs.extend(generate_sound_packs(cache, version, options)?);
+    }
+    if let Some(config) = &config {
+        outputs.extend(generate_font_packs(config, options));
+    }
+    if let Some(textures) = &cli.variants {
+        let name = "Natural Variation";
+        match generate_variants(textures, cli.variant_count, cache, version, options) {
+            Ok(output) => outputs.push((name, output)),
+            Err(e) => error!("Error while generating pack \"{}\": {:?}", name, e),
+        }
+    }
+    if let Some(keywords) = &cli.emissive {
+        let name = "Glowing";
+        let emissive = EmissiveOptions {
+            threshold: cli.emissive_threshold,
+            keywords: if keywords.is_empty() {
+                EmissiveOptions::default().keywords
+            } else {
+                keywords.clone()
+            },
+        };
+        match emissive::generate_emissive_pack(
+            name,
+            "§eOres and light sources glow in the dark (needs OptiFine)\n§3By: funnyboy_roks",
+            &emissive,
+            options,
+        ) {
+            Ok(output) => outputs.push((name, output)),
+            Err(e) => error!("Error while generating pack \"{}\": {:?}", name, e),
+        }
+    }
+    if cli.extrude_items {
+        let name = "3D Items";
+        match generate_extruded(cli.item_thickness, cache, version, options) {
+            Ok(output) => outputs.push((name, output)),
+            Err(e) => error!("Error while generating pack \"{}\": {:?}", name, e),
+        }
+    }
+    #[cfg(feature = "font")]
+    if let Some(path) = &cli.font {
+        let font = gen_rp_rs::font::TtfFont {
+            path: path.clone(),
+            cell_size: cli.font_size,
+            antialias: cli.font_antialias,
+        };
+        let name = "Custom Font";
+        match gen_rp_rs::font::generate_ttf_font_pack(
+            name,
+            "§fThe game in a different font\n§3By: funnyboy_roks",
+            &font,
+            options,
+        ) {
+            Ok(output) => outputs.push((name, output)),
+            Err(e) => error!("Error while generating pack \"{}\": {:?}", name, e),
+        }
+    }
+    if options.cancelled() {
+        interrupted(&outputs);
+    }
+    finish_packs(cli, outputs, resourcepacks)?;
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    if let Some(dir) = &cli.datapack
+        && !options.cancelled()
+    {
+        let output = generate_datapack(dir, cache, version, options)
+            .with_context(|| format!("Generating data pack from {}", dir.display()))?;
+        // data packs go in a world, not `resourcepacks`
+        finish_packs(cli, vec![("Generated Data", output)], None)?;
+    }
+
+    Ok(())
+}
 
-    let version = if let Some(id) = cli.version {
-        Version::get_by_id(&id).context("Fetching version")?
+/// After ctrl-C, list the packs that were finished before stopping and exit
+fn interrupted(outputs: &[(&str, PackOutput)]) -> ! {
+    warn!("Interrupted, {} packs were finished", outputs.len());
+    for (name, output) in outputs {
+        match output.data.path() {
+            Some(path) => info!("{}: {}", name, path.display()),
+            None => info!("{}", name),
+        }
+    }
+    std::process::exit(130);
+}
+
+fn generate_variants(
+    textures: &[String],
+    count: usize,
+    cache: &CacheDir,
+    version: &Version,
+    options: &GenerateOptions,
+) -> anyhow::Result<PackOutput> {
+    let models = BlockModels::from_jar(version.download_jar(cache)?).context("Reading models")?;
+    let textures: Vec<String> = if textures.is_empty() {
+        variants::DEFAULT_TEXTURES
+            .iter()
+            .map(|t| t.to_string())
+            .collect()
     } else {
-        Version::get_latest().context("Getting latest version")?
+        textures.to_vec()
     };
+    let variant = VariantOptions {
+        count,
+        ..Default::default()
+    };
+    Ok(variants::generate_variant_pack(
+        "Natural Variation",
+        "§aBlocks vary a little, like in nature\n§3By: funnyboy_roks",
+        &textures,
+        &models,
+        &variant,
+        options,
+    )?)
+}
 
-    let jar_file = version.download_jar("clients")?;
+fn generate_extruded(
+    thickness: f32,
+    cache: &CacheDir,
+    version: &Version,
+    options: &GenerateOptions,
+) -> anyhow::Result<PackOutput> {
+    let models =
+        ItemModels::from_jar(version.download_jar(cache)?).context("Reading item models")?;
+    Ok(extrude::generate_extruded_pack(
+        "3D Items",
+        "§bItems with some depth to them\n§3By: funnyboy_roks",
+        &models,
+        &ExtrudeOptions { thickness },
+        options,
+    )?)
+}
 
-    let textures_dir = TempDir::new().context("Creating temporary directory for textures")?;
-    let out_dir = Path::new("out");
+/// Build a data pack from the templates in `dir`, for the same version as
+/// the resource packs
+fn generate_datapack(
+    dir: &Path,
+    cache: &CacheDir,
+    version: &Version,
+    options: &GenerateOptions,
+) -> anyhow::Result<PackOutput> {
+    let version_json = VersionJson::from_jar(version.download_jar(cache)?)?;
+    let mut pack = DataPack::new(
+        "§dGoes with the generated resource packs\n§3By: funnyboy_roks",
+        version_json.pack_version.data_major,
+    );
+    let vars = BTreeMap::from([
+        ("SEED", options.seed.to_string()),
+        ("VERSION", version.id.clone()),
+    ]);
+    pack.add_templates(dir, &vars)?;
+    Ok(pack.write("Generated Data", options)?)
+}
+
+/// Extract the vanilla textures for `version` and any `--mod`s, with
+/// `--input` laid on top
+fn prepare_textures(
+    cli: &Cli,
+    cache: &CacheDir,
+    version: &Version,
+) -> anyhow::Result<(PathBuf, u32)> {
+    let jar_file = version.download_jar(cache)?;
+
+    let textures_dir = cache.fresh_textures_dir(version)?;
 
-    std::fs::create_dir_all(out_dir)
-        .with_context(|| format!("Creating dir: {}", out_dir.display()))?;
+    debug!("Extracting textures to {}", textures_dir.display());
+    let pack_format = extract_jar(jar_file, &textures_dir).context("Extracting JAR")?;
+    for jar in &cli.mods {
+        let file = File::open(jar).with_context(|| format!("Opening {}", jar.display()))?;
+        let namespaces = extract_mod_jar(file, &textures_dir)
+            .with_context(|| format!("Extracting {}", jar.display()))?;
+        if namespaces.is_empty() {
+            warn!("{} has no textures", jar.display());
+        }
+    }
+    if let Some(input) = &cli.input {
+        let file = File::open(input).with_context(|| format!("Opening {}", input.display()))?;
+        extract_pack(file, &textures_dir)
+            .with_context(|| format!("Extracting {}", input.display()))?;
+    }
+
+    Ok((textures_dir, pack_format))
+}
 
-    let pack_format = extract_jar(jar_file, dbg!(&textures_dir)).context("Extracting JAR")?;
+fn generate_lang_packs(
+    cache: &CacheDir,
+    version: &Version,
+    options: &GenerateOptions,
+) -> anyhow::Result<Vec<(&'static str, PackOutput)>> {
+    let jar = version.download_jar(cache)?;
+    let lang = lang::read_jar_lang(jar).context("Reading en_us.json")?;
+    let packs: [(&str, &str, &dyn TextTransform); 5] = [
+        (
+            "UPPERCASE",
+            "§eALL TEXT IS IN CAPITALS\n§3By: funnyboy_roks",
+            &Uppercase,
+        ),
+        (
+            "mOcKiNg",
+            "§eaLl TeXt Is In MoCkInG cAsE\n§3By: funnyboy_roks",
+            &MockingCase,
+        ),
+        (
+            "Reversed",
+            "§esdrawkcab si txet llA\n§3By: funnyboy_roks",
+            &Reversed,
+        ),
+        (
+            "Pirate",
+            "§eAll text be spoken like a pirate\n§3By: funnyboy_roks",
+            &Pirate,
+        ),
+        ("Uwu", "§eAww text is cutew\n§3By: funnyboy_roks", &Uwu),
+    ];
 
-    let num_files = WalkDir::new(&textures_dir).into_iter().count();
+    let mut outputs = Vec::new();
+    for (name, desc, f) in packs {
+        match lang::generate_lang_pack(name, desc, f, &lang, options) {
+            Ok(output) => outputs.push((name, output)),
+            Err(e) => error!("Error while generating pack \"{}\": {:?}", name, e),
+        }
+    }
+    Ok(outputs)
+}
+
+#[cfg(feature = "sound")]
+fn generate_sound_packs(
+    cache: &CacheDir,
+    version: &Version,
+    options: &GenerateOptions,
+) -> anyhow::Result<Vec<(&'static str, PackOutput)>> {
+    use gen_rp_rs::sound::{BitCrush, Reverse, SoundTransform, Speed, generate_sound_pack};
+
+    let sounds_dir =
+        gen_rp_rs::assets::extract_sounds(version, cache).context("Extracting sounds")?;
+    let packs: [(&str, &str, &dyn SoundTransform); 3] = [
+        (
+            "Nightcore",
+            "§dEvery sound is faster and higher\n§3By: funnyboy_roks",
+            &Speed { factor: 1.25 },
+        ),
+        (
+            "8-bit Audio",
+            "§aEvery sound is bit-crushed\n§3By: funnyboy_roks",
+            &BitCrush {
+                bits: 4,
+                downsample: 4,
+            },
+        ),
+        (
+            "Backwards",
+            "§cEvery sound plays in reverse\n§3By: funnyboy_roks",
+            &Reverse,
+        ),
+    ];
+
+    let mut outputs = Vec::new();
+    for (name, desc, f) in packs {
+        info!("Generating {}", name);
+        match generate_sound_pack(name, desc, f, &sounds_dir, options) {
+            Ok(output) => outputs.push((name, output)),
+            Err(e) => error!("Error while generating pack \"{}\": {:?}", name, e),
+        }
+    }
+    Ok(outputs)
+}
+
+fn config_packs(config: &Config) -> impl Iterator<Item = (&str, &str, &dyn Transform)> {
+    config
+        .packs
+        .iter()
+        .filter(|p| !p.fonts_only)
+        .map(|p| (&*p.name, &*p.description, p as &dyn Transform))
+}
+
+/// Generate the `fonts_only` packs in `config`, returning the ones that
+/// succeeded
+fn generate_font_packs<'a>(
+    config: &'a Config,
+    options: &GenerateOptions,
+) -> Vec<(&'a str, PackOutput)> {
+    let mut outputs = Vec::new();
+    for pack in config.packs.iter().filter(|p| p.fonts_only) {
+        match gen_rp_rs::font::generate_filtered_font_pack(
+            &pack.name,
+            &pack.description,
+            pack,
+            options,
+        ) {
+            Ok(output) => outputs.push((&*pack.name, output)),
+            Err(e) => error!("Error while generating pack \"{}\": {:?}", pack.name, e),
+        }
+    }
+    outputs
+}
+
+/// Generate all of `packs` in parallel, returning the ones that succeeded
+fn generate_packs<'a>(
+    packs: &[(&'a str, &'a str, &'a dyn Transform)],
+    options: &'a GenerateOptions,
+    progress: ProgressKind,
+) -> anyhow::Result<Vec<(&'a str, PackOutput)>> {
+    std::fs::create_dir_all(&options.out_dir)
+        .with_context(|| format!("Creating dir: {}", options.out_dir.display()))?;
+
+    let num_files = WalkDir::new(&options.textures_dir).into_iter().count();
     let prog_group = ProgressGroup::builder()
         .progress_width(80)
         .style(prog::ProgressStyle {
@@ -232,37 +1208,394 @@ fn main() -> anyhow::Result<()> {
         })
         .build();
 
-    let textures_dir_arc = Arc::new(textures_dir.path().to_path_buf());
-    let threads = PACKS.iter().map(|p| {
-        let prog_group = prog_group.clone();
-        let textures_dir = textures_dir_arc.clone();
-        std::thread::spawn(move || {
-            let mut prog = Progress::builder(prog_group)
-                .label(p.name)
-                .init(0)
-                .max(num_files - 1)
-                .build()
-                .unwrap();
-            let res = generate_pack(
-                p.name,
-                p.desc,
-                &mut prog,
-                &*textures_dir,
-                out_dir,
-                pack_format,
-                p.func,
+    let mut generator = Generator::new(options);
+    for &(name, desc, func) in packs {
+        generator.add(name, desc, func);
+    }
+    let outputs = generator.run(|name| -> Box<dyn ProgressSink> {
+        match progress {
+            ProgressKind::Bars => Box::new(
+                Progress::builder(prog_group.clone())
+                    .label(name)
+                    .init(0)
+                    .max(num_files - 1)
+                    .build()
+                    .unwrap(),
+            ),
+            ProgressKind::Json => Box::new(JsonProgress::new(name, num_files)),
+        }
+    });
+    if progress == ProgressKind::Bars {
+        // drawn once more either way, so an interrupted run doesn't leave
+        // the bars half drawn
+        prog_group.draw();
+    }
+    let outputs = match outputs {
+        Err(GenRpError::Cancelled) => return Ok(Vec::new()),
+        res => res?,
+    };
+
+    Ok(outputs
+        .into_iter()
+        .filter_map(|(name, res)| match res {
+            Ok(output) => Some((name, output)),
+            Err(GenRpError::Cancelled) => None,
+            Err(e) => {
+                match progress {
+                    ProgressKind::Bars => {
+                        error!("Error while generating pack \"{}\": {:?}", name, e)
+                    }
+                    ProgressKind::Json => ProgressEvent::Failed {
+                        pack: name,
+                        error: format!("{:?}", e),
+                    }
+                    .emit(),
+                }
+                None
+            }
+        })
+        .collect())
+}
+
+/// Print the hashes of the generated packs, and write or install them
+fn finish_packs(
+    cli: &Cli,
+    outputs: Vec<(&str, PackOutput)>,
+    resourcepacks: Option<&Path>,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "upload")]
+    let upload = cli
+        .upload
+        .as_deref()
+        .map(gen_rp_rs::upload::UploadTarget::parse)
+        .transpose()?;
+
+    let mut warnings = 0;
+    let mut skipped = 0;
+    let mut pack_timings = Vec::new();
+    for (name, output) in outputs {
+        pack_timings.push((name, output.report.timings));
+        let report = &output.report;
+        warnings += report.warnings.len();
+        skipped += report.skipped.len();
+        if cli.progress == ProgressKind::Json {
+            ProgressEvent::finished(name, &output).emit();
+        } else {
+            println!(
+                "{}: {} files, {:.1} MiB, {} skipped, seed {}, in {:.1?}",
+                name,
+                report.files_written,
+                report.bytes_written as f64 / (1024. * 1024.),
+                report.skipped.len(),
+                report.seed,
+                report.duration
             );
-            match res {
-                Ok(()) => {}
+            for warning in &report.warnings {
+                warn!("{}: {}", name, warning);
+            }
+            if let Some(sha1) = &output.sha1 {
+                println!("{}: sha1 {}", name, sha1);
+            }
+        }
+        if cli.write_sha1 {
+            output
+                .write_sha1_file()
+                .with_context(|| format!("Writing SHA-1 for {}", name))?;
+        }
+        if let (Some(resourcepacks), PackData::File(zip)) = (resourcepacks, &output.data) {
+            let installed = install::install_pack(zip, resourcepacks, cli.replace)
+                .with_context(|| format!("Installing {}", name))?;
+            info!("{}: installed to {}", name, installed.display());
+        }
+        #[cfg(feature = "upload")]
+        if let Some(target) = &upload {
+            let url = gen_rp_rs::upload::upload_pack(&output, name, target)
+                .with_context(|| format!("Uploading {}", name))?;
+            info!("{}: uploaded to {}", name, url);
+        }
+    }
+
+    if warnings > 0 && cli.progress == ProgressKind::Bars {
+        warn!("{} warnings, {} files skipped", warnings, skipped);
+    }
+    if cli.timings && cli.progress == ProgressKind::Bars && !pack_timings.is_empty() {
+        print!("{}", timings::table(pack_timings));
+    }
+    if cli.deny_warnings && warnings > 0 {
+        anyhow::bail!("{} warnings while generating packs", warnings);
+    }
+
+    Ok(())
+}
+
+fn extract_palettes(inputs: &[PathBuf], k: usize, output: &Path, seed: u64) -> anyhow::Result<()> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    for input in inputs {
+        // images in a directory keep their path relative to it
+        let mut images = Vec::new();
+        if input.is_dir() {
+            for entry in WalkDir::new(input).sort_by_file_name() {
+                let entry = entry?;
+                if entry.path().extension().is_some_and(|ext| ext == "png") {
+                    let relative = entry
+                        .path()
+                        .strip_prefix(input)
+                        .expect("path is in input")
+                        .to_path_buf();
+                    images.push((entry.path().to_path_buf(), relative));
+                }
+            }
+        } else {
+            let name = input
+                .file_name()
+                .with_context(|| format!("{} isn't a file", input.display()))?;
+            images.push((input.clone(), PathBuf::from(name)));
+        }
+
+        for (path, relative) in images {
+            let mut image = image::open(&path)
+                .with_context(|| format!("Reading {}", path.display()))?
+                .into_rgba8();
+            let palette = match Palette::extract(&image, k, &mut rng) {
+                Ok(palette) => palette,
                 Err(e) => {
-                    eprintln!("Error while generating pack \"{}\": {:?}", p.name, e);
+                    warn!("{}: skipped, {}", path.display(), e);
+                    continue;
                 }
+            };
+            palette.apply_to(&mut image);
+
+            let out = output.join(&relative).with_extension("png");
+            if let Some(parent) = out.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Creating {}", parent.display()))?;
             }
-        })
-    });
+            image
+                .save(&out)
+                .with_context(|| format!("Writing {}", out.display()))?;
+            let swatch = out.with_extension("palette.png");
+            palette
+                .swatch(16)
+                .save(&swatch)
+                .with_context(|| format!("Writing {}", swatch.display()))?;
+            let hex = palette.to_hex_list();
+            let hex_path = out.with_extension("hex");
+            fs::write(&hex_path, &hex)
+                .with_context(|| format!("Writing {}", hex_path.display()))?;
+
+            println!(
+                "{}: {}",
+                relative.display(),
+                hex.lines().collect::<Vec<_>>().join(" ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn publish(cli: &Cli, args: &PublishArgs) -> anyhow::Result<()> {
+    let game_versions = if args.game_versions.is_empty() {
+        vec![match &cli.version {
+            Some(id) => id.clone(),
+            None => Version::get_latest().context("Getting latest version")?.id,
+        }]
+    } else {
+        args.game_versions.clone()
+    };
+    let game_versions: Vec<&str> = game_versions.iter().map(String::as_str).collect();
+    let version_number = args.version_number.as_deref().unwrap_or(game_versions[0]);
+
+    let file_name = args
+        .zip
+        .file_name()
+        .context("Pack has no file name")?
+        .to_string_lossy();
+    let pack_name = file_name.trim_end_matches(".zip");
+    let name = format!("{} {}", pack_name, version_number);
+
+    if let Some(project) = &args.modrinth {
+        let token = std::env::var("MODRINTH_TOKEN").context("MODRINTH_TOKEN env var not set")?;
+        let version_type = match args.release_type {
+            ReleaseKind::Release => VersionType::Release,
+            ReleaseKind::Beta => VersionType::Beta,
+            ReleaseKind::Alpha => VersionType::Alpha,
+        };
 
-    threads.into_iter().try_for_each(JoinHandle::join).unwrap();
-    prog_group.draw();
+        let existing = modrinth::project_versions(&token, project)
+            .context("Getting Modrinth versions")?
+            .into_iter()
+            .find(|v| v.version_number == version_number);
+        match existing {
+            Some(existing) => {
+                let sha1 = sha1_file(&args.zip)?;
+                modrinth::add_version_file(&token, &existing.id, &*file_name, &args.zip)
+                    .context("Uploading the pack to the Modrinth version")?;
+                ModifyVersionReq {
+                    changelog: Some(&args.changelog),
+                    game_versions: Some(&game_versions),
+                    version_type: Some(version_type),
+                    primary_file: Some(["sha1", &sha1]),
+                    ..Default::default()
+                }
+                .send(&token, &existing.id)
+                .context("Updating Modrinth version")?;
+                info!(
+                    "Modrinth: added {} to version {} as its primary file",
+                    file_name, version_number
+                );
+            }
+            None => {
+                CreateVersionReq {
+                    name: &name,
+                    version_number,
+                    changelog: &args.changelog,
+                    game_versions: &game_versions,
+                    version_type,
+                    status: VersionStatus::Listed,
+                    project_id: project,
+                }
+                .send(&token, &*file_name, &args.zip)
+                .context("Creating Modrinth version")?;
+                info!("Modrinth: created version {}", version_number);
+            }
+        }
+    }
+
+    if let Some(project) = &args.curseforge {
+        let token =
+            std::env::var("CURSEFORGE_TOKEN").context("CURSEFORGE_TOKEN env var not set")?;
+        let game_version_ids = curseforge::game_version_ids(&token, &game_versions)
+            .context("Getting CurseForge game versions")?;
+
+        let file_id = UploadFileReq {
+            changelog: &args.changelog,
+            changelog_type: "markdown",
+            display_name: &name,
+            game_versions: &game_version_ids,
+            release_type: match args.release_type {
+                ReleaseKind::Release => ReleaseType::Release,
+                ReleaseKind::Beta => ReleaseType::Beta,
+                ReleaseKind::Alpha => ReleaseType::Alpha,
+            },
+        }
+        .send(&token, project, &args.zip)
+        .context("Uploading to CurseForge")?;
+        info!("CurseForge: uploaded file {}", file_id);
+    }
 
     Ok(())
 }
+
+/// Build the packs in `--config`, then rebuild the ones that changed
+/// whenever the config (or `--input` or a `--mod`) is saved
+fn watch(
+    cli: &Cli,
+    cache: &CacheDir,
+    version: &Version,
+    options: &GenerateOptions,
+    resourcepacks: Option<&Path>,
+) -> anyhow::Result<()> {
+    let config_path = cli
+        .config
+        .as_deref()
+        .context("`watch` needs a --config to watch")?;
+    let canonicalize = |path: &Path| {
+        path.canonicalize()
+            .with_context(|| format!("Finding {}", path.display()))
+    };
+    let config_path = canonicalize(config_path)?;
+    let inputs = cli
+        .input
+        .iter()
+        .chain(&cli.mods)
+        .map(|path| canonicalize(path))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let watched: Vec<&Path> = std::iter::once(&*config_path)
+        .chain(inputs.iter().map(PathBuf::as_path))
+        .collect();
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    // editors often replace files rather than writing to them, which would
+    // lose a watch on the file itself
+    for path in &watched {
+        let dir = path.parent().expect("canonical path has a parent");
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Watching {}", dir.display()))?;
+    }
+
+    let mut built = Vec::new();
+    loop {
+        match Config::load(&config_path) {
+            Ok(config) => {
+                let changed: Vec<_> = config_packs(&config)
+                    .zip(config.packs.iter().filter(|p| !p.fonts_only))
+                    .filter(|(_, pack)| !built.contains(*pack))
+                    .map(|(pack, _)| pack)
+                    .collect();
+
+                if changed.is_empty() {
+                    info!("No packs changed");
+                    built = config.packs;
+                } else {
+                    match generate_packs(&changed, options, cli.progress) {
+                        Ok(outputs) => {
+                            if let Err(e) = finish_packs(cli, outputs, resourcepacks) {
+                                error!("{:?}", e);
+                            }
+                            built = config.packs;
+                        }
+                        // keep `built` as it was, so these packs are tried
+                        // again on the next change
+                        Err(e) => error!("{:?}", e),
+                    }
+                }
+            }
+            Err(e) => error!("Error loading config {}: {:?}", config_path.display(), e),
+        }
+
+        info!("Watching {} for changes...", config_path.display());
+        let changed = wait_for_change(&rx, &watched)?;
+
+        if inputs.iter().any(|input| changed.contains(input)) {
+            info!("Inputs changed, extracting them again");
+            if let Err(e) = prepare_textures(cli, cache, version) {
+                error!("{:?}", e);
+            }
+            built.clear();
+        }
+    }
+}
+
+/// Block until one of `paths` is changed, returning every changed path once
+/// things have settled
+fn wait_for_change(
+    rx: &mpsc::Receiver<notify::Result<notify::Event>>,
+    paths: &[&Path],
+) -> anyhow::Result<Vec<PathBuf>> {
+    let relevant = |event: notify::Event| -> Vec<PathBuf> {
+        if event.kind.is_access() {
+            return Vec::new();
+        }
+        event
+            .paths
+            .into_iter()
+            .filter(|path| paths.contains(&path.as_path()))
+            .collect()
+    };
+
+    loop {
+        let mut changed = relevant(rx.recv()??);
+        if changed.is_empty() {
+            continue;
+        }
+
+        // editors tend to save in a few steps, so wait for them to finish
+        while let Ok(event) = rx.recv_timeout(Duration::from_millis(200)) {
+            changed.extend(relevant(event?));
+        }
+        return Ok(changed);
+    }
+}
@@ -0,0 +1,131 @@
+//! Combining several packs into one
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use serde_json::Value;
+
+use crate::{
+    GenerationReport, PackMcMeta, PackOutput,
+    error::{GenRpError, Result},
+    generated::GENERATED_JSON,
+    output::OutputSink,
+    pack_source::PackSource,
+};
+
+/// Layer `packs` (zips or directories) into a single pack written to `sink`.
+/// Later packs take precedence, the same as packs higher up in the game's
+/// resource pack list.
+///
+/// `pack.mcmeta` is reconciled rather than copied: the newest `pack_format`
+/// of the inputs is used (with a warning if they differ), the description is
+/// `description` or else the last pack's, and any other sections (like
+/// `language`) are taken from the last pack that has them.  The inputs'
+/// `generated.json`s are left out, as none of them describes the merged pack.
+pub fn merge_packs(
+    packs: &[impl AsRef<Path>],
+    mut sink: Box<dyn OutputSink>,
+    description: Option<&str>,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut report = GenerationReport::default();
+
+    let mut sources = Vec::with_capacity(packs.len());
+    // where each file comes from, later packs replacing earlier ones
+    let mut files = BTreeMap::<PathBuf, usize>::new();
+    let mut metas = Vec::with_capacity(packs.len());
+    for (i, pack) in packs.iter().enumerate() {
+        let pack = pack.as_ref();
+        let mut source = PackSource::open(pack)?;
+        for file in source.files()? {
+            if file != Path::new("pack.mcmeta") && file != Path::new(GENERATED_JSON) {
+                files.insert(file, i);
+            }
+        }
+
+        let meta = source.read(Path::new("pack.mcmeta"))?;
+        let meta: Value = serde_json::from_slice(&meta).map_err(|e| {
+            GenRpError::InvalidPack(format!("{}: bad pack.mcmeta: {}", pack.display(), e))
+        })?;
+        metas.push((pack, meta));
+        sources.push(source);
+    }
+
+    let pack_mcmeta = merge_mcmeta(&metas, description, &mut report.warnings)?;
+
+    for (path, i) in files {
+        let data = sources[i].read(&path)?;
+        sink.add_file(&path, &data)?;
+        report.files_written += 1;
+        report.bytes_written += data.len() as u64;
+    }
+
+    sink.add_file(Path::new("pack.mcmeta"), pack_mcmeta.as_bytes())?;
+    report.files_written += 1;
+    report.bytes_written += pack_mcmeta.len() as u64;
+
+    let data = sink.finish()?;
+    let sha1 = data.sha1()?;
+    report.duration = start.elapsed();
+
+    Ok(PackOutput { data, sha1, report })
+}
+
+fn merge_mcmeta(
+    metas: &[(&Path, Value)],
+    description: Option<&str>,
+    warnings: &mut Vec<String>,
+) -> Result<String> {
+    let mut formats = Vec::with_capacity(metas.len());
+    for (path, meta) in metas {
+        let format = pack_format(meta).ok_or_else(|| {
+            GenRpError::InvalidPack(format!(
+                "{}: pack.mcmeta has no pack format",
+                path.display()
+            ))
+        })?;
+        formats.push(format);
+    }
+    let pack_format = formats.iter().copied().max().unwrap_or_default();
+    if formats.iter().any(|&format| format != pack_format) {
+        warnings.push(format!(
+            "merging packs with different formats ({:?}), using {}",
+            formats, pack_format
+        ));
+    }
+
+    let mut merged = serde_json::Map::new();
+    for (_, meta) in metas {
+        if let Value::Object(meta) = meta {
+            merged.extend(meta.clone());
+        }
+    }
+
+    let mut pack = serde_json::to_value(PackMcMeta::new("", pack_format))?["pack"].take();
+    pack["description"] = match description {
+        Some(description) => description.into(),
+        None => metas
+            .iter()
+            .rev()
+            .find_map(|(_, meta)| meta["pack"].get("description").cloned())
+            .unwrap_or_default(),
+    };
+    merged.insert("pack".into(), pack);
+
+    Ok(serde_json::to_string_pretty(&merged)?)
+}
+
+/// `pack_format`, or the newest of `max_format` for packs made for 1.21.9+,
+/// which can be a number or `[major, minor]`
+fn pack_format(meta: &Value) -> Option<u32> {
+    let pack = meta.get("pack")?;
+    let format = pack.get("pack_format").or_else(|| pack.get("max_format"))?;
+    let format = match format {
+        Value::Array(parts) => parts.first()?,
+        format => format,
+    };
+    format.as_u64().map(|format| format as u32)
+}
@@ -0,0 +1,266 @@
+//! Block models and blockstates, for packs that add textures rather than
+//! just changing them.
+//!
+//! The game only picks a texture because a model points at it, and a model
+//! only because a blockstate does, so new variants of a texture need a model
+//! for each and a blockstate choosing between them.  [`BlockModels`] reads
+//! the vanilla ones from the client jar to work those out.
+
+use std::{
+    collections::BTreeMap,
+    io::{BufReader, Read, Seek},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::error::Result;
+
+const BLOCKSTATES_PREFIX: &str = "assets/minecraft/blockstates/";
+const MODELS_PREFIX: &str = "assets/minecraft/models/";
+
+/// A model file, `assets/<namespace>/models/<id>.json`.  Only the parent and
+/// textures are kept, which is all a model that just swaps textures needs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Model {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Texture variable (like `all` or `side`) to a texture id or another
+    /// variable (`#side`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub textures: BTreeMap<String, String>,
+}
+
+/// A blockstate file, `assets/<namespace>/blockstates/<block>.json`.
+/// Multipart blockstates (fences, walls, ...) aren't supported, and are
+/// read with no variants.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blockstate {
+    /// State (like `axis=y`, or `""` for every state) to the models it can
+    /// be shown with
+    #[serde(default)]
+    pub variants: BTreeMap<String, Variants>,
+}
+
+/// One model for a state, or several to pick between at random by weight
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Variants {
+    One(Variant),
+    Weighted(Vec<Variant>),
+}
+
+impl Variants {
+    pub fn as_slice(&self) -> &[Variant] {
+        match self {
+            Variants::One(variant) => std::slice::from_ref(variant),
+            Variants::Weighted(variants) => variants,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Variant {
+    pub model: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub x: i32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub y: i32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub uvlock: bool,
+    /// How likely this is to be picked, relative to the others for the state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+}
+
+fn is_zero(n: &i32) -> bool {
+    *n == 0
+}
+
+/// `minecraft:block/stone` and `block/stone` are the same thing
+fn strip_namespace(id: &str) -> &str {
+    id.strip_prefix("minecraft:").unwrap_or(id)
+}
+
+fn same_id(a: &str, b: &str) -> bool {
+    strip_namespace(a) == strip_namespace(b)
+}
+
+/// Where the model `id` is in a pack
+pub fn model_path(id: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}.json", MODELS_PREFIX, strip_namespace(id)))
+}
+
+/// Where the blockstate for `block` is in a pack
+pub fn blockstate_path(block: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}.json", BLOCKSTATES_PREFIX, block))
+}
+
+/// The vanilla blockstates and block models
+#[derive(Clone, Debug, Default)]
+pub struct BlockModels {
+    /// Block name (like `stone`) to its blockstate
+    pub blockstates: BTreeMap<String, Blockstate>,
+    /// Model id without the namespace (like `block/stone`) to the model
+    pub models: BTreeMap<String, Model>,
+}
+
+impl BlockModels {
+    /// Read the blockstates and block models from a client jar
+    pub fn from_jar(jar: impl Read + Seek) -> Result<Self> {
+        let mut dec = ZipArchive::new(BufReader::new(jar))?;
+        let mut models = Self::default();
+
+        for i in 0..dec.len() {
+            let file = dec.by_index(i)?;
+            let Some(name) = file.name().strip_suffix(".json") else {
+                continue;
+            };
+
+            if let Some(block) = name.strip_prefix(BLOCKSTATES_PREFIX) {
+                let block = block.to_string();
+                models
+                    .blockstates
+                    .insert(block, serde_json::from_reader(file)?);
+            } else if let Some(id) = name.strip_prefix(MODELS_PREFIX)
+                && id.starts_with("block/")
+            {
+                let id = id.to_string();
+                models.models.insert(id, serde_json::from_reader(file)?);
+            }
+        }
+
+        Ok(models)
+    }
+
+    /// Texture variables of the model `id`, including the ones it gets from
+    /// its parents, with `#references` resolved
+    pub fn resolved_textures(&self, id: &str) -> BTreeMap<String, String> {
+        let mut textures = BTreeMap::new();
+        let mut next = Some(strip_namespace(id));
+        // parents are only followed so far, in case of a loop
+        for _ in 0..16 {
+            let Some(model) = next.and_then(|id| self.models.get(id)) else {
+                break;
+            };
+            for (key, value) in &model.textures {
+                textures.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+            next = model.parent.as_deref().map(strip_namespace);
+        }
+
+        let references = textures.clone();
+        for value in textures.values_mut() {
+            for _ in 0..16 {
+                match value.strip_prefix('#').and_then(|key| references.get(key)) {
+                    Some(target) => *value = target.clone(),
+                    None => break,
+                }
+            }
+        }
+
+        textures
+    }
+
+    /// Blocks with a model that uses any of `textures` (like `block/stone`)
+    pub fn blocks_using<'a>(&'a self, textures: &[&str]) -> Vec<&'a str> {
+        self.blockstates
+            .iter()
+            .filter(|(_, blockstate)| {
+                blockstate
+                    .variants
+                    .values()
+                    .flat_map(Variants::as_slice)
+                    .flat_map(|variant| self.resolved_textures(&variant.model).into_values())
+                    .any(|t| textures.iter().any(|&texture| same_id(&t, texture)))
+            })
+            .map(|(block, _)| &**block)
+            .collect()
+    }
+
+    /// Change the blockstate of `block` to also pick from copies of its
+    /// models at random, with the copy `i` using the variant `i` of each of
+    /// the textures in `variants` (vanilla texture to variant ids, like
+    /// `block/stone` to `[block/stone_1, block/stone_2]`).  The weights and
+    /// rotations it already has are kept.  Returns the blockstate along with
+    /// the models it needs, or `None` if the block doesn't use any of the
+    /// textures.
+    pub fn randomise(
+        &self,
+        block: &str,
+        variants: &BTreeMap<String, Vec<String>>,
+    ) -> Option<(Blockstate, BTreeMap<String, Model>)> {
+        let blockstate = self.blockstates.get(block)?;
+        let count = variants.values().map(Vec::len).max().unwrap_or(0);
+
+        let mut models = BTreeMap::new();
+        let mut randomised = Blockstate::default();
+        for (state, choices) in &blockstate.variants {
+            let mut weighted = Vec::new();
+            for choice in choices.as_slice() {
+                weighted.push(choice.clone());
+                let textures = self.resolved_textures(&choice.model);
+                let model = strip_namespace(&choice.model);
+
+                for i in 0..count {
+                    // the variable -> variant texture for every variable
+                    // using one of `variants`
+                    let replaced: BTreeMap<_, _> = textures
+                        .iter()
+                        .filter_map(|(key, t)| {
+                            let (_, textures) = variants.iter().find(|(v, _)| same_id(t, v))?;
+                            Some((key.clone(), textures.get(i)?.clone()))
+                        })
+                        .collect();
+                    if replaced.is_empty() {
+                        continue;
+                    }
+
+                    let id = format!("{}_variant_{}", model, i + 1);
+                    models.entry(id.clone()).or_insert_with(|| Model {
+                        parent: Some(format!("minecraft:{}", model)),
+                        textures: replaced,
+                    });
+                    weighted.push(Variant {
+                        model: format!("minecraft:{}", id),
+                        ..choice.clone()
+                    });
+                }
+            }
+
+            let choices = if weighted.len() == 1 {
+                Variants::One(weighted.remove(0))
+            } else {
+                Variants::Weighted(weighted)
+            };
+            randomised.variants.insert(state.clone(), choices);
+        }
+
+        (!models.is_empty()).then_some((randomised, models))
+    }
+
+    /// The blockstates and models to add to a pack so that every block
+    /// using the textures in `variants` shows them too, see
+    /// [`Self::randomise`]
+    pub fn variant_files(
+        &self,
+        variants: &BTreeMap<String, Vec<String>>,
+    ) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        let textures: Vec<_> = variants.keys().map(|t| &**t).collect();
+        let mut files = BTreeMap::new();
+        for block in self.blocks_using(&textures) {
+            let Some((blockstate, models)) = self.randomise(block, variants) else {
+                continue;
+            };
+            files.insert(
+                blockstate_path(block),
+                serde_json::to_vec_pretty(&blockstate)?,
+            );
+            for (id, model) in models {
+                files.insert(model_path(&id), serde_json::to_vec_pretty(&model)?);
+            }
+        }
+        Ok(files)
+    }
+}
@@ -4,12 +4,14 @@ use ::reqwest::{
     StatusCode,
     blocking::{self as reqwest, Client},
 };
-use anyhow::{Context, bail};
 use lazy_static::lazy_static;
-use reqwest::multipart::Form;
-use serde::{Deserialize, Serialize};
+use reqwest::{Response, multipart::Form};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
-use crate::Version;
+use crate::{
+    Version,
+    error::{GenRpError, IoContext, Result},
+};
 
 const MODRINTH_API: &str = "https://api.modrinth.com/v2";
 
@@ -23,7 +25,7 @@ lazy_static! {
 #[derive(Debug, Deserialize)]
 pub struct CreateVersionRes {}
 
-#[derive(Debug, Serialize)]
+#[derive(Clone, Copy, Debug, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum VersionType {
     Release,
@@ -70,7 +72,7 @@ impl CreateVersionReq<'_> {
         modrinth_token: &str,
         file_name: impl Into<String>,
         file: &Path,
-    ) -> anyhow::Result<()> {
+    ) -> Result<()> {
         #[derive(Debug, Serialize)]
         struct AdditionalData<'a> {
             #[serde(flatten)]
@@ -100,57 +102,109 @@ impl CreateVersionReq<'_> {
                 Form::new()
                     .text(
                         "data",
-                        serde_json::to_string(&dbg!(data))
+                        serde_json::to_string(&data)
                             .expect("This structure can't fail to serialize"),
                     )
                     .file(file_name, file)
-                    .with_context(|| format!("Reading {}", file.display()))?,
+                    .io_context(|| format!("Reading {}", file.display()))?,
             )
             .send()?;
 
-        let json: serde_json::Value = match response.status() {
-            StatusCode::OK => response.json().context("Parsing response json")?,
-            status => {
-                bail!(
-                    "Failed creating version ({}): {:?}",
-                    status,
-                    response.json::<serde_json::Value>().ok()
-                );
-            }
-        };
+        let json: serde_json::Value = parse_response(response)?;
 
-        dbg!(json);
+        tracing::debug!("Modrinth responded with {}", json);
 
         Ok(())
     }
 }
 
 #[derive(Debug, Deserialize)]
-struct ModrinthVersion {
-    game_versions: Vec<String>,
+pub struct ModrinthVersion {
+    pub id: String,
+    pub version_number: String,
+    pub game_versions: Vec<String>,
+}
+
+/// Every version of the project `slug`, newest first
+pub fn project_versions(modrinth_token: &str, slug: &str) -> Result<Vec<ModrinthVersion>> {
+    let response = CLIENT
+        .get(format!("{}/project/{}/version", MODRINTH_API, slug))
+        .header("Authorization", modrinth_token)
+        .send()?;
+
+    parse_response(response)
 }
 
-pub fn project_has_version(
+/// Changes to an existing version, fields left as `None` are kept
+#[derive(Debug, Default, Serialize)]
+pub struct ModifyVersionReq<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_versions: Option<&'a [&'a str]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_type: Option<VersionType>,
+    /// The file to make primary, as `["sha1", <hash>]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_file: Option<[&'a str; 2]>,
+}
+
+impl ModifyVersionReq<'_> {
+    pub fn send(self, modrinth_token: &str, version_id: &str) -> Result<()> {
+        let response = CLIENT
+            .patch(format!("{}/version/{}", MODRINTH_API, version_id))
+            .header("Authorization", modrinth_token)
+            .json(&self)
+            .send()?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT | StatusCode::OK => Ok(()),
+            status => Err(GenRpError::Modrinth {
+                status,
+                body: response.json::<serde_json::Value>().ok(),
+            }),
+        }
+    }
+}
+
+/// Upload `file` to the existing version `version_id`, alongside its other
+/// files.  Make it the primary file with [`ModifyVersionReq::primary_file`].
+pub fn add_version_file(
     modrinth_token: &str,
-    slug: &str,
-    version: &Version,
-) -> anyhow::Result<bool> {
+    version_id: &str,
+    file_name: impl Into<String>,
+    file: &Path,
+) -> Result<()> {
+    let response = CLIENT
+        .post(format!("{}/version/{}/file", MODRINTH_API, version_id))
+        .header("Authorization", modrinth_token)
+        .multipart(
+            Form::new()
+                .text("data", "{}")
+                .file(file_name.into(), file)
+                .io_context(|| format!("Reading {}", file.display()))?,
+        )
+        .send()?;
+
+    match response.status() {
+        StatusCode::NO_CONTENT | StatusCode::OK => Ok(()),
+        status => Err(GenRpError::Modrinth {
+            status,
+            body: response.json::<serde_json::Value>().ok(),
+        }),
+    }
+}
+
+pub fn project_has_version(modrinth_token: &str, slug: &str, version: &Version) -> Result<bool> {
     let req = CLIENT
         .get(format!("{}/project/{}/version", MODRINTH_API, slug))
         .query(&[("game_versions", &version.id)])
         .header("Authorization", modrinth_token);
     let response = req.send()?;
 
-    let json: Vec<ModrinthVersion> = match response.status() {
-        StatusCode::OK => response.json().context("Parsing response json")?,
-        status => {
-            bail!(
-                "Failed getting version ({}): {:?}",
-                status,
-                response.json::<serde_json::Value>().ok()
-            );
-        }
-    };
+    let json: Vec<ModrinthVersion> = parse_response(response)?;
 
     if json.is_empty() {
         return Ok(false);
@@ -163,22 +217,13 @@ pub fn project_latest_version<'a>(
     modrinth_token: &str,
     slug: &str,
     versions: &HashMap<&'a str, &'a Version>,
-) -> anyhow::Result<Option<&'a Version>> {
+) -> Result<Option<&'a Version>> {
     let req = CLIENT
         .get(format!("{}/project/{}/version", MODRINTH_API, slug))
         .header("Authorization", modrinth_token);
     let response = req.send()?;
 
-    let json: Vec<ModrinthVersion> = match response.status() {
-        StatusCode::OK => response.json().context("Parsing response json")?,
-        status => {
-            bail!(
-                "Failed getting versions ({}): {:?}",
-                status,
-                response.json::<serde_json::Value>().ok()
-            );
-        }
-    };
+    let json: Vec<ModrinthVersion> = parse_response(response)?;
 
     Ok(json
         .iter()
@@ -187,3 +232,13 @@ pub fn project_latest_version<'a>(
         .max()
         .copied())
 }
+
+fn parse_response<T: DeserializeOwned>(response: Response) -> Result<T> {
+    match response.status() {
+        StatusCode::OK => Ok(response.json()?),
+        status => Err(GenRpError::Modrinth {
+            status,
+            body: response.json::<serde_json::Value>().ok(),
+        }),
+    }
+}
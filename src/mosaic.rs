@@ -0,0 +1,222 @@
+//! Textures rebuilt out of small tiles, like toy bricks, beads or
+//! cross-stitches: each square cell of a texture is averaged to one colour and
+//! a tile shaded with that colour is stamped in its place.
+//!
+//! The tiles are drawn at the size of the cell, or can be any image, whose
+//! greys shade the colour of the cell (mid grey leaves it as it is, lighter
+//! greys are highlights and darker ones shadows):
+//!
+//! ```toml
+//! filters = [
+//!     { type = "resize", target = 64, filter = "nearest" },
+//!     { type = "mosaic", cell = 4, tile = "brick" },
+//!     { type = "mosaic", cell = 8, sprite = "tiles/tile.png" },
+//! ]
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, LumaA, Rgba, RgbaImage, imageops};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blend::BlendMode,
+    error::{GenRpError, Result},
+    filter::{HexColour, Transform, mean_colour},
+};
+
+/// Cells less opaque than this are left transparent, keeping the shapes of
+/// items
+const MIN_ALPHA: u8 = 128;
+/// The shade that leaves a colour as it is
+const FLAT: u8 = 128;
+
+/// The built-in tiles
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tile {
+    /// A toy brick, seen from above with a stud in the middle
+    #[default]
+    Brick,
+    /// A fuse bead, a short tube with a hole through the middle
+    Bead,
+    /// A cross-stitch, an X of thread
+    Stitch,
+    /// A flat square with grout along two sides
+    Square,
+}
+
+impl Tile {
+    /// The shade and coverage of the pixel `(x, y)` of a `size`x`size` tile,
+    /// lit from the top left
+    fn shade(self, x: u32, y: u32, size: u32) -> LumaA<u8> {
+        let last = size - 1;
+        // from the centre, in fractions of the tile
+        let u = (x as f32 + 0.5) / size as f32 - 0.5;
+        let v = (y as f32 + 0.5) / size as f32 - 0.5;
+        let r = u.hypot(v);
+        let pixel = 1. / size as f32;
+        let lit = |light: u8, dark: u8| if u + v < 0. { light } else { dark };
+
+        match self {
+            Tile::Brick => {
+                if x == 0 || y == 0 {
+                    LumaA([160, 255])
+                } else if x == last || y == last {
+                    LumaA([88, 255])
+                } else if r <= 0.3 {
+                    LumaA([lit(168, FLAT), 255])
+                } else if r <= 0.3 + pixel && u + v > 0. {
+                    LumaA([96, 255])
+                } else {
+                    LumaA([FLAT, 255])
+                }
+            }
+            Tile::Bead => {
+                if !(0.2..=0.5).contains(&r) {
+                    LumaA([0, 0])
+                } else if r > 0.5 - pixel {
+                    LumaA([lit(FLAT, 96), 255])
+                } else {
+                    LumaA([lit(176, FLAT), 255])
+                }
+            }
+            Tile::Stitch => {
+                let width = pixel / 2.;
+                let down = (u - v).abs() <= width;
+                let up = (u + v).abs() <= width;
+                match (down, up) {
+                    (true, true) => LumaA([160, 255]),
+                    (false, true) => LumaA([144, 255]),
+                    (true, false) => LumaA([112, 255]),
+                    (false, false) => LumaA([0, 0]),
+                }
+            }
+            Tile::Square => {
+                if x == last || y == last {
+                    LumaA([0, 0])
+                } else if x == 0 || y == 0 {
+                    LumaA([152, 255])
+                } else {
+                    LumaA([FLAT, 255])
+                }
+            }
+        }
+    }
+
+    fn draw(self, size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size, size, |x, y| {
+            let LumaA([l, a]) = self.shade(x, y, size);
+            Rgba([l, l, l, a])
+        })
+    }
+}
+
+/// Rebuild every texture out of `cell`x`cell` tiles
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Mosaic {
+    /// Pixels across each tile
+    #[serde(default = "default_cell")]
+    pub cell: u32,
+    #[serde(default)]
+    pub tile: Tile,
+    /// An image to use as the tile instead, relative to the config file,
+    /// scaled to the size of the cell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<PathBuf>,
+    /// What shows between tiles, or a darker shade of the tile's colour if
+    /// not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<HexColour>,
+    /// `sprite`, once it's loaded
+    #[serde(skip)]
+    image: Option<RgbaImage>,
+}
+
+fn default_cell() -> u32 {
+    4
+}
+
+impl Mosaic {
+    pub fn new(cell: u32, tile: Tile) -> Self {
+        Self {
+            cell,
+            tile,
+            sprite: None,
+            background: None,
+            image: None,
+        }
+    }
+
+    /// Show `colour` between the tiles
+    pub fn with_background(self, colour: HexColour) -> Self {
+        Self {
+            background: Some(colour),
+            ..self
+        }
+    }
+
+    /// Load `sprite`, with its path relative to `base`
+    pub(crate) fn resolve(&mut self, base: &Path) -> Result<()> {
+        let Some(path) = &self.sprite else {
+            return Ok(());
+        };
+        let path = base.join(path);
+        let sprite = image::open(&path).map_err(|source| GenRpError::Decode {
+            path: path.clone(),
+            source,
+        })?;
+        self.image = Some(sprite.into_rgba8());
+        self.sprite = Some(path);
+        Ok(())
+    }
+
+    /// The tile, at the size of a cell
+    fn tile(&self, cell: u32) -> RgbaImage {
+        match &self.image {
+            Some(image) => imageops::resize(image, cell, cell, imageops::FilterType::Nearest),
+            None => self.tile.draw(cell),
+        }
+    }
+}
+
+impl Transform for Mosaic {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let image = image.into_rgba8();
+        let cell = self.cell.max(1);
+        let tile = self.tile(cell);
+
+        let (width, height) = image.dimensions();
+        let mut out = RgbaImage::new(width, height);
+        for y0 in (0..height).step_by(cell as usize) {
+            for x0 in (0..width).step_by(cell as usize) {
+                let (x1, y1) = ((x0 + cell).min(width), (y0 + cell).min(height));
+                let colour = mean_colour(&image, x0..x1, y0..y1);
+                if colour[3] < MIN_ALPHA {
+                    continue;
+                }
+
+                let base = [colour[0], colour[1], colour[2]].map(|c| c as f32 / 255.);
+                let background = match self.background {
+                    Some(HexColour(rgb)) => rgb.0.map(|c| c as f32 / 255.),
+                    None => base.map(|c| c * 0.5),
+                };
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let px = tile.get_pixel(x - x0, y - y0);
+                        let shade = [px[0], px[1], px[2]].map(|c| c as f32 / 255.);
+                        let shaded = BlendMode::Overlay.blend(base, shade, 1.);
+                        let t = px[3] as f32 / 255.;
+                        let rgb: [f32; 3] = std::array::from_fn(|c| {
+                            background[c] + (shaded[c] - background[c]) * t
+                        });
+                        let [r, g, b] = rgb.map(|c| (c.clamp(0., 1.) * 255.).round() as u8);
+                        out.put_pixel(x, y, Rgba([r, g, b, colour[3]]));
+                    }
+                }
+            }
+        }
+
+        out.into()
+    }
+}
@@ -0,0 +1,146 @@
+//! Encoding the PNGs written to packs as small as they can be.
+//!
+//! Textures with at most 256 colours (like the ones from palette and
+//! dithering filters) are written as indexed PNGs with exactly those colours,
+//! and every PNG can then be shrunk further with [oxipng], which tries
+//! smaller colour types, bit depths and filters for each image and
+//! recompresses it.  Zips are stored uncompressed, so this is the only
+//! compression a pack gets.
+
+use std::{collections::HashMap, io::Cursor};
+
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use oxipng::{Deflater, Options, StripChunks, ZopfliOptions};
+use png::{BitDepth, ColorType};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// Encode `image` as a PNG, indexed if it has few enough colours, and
+/// optimise it with `optimize`
+pub fn encode_png(image: &DynamicImage, optimize: OptLevel) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    // 16-bit and float images would lose precision in a palette
+    let indexed = match image {
+        DynamicImage::ImageRgba8(image) => encode_indexed(image, &mut data)?,
+        DynamicImage::ImageRgb8(_) => encode_indexed(&image.to_rgba8(), &mut data)?,
+        _ => false,
+    };
+    if !indexed {
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+    }
+    optimize.optimize(&mut data)?;
+    Ok(data)
+}
+
+/// Write `image` to `data` as an indexed PNG, with as few bits per pixel as
+/// its palette needs, if it has at most 256 colours and isn't so small that
+/// the palette outweighs the pixels.  Returns whether it did.
+fn encode_indexed(image: &RgbaImage, data: &mut Vec<u8>) -> Result<bool> {
+    let mut palette = HashMap::new();
+    let mut indices = Vec::with_capacity(image.len() / 4);
+    for px in image.pixels() {
+        let next = palette.len();
+        let index = *palette.entry(px.0).or_insert(next);
+        if index > 255 {
+            return Ok(false);
+        }
+        indices.push(index as u8);
+    }
+    // each pixel saves three bytes and each colour costs four, so small
+    // images with lots of colours are smaller as they are
+    if palette.len() * 4 > indices.len() * 3 {
+        return Ok(false);
+    }
+
+    let depth = match palette.len() {
+        0..=2 => BitDepth::One,
+        3..=4 => BitDepth::Two,
+        5..=16 => BitDepth::Four,
+        _ => BitDepth::Eight,
+    };
+    let bits = depth as usize;
+
+    let mut colours = vec![[0; 4]; palette.len()];
+    for (colour, index) in palette {
+        colours[index] = colour;
+    }
+    let rgb: Vec<u8> = colours.iter().flat_map(|c| [c[0], c[1], c[2]]).collect();
+    // entries after the last transparent one can be left out of tRNS
+    let alpha_len = colours
+        .iter()
+        .rposition(|c| c[3] != 255)
+        .map_or(0, |i| i + 1);
+    let alpha: Vec<u8> = colours[..alpha_len].iter().map(|c| c[3]).collect();
+
+    // rows are packed most significant bits first, each starting on a byte
+    let (width, height) = image.dimensions();
+    let row_len = (width as usize * bits).div_ceil(8);
+    let mut packed = vec![0; row_len * height as usize];
+    for (row, indices) in packed
+        .chunks_exact_mut(row_len)
+        .zip(indices.chunks_exact(width as usize))
+    {
+        for (x, &index) in indices.iter().enumerate() {
+            let bit = x * bits;
+            row[bit / 8] |= index << (8 - bits - bit % 8);
+        }
+    }
+
+    let mut encoder = png::Encoder::new(data, width, height);
+    encoder.set_color(ColorType::Indexed);
+    encoder.set_depth(depth);
+    encoder.set_palette(rgb);
+    if !alpha.is_empty() {
+        encoder.set_trns(alpha);
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&packed)?;
+    writer.finish()?;
+    Ok(true)
+}
+
+/// How hard to try to shrink each PNG
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptLevel {
+    /// Write PNGs as the encoder gives them
+    #[default]
+    None,
+    /// oxipng's default preset, around a millisecond for a 16x16 texture
+    Fast,
+    /// Try every reduction and filter and compress with Zopfli, which is
+    /// slower by a couple of orders of magnitude for a few percent more
+    Max,
+}
+
+impl OptLevel {
+    fn options(self) -> Option<Options> {
+        let mut options = match self {
+            Self::None => return None,
+            Self::Fast => Options::from_preset(2),
+            Self::Max => Options {
+                deflater: Deflater::Zopfli(ZopfliOptions::default()),
+                ..Options::from_preset(6)
+            },
+        };
+        // the colour of transparent pixels shows in mipmaps, and may have
+        // been filled in on purpose by `bleed_alpha`
+        options.optimize_alpha = false;
+        options.strip = StripChunks::Safe;
+        Some(options)
+    }
+
+    /// Replace the PNG in `data` with a smaller one, if one can be found.
+    /// The pixels are unchanged.
+    pub fn optimize(self, data: &mut Vec<u8>) -> Result<()> {
+        let Some(options) = self.options() else {
+            return Ok(());
+        };
+        let optimized = oxipng::optimize_from_memory(data, &options)?;
+        if optimized.len() < data.len() {
+            *data = optimized;
+        }
+        Ok(())
+    }
+}
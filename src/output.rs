@@ -0,0 +1,414 @@
+//! Where generated packs are written: a zip, a directory, a `.tar.gz`, or
+//! bytes in memory
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{Compression, write::GzEncoder};
+use sha1::{Digest, Sha1};
+use tempfile::NamedTempFile;
+use walkdir::WalkDir;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    download,
+    error::{GenRpError, IoContext, Result},
+    generated::GENERATED_JSON,
+};
+
+/// Receives the files of a pack as it is generated
+pub trait OutputSink {
+    /// Add a file at `path`, relative to the root of the pack
+    fn add_file(&mut self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Add a file by copying it from `reader`, returning its size
+    fn copy_file(&mut self, path: &Path, reader: &mut dyn Read) -> Result<u64> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .io_context(|| format!("Reading {}", path.display()))?;
+        self.add_file(path, &data)?;
+        Ok(data.len() as u64)
+    }
+
+    /// Finish writing the pack
+    fn finish(self: Box<Self>) -> Result<PackData>;
+}
+
+/// Where a finished pack ended up
+#[derive(Clone, Debug)]
+pub enum PackData {
+    /// A zip or `.tar.gz`
+    File(PathBuf),
+    Dir(PathBuf),
+    /// A zip in memory, from [`MemorySink`]
+    Memory(Vec<u8>),
+}
+
+impl PackData {
+    /// Hex-encoded SHA-1 of the zip or `.tar.gz`
+    pub fn sha1(&self) -> Result<Option<String>> {
+        Ok(match self {
+            Self::File(path) => Some(download::sha1_file(path)?),
+            Self::Dir(_) => None,
+            Self::Memory(data) => Some(format!("{:x}", Sha1::digest(data))),
+        })
+    }
+
+    /// The file or directory the pack was written to
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::File(path) | Self::Dir(path) => Some(path),
+            Self::Memory(_) => None,
+        }
+    }
+}
+
+/// Check that `pack_name` names a single file in the output directory, so a
+/// pack called `..` or `a/b` can't be written (or removed) outside of it
+pub fn check_pack_name(pack_name: &str) -> Result<()> {
+    let mut components = Path::new(pack_name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) if name == pack_name => Ok(()),
+        _ => Err(GenRpError::PackName(pack_name.to_string())),
+    }
+}
+
+/// The kinds of [`OutputSink`] that write to disk
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Zip,
+    Dir,
+    TarGz,
+}
+
+impl OutputFormat {
+    /// Where a pack called `pack_name` is written in `out_dir`
+    pub fn path(self, out_dir: &Path, pack_name: &str) -> PathBuf {
+        let path = out_dir.join(pack_name);
+        match self {
+            Self::Zip => path.with_added_extension("zip"),
+            Self::Dir => path,
+            Self::TarGz => path.with_added_extension("tar.gz"),
+        }
+    }
+
+    pub fn sink(self, path: &Path, reproducible: bool) -> Result<Box<dyn OutputSink>> {
+        Ok(match self {
+            Self::Zip => Box::new(ZipSink::create(path, reproducible)?),
+            Self::Dir => Box::new(DirSink::create(path)?),
+            Self::TarGz => Box::new(TarGzSink::create(path, reproducible)?),
+        })
+    }
+}
+
+fn zip_options(reproducible: bool) -> SimpleFileOptions {
+    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    if reproducible {
+        // fixed metadata so the same input always gives the same zip
+        options
+            .last_modified_time(zip::DateTime::default())
+            .unix_permissions(0o644)
+    } else {
+        options
+    }
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<File>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).io_context(|| format!("Creating dir {}", parent.display()))?;
+    }
+    let file = File::create(path).io_context(|| format!("Creating file {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+/// A temporary file next to `path`, which [`persist`] moves to `path` once
+/// it's complete, so that a crash never leaves half a pack there.  It's
+/// removed if dropped before then.
+fn create_temp(path: &Path) -> Result<BufWriter<NamedTempFile>> {
+    let dir = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir).io_context(|| format!("Creating dir {}", dir.display()))?;
+
+    let mut prefix = std::ffi::OsString::from(".");
+    prefix.push(path.file_name().unwrap_or_default());
+    prefix.push(".");
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix).suffix(".part");
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o644));
+    let file = builder
+        .tempfile_in(dir)
+        .io_context(|| format!("Creating temporary file for {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+fn persist(writer: BufWriter<NamedTempFile>, path: &Path) -> Result<()> {
+    writer
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)
+        .and_then(|file| file.persist(path).map_err(|e| e.error))
+        .io_context(|| format!("Writing {}", path.display()))?;
+    Ok(())
+}
+
+/// A zip, written to a temporary file until it's finished
+pub struct ZipSink {
+    path: PathBuf,
+    writer: ZipWriter<BufWriter<NamedTempFile>>,
+    options: SimpleFileOptions,
+}
+
+impl ZipSink {
+    /// Entries are stored uncompressed, since the textures are already
+    /// compressed pngs
+    pub fn create(path: impl Into<PathBuf>, reproducible: bool) -> Result<Self> {
+        let path = path.into();
+        Ok(Self {
+            writer: ZipWriter::new(create_temp(&path)?),
+            path,
+            options: zip_options(reproducible),
+        })
+    }
+}
+
+impl OutputSink for ZipSink {
+    fn add_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        self.writer.start_file_from_path(path, self.options)?;
+        self.writer
+            .write_all(data)
+            .io_context(|| format!("Writing {} to zip", path.display()))
+    }
+
+    fn copy_file(&mut self, path: &Path, reader: &mut dyn Read) -> Result<u64> {
+        self.writer.start_file_from_path(path, self.options)?;
+        io::copy(reader, &mut self.writer)
+            .io_context(|| format!("Writing {} to zip", path.display()))
+    }
+
+    fn finish(self: Box<Self>) -> Result<PackData> {
+        persist(self.writer.finish()?, &self.path)?;
+        Ok(PackData::File(self.path))
+    }
+}
+
+/// An unzipped pack, which the game can load straight from `resourcepacks`
+pub struct DirSink {
+    dir: PathBuf,
+    /// Files added so far, when resuming, so that the rest can be removed
+    /// at the end
+    resumed: Option<HashSet<PathBuf>>,
+}
+
+impl DirSink {
+    /// Anything already in `dir` is removed, so stale files from a previous
+    /// run don't end up in the pack.  Only empty directories and packs
+    /// generated before (with a `generated.json`) are removed, anything else
+    /// is an error.
+    pub fn create(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        if dir.exists() {
+            let empty = fs::read_dir(&dir)
+                .io_context(|| format!("Reading {}", dir.display()))?
+                .next()
+                .is_none();
+            if !empty && !dir.join(GENERATED_JSON).is_file() {
+                return Err(GenRpError::File {
+                    path: dir,
+                    reason: format!(
+                        "not replacing a directory without a {}, remove it or use --resume",
+                        GENERATED_JSON
+                    ),
+                });
+            }
+            fs::remove_dir_all(&dir).io_context(|| format!("Removing {}", dir.display()))?;
+        }
+        fs::create_dir_all(&dir).io_context(|| format!("Creating dir {}", dir.display()))?;
+        Ok(Self { dir, resumed: None })
+    }
+
+    /// Keep what's already in `dir`, from a run that didn't finish, and only
+    /// write the files that are missing or different.  Files that aren't
+    /// added again are removed by [`OutputSink::finish`].
+    pub fn resume(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).io_context(|| format!("Creating dir {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            resumed: Some(HashSet::new()),
+        })
+    }
+
+    /// Remove the files left from the previous run that weren't added again,
+    /// and any directories that leaves empty
+    fn remove_stale(&self, added: &HashSet<PathBuf>) -> Result<()> {
+        for entry in WalkDir::new(&self.dir).contents_first(true) {
+            let entry = entry?;
+            let path = entry.path();
+            let in_pack = path.strip_prefix(&self.dir).expect("path is in dir");
+            if entry.file_type().is_dir() {
+                if path != self.dir && fs::read_dir(path).is_ok_and(|mut d| d.next().is_none()) {
+                    fs::remove_dir(path).io_context(|| format!("Removing {}", path.display()))?;
+                }
+            } else if !added.contains(in_pack) {
+                fs::remove_file(path).io_context(|| format!("Removing {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OutputSink for DirSink {
+    fn add_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        if let Some(added) = &mut self.resumed {
+            added.insert(path.to_path_buf());
+            if fs::read(self.dir.join(path)).is_ok_and(|existing| existing == data) {
+                return Ok(());
+            }
+        }
+
+        let mut file = create_file(&self.dir.join(path))?;
+        file.write_all(data)
+            .and_then(|_| file.flush())
+            .io_context(|| format!("Writing {}", path.display()))
+    }
+
+    fn copy_file(&mut self, path: &Path, reader: &mut dyn Read) -> Result<u64> {
+        if self.resumed.is_some() {
+            let mut data = Vec::new();
+            reader
+                .read_to_end(&mut data)
+                .io_context(|| format!("Reading {}", path.display()))?;
+            self.add_file(path, &data)?;
+            return Ok(data.len() as u64);
+        }
+
+        let mut file = create_file(&self.dir.join(path))?;
+        let n = io::copy(reader, &mut file).io_context(|| format!("Writing {}", path.display()))?;
+        file.flush()
+            .io_context(|| format!("Writing {}", path.display()))?;
+        Ok(n)
+    }
+
+    fn finish(self: Box<Self>) -> Result<PackData> {
+        if let Some(added) = &self.resumed {
+            self.remove_stale(added)?;
+        }
+        Ok(PackData::Dir(self.dir))
+    }
+}
+
+/// A `.tar.gz`, written to a temporary file until it's finished
+pub struct TarGzSink {
+    path: PathBuf,
+    builder: tar::Builder<GzEncoder<BufWriter<NamedTempFile>>>,
+    mtime: u64,
+}
+
+impl TarGzSink {
+    pub fn create(path: impl Into<PathBuf>, reproducible: bool) -> Result<Self> {
+        let path = path.into();
+        let encoder = GzEncoder::new(create_temp(&path)?, Compression::default());
+        let mtime = if reproducible {
+            0
+        } else {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        };
+        Ok(Self {
+            path,
+            builder: tar::Builder::new(encoder),
+            mtime,
+        })
+    }
+}
+
+impl OutputSink for TarGzSink {
+    fn add_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(self.mtime);
+        header.set_cksum();
+        self.builder
+            .append_data(&mut header, path, data)
+            .io_context(|| format!("Writing {} to tar", path.display()))
+    }
+
+    fn finish(self: Box<Self>) -> Result<PackData> {
+        let writer = self
+            .builder
+            .into_inner()
+            .and_then(GzEncoder::finish)
+            .io_context(|| format!("Writing {}", self.path.display()))?;
+        persist(writer, &self.path)?;
+        Ok(PackData::File(self.path))
+    }
+}
+
+/// A zipped pack kept in memory, for uploading without touching disk
+pub struct MemorySink {
+    writer: ZipWriter<Cursor<Vec<u8>>>,
+    options: SimpleFileOptions,
+}
+
+impl MemorySink {
+    pub fn new(reproducible: bool) -> Self {
+        Self {
+            writer: ZipWriter::new(Cursor::new(Vec::new())),
+            options: zip_options(reproducible),
+        }
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn add_file(&mut self, path: &Path, data: &[u8]) -> Result<()> {
+        self.writer.start_file_from_path(path, self.options)?;
+        self.writer
+            .write_all(data)
+            .io_context(|| format!("Writing {} to zip", path.display()))
+    }
+
+    fn finish(self: Box<Self>) -> Result<PackData> {
+        Ok(PackData::Memory(self.writer.finish()?.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_names_are_single_file_names() {
+        for name in ["8-bit", "My Pack", "pack.v2"] {
+            assert!(check_pack_name(name).is_ok(), "{name:?}");
+        }
+        for name in ["", ".", "..", "a/b", "../a", "a/", "./a", "/a"] {
+            assert!(check_pack_name(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn dir_sink_only_replaces_generated_packs() {
+        let out = tempfile::tempdir().unwrap();
+        let dir = out.path().join("pack");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "mine").unwrap();
+        assert!(DirSink::create(&dir).is_err());
+        assert!(dir.join("notes.txt").exists());
+
+        fs::write(dir.join(GENERATED_JSON), "{}").unwrap();
+        DirSink::create(&dir).unwrap();
+        assert!(!dir.join("notes.txt").exists());
+    }
+}
@@ -0,0 +1,92 @@
+//! Reading existing resource packs, zipped or not
+
+use std::{
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+use crate::error::{GenRpError, IoContext, Result};
+
+/// A resource pack on disk, either a zip or a directory laid out like one
+pub enum PackSource {
+    Zip(ZipArchive<BufReader<File>>),
+    Dir(PathBuf),
+}
+
+impl PackSource {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return Ok(Self::Dir(path.to_path_buf()));
+        }
+
+        let file = File::open(path).io_context(|| format!("Opening {}", path.display()))?;
+        Ok(Self::Zip(ZipArchive::new(BufReader::new(file))?))
+    }
+
+    /// Every file in the pack, relative to its root and sorted
+    pub fn files(&mut self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        match self {
+            Self::Zip(zip) => {
+                for i in 0..zip.len() {
+                    let file = zip.by_index(i)?;
+                    if file.is_dir() {
+                        continue;
+                    }
+                    let path = file.enclosed_name().ok_or_else(|| {
+                        GenRpError::InvalidPack(format!("malformed path {}", file.name()))
+                    })?;
+                    files.push(path);
+                }
+            }
+            Self::Dir(dir) => {
+                for entry in WalkDir::new(&*dir) {
+                    let entry = entry?;
+                    if entry.file_type().is_dir() {
+                        continue;
+                    }
+                    files.push(
+                        entry
+                            .path()
+                            .strip_prefix(&*dir)
+                            .expect("path is in dir")
+                            .to_path_buf(),
+                    );
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    /// Read the file at `path` (relative to the root of the pack)
+    pub fn read(&mut self, path: &Path) -> Result<Vec<u8>> {
+        match self {
+            Self::Zip(zip) => {
+                let mut file = zip.by_name(&zip_name(path))?;
+                let mut buf = Vec::with_capacity(file.size() as usize);
+                file.read_to_end(&mut buf)
+                    .io_context(|| format!("Reading {}", path.display()))?;
+                Ok(buf)
+            }
+            Self::Dir(dir) => {
+                let path = dir.join(path);
+                fs::read(&path).io_context(|| format!("Reading {}", path.display()))
+            }
+        }
+    }
+}
+
+/// The name of `path` inside of a zip, which always uses `/`
+fn zip_name(path: &Path) -> String {
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,282 @@
+use std::{borrow::Cow, fs, path::Path};
+
+use image::{DynamicImage, ImageReader, Rgb, Rgba, RgbaImage};
+use rand::Rng;
+
+use crate::{
+    dither::{Dither, dither},
+    error::{GenRpError, IoContext, Result},
+    filter::LumaWeights,
+    filter::Transform,
+    k_means::{DEFAULT_MIN_ALPHA, closest, k_means_rgba, recolour},
+};
+
+#[derive(Clone, Debug)]
+pub struct Palette {
+    pub colours: Cow<'static, [Rgb<u8>]>,
+}
+
+impl Palette {
+    pub const fn new(colours: &'static [Rgb<u8>]) -> Self {
+        Self {
+            colours: Cow::Borrowed(colours),
+        }
+    }
+
+    /// Load a palette, picking the format from the extension: `.gpl` for a
+    /// GIMP palette, `.png` for an image strip, otherwise a list of hex colours
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("png") => Self::from_png(path),
+            Some("gpl") => Self::from_gpl(
+                &fs::read_to_string(path)
+                    .io_context(|| format!("Reading palette {}", path.display()))?,
+            ),
+            _ => Self::from_hex_list(
+                &fs::read_to_string(path)
+                    .io_context(|| format!("Reading palette {}", path.display()))?,
+            ),
+        }
+    }
+
+    /// Colours like `#1d2b53`, separated by whitespace or commas, the `#` is
+    /// optional
+    pub fn from_hex_list(s: &str) -> Result<Self> {
+        s.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(parse_hex)
+            .collect::<Result<Vec<_>>>()
+            .and_then(Self::from_vec)
+    }
+
+    /// A GIMP palette, see <https://developer.gimp.org/core/standards/gpl/>
+    pub fn from_gpl(s: &str) -> Result<Self> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some("GIMP Palette") {
+            return Err(GenRpError::InvalidPalette(
+                "missing \"GIMP Palette\" header".into(),
+            ));
+        }
+
+        let mut colours = Vec::new();
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("Name:")
+                || line.starts_with("Columns:")
+            {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace().map(str::parse::<u8>);
+            let mut next = || {
+                parts
+                    .next()
+                    .and_then(|c| c.ok())
+                    .ok_or_else(|| GenRpError::InvalidPalette(format!("bad line: {}", line)))
+            };
+            colours.push(Rgb([next()?, next()?, next()?]));
+        }
+
+        Self::from_vec(colours)
+    }
+
+    /// Every distinct opaque colour in the image, in reading order
+    pub fn from_png(path: &Path) -> Result<Self> {
+        let image = ImageReader::open(path)
+            .io_context(|| format!("Reading palette {}", path.display()))?
+            .decode()
+            .map_err(|source| GenRpError::Decode {
+                path: path.to_path_buf(),
+                source,
+            })?
+            .into_rgba8();
+
+        let mut colours = Vec::new();
+        for px in image.pixels().filter(|px| px[3] > 0) {
+            let rgb = Rgb([px[0], px[1], px[2]]);
+            if !colours.contains(&rgb) {
+                colours.push(rgb);
+            }
+        }
+
+        Self::from_vec(colours)
+    }
+
+    fn from_vec(colours: Vec<Rgb<u8>>) -> Result<Self> {
+        if colours.is_empty() {
+            return Err(GenRpError::InvalidPalette("palette is empty".into()));
+        }
+        Ok(Self {
+            colours: Cow::Owned(colours),
+        })
+    }
+
+    /// Up to `k` colours for the visible pixels of `image` found with
+    /// k-means, darkest first
+    pub fn extract(image: &RgbaImage, k: usize, rng: &mut impl Rng) -> Result<Self> {
+        let mut colours = k_means_rgba(k, image, DEFAULT_MIN_ALPHA, rng);
+        let luma = |c: &Rgb<u8>| LumaWeights::default().luma(c.0.map(f32::from));
+        colours.sort_by(|a, b| luma(a).total_cmp(&luma(b)));
+        Self::from_vec(colours)
+    }
+
+    pub fn nearest(&self, colour: Rgb<u8>) -> Rgb<u8> {
+        closest(colour, &self.colours)
+    }
+
+    /// Replace the colour of each visible pixel with the nearest in the
+    /// palette
+    pub fn apply_to(&self, image: &mut RgbaImage) {
+        recolour(image, &self.colours, DEFAULT_MIN_ALPHA);
+    }
+
+    /// One `#rrggbb` per line, as read by [`Palette::from_hex_list`]
+    pub fn to_hex_list(&self) -> String {
+        self.colours
+            .iter()
+            .map(|c| format!("#{:02x}{:02x}{:02x}\n", c[0], c[1], c[2]))
+            .collect()
+    }
+
+    /// A row of `size` pixel squares, one for each colour, as read by
+    /// [`Palette::from_png`]
+    pub fn swatch(&self, size: u32) -> RgbaImage {
+        RgbaImage::from_fn(size * self.colours.len() as u32, size, |x, _| {
+            let [r, g, b] = self.colours[(x / size) as usize].0;
+            Rgba([r, g, b, 255])
+        })
+    }
+}
+
+/// A colour like `#1d2b53`, the `#` is optional
+pub(crate) fn parse_hex(s: &str) -> Result<Rgb<u8>> {
+    let hex = s.trim_start_matches('#');
+    let invalid = || GenRpError::InvalidPalette(format!("invalid hex colour: {}", s));
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+}
+
+/// Game Boy (DMG) greens
+pub const GAME_BOY: Palette = Palette::new(&[
+    Rgb([0x0f, 0x38, 0x0f]),
+    Rgb([0x30, 0x62, 0x30]),
+    Rgb([0x8b, 0xac, 0x0f]),
+    Rgb([0x9b, 0xbc, 0x0f]),
+]);
+
+/// CGA mode 4, palette 1, high intensity
+pub const CGA: Palette = Palette::new(&[
+    Rgb([0x00, 0x00, 0x00]),
+    Rgb([0x55, 0xff, 0xff]),
+    Rgb([0xff, 0x55, 0xff]),
+    Rgb([0xff, 0xff, 0xff]),
+]);
+
+/// NES (2C02) colours, without the duplicate blacks
+pub const NES: Palette = Palette::new(&[
+    Rgb([0x00, 0x00, 0x00]),
+    Rgb([0xfc, 0xfc, 0xfc]),
+    Rgb([0xf8, 0xf8, 0xf8]),
+    Rgb([0xbc, 0xbc, 0xbc]),
+    Rgb([0x7c, 0x7c, 0x7c]),
+    Rgb([0xa4, 0xe4, 0xfc]),
+    Rgb([0x3c, 0xbc, 0xfc]),
+    Rgb([0x00, 0x78, 0xf8]),
+    Rgb([0x00, 0x00, 0xfc]),
+    Rgb([0xb8, 0xb8, 0xf8]),
+    Rgb([0x68, 0x88, 0xfc]),
+    Rgb([0x00, 0x58, 0xf8]),
+    Rgb([0x00, 0x00, 0xbc]),
+    Rgb([0xd8, 0xb8, 0xf8]),
+    Rgb([0x98, 0x78, 0xf8]),
+    Rgb([0x68, 0x44, 0xfc]),
+    Rgb([0x44, 0x28, 0xbc]),
+    Rgb([0xf8, 0xb8, 0xf8]),
+    Rgb([0xf8, 0x78, 0xf8]),
+    Rgb([0xd8, 0x00, 0xcc]),
+    Rgb([0x94, 0x00, 0x84]),
+    Rgb([0xf8, 0xa4, 0xc0]),
+    Rgb([0xf8, 0x58, 0x98]),
+    Rgb([0xe4, 0x00, 0x58]),
+    Rgb([0xa8, 0x00, 0x20]),
+    Rgb([0xf0, 0xd0, 0xb0]),
+    Rgb([0xf8, 0x78, 0x58]),
+    Rgb([0xf8, 0x38, 0x00]),
+    Rgb([0xa8, 0x10, 0x00]),
+    Rgb([0xfc, 0xe0, 0xa8]),
+    Rgb([0xfc, 0xa0, 0x44]),
+    Rgb([0xe4, 0x5c, 0x10]),
+    Rgb([0x88, 0x14, 0x00]),
+    Rgb([0xf8, 0xd8, 0x78]),
+    Rgb([0xf8, 0xb8, 0x00]),
+    Rgb([0xac, 0x7c, 0x00]),
+    Rgb([0x50, 0x30, 0x00]),
+    Rgb([0xd8, 0xf8, 0x78]),
+    Rgb([0xb8, 0xf8, 0x18]),
+    Rgb([0x00, 0xb8, 0x00]),
+    Rgb([0x00, 0x78, 0x00]),
+    Rgb([0xb8, 0xf8, 0xb8]),
+    Rgb([0x58, 0xd8, 0x54]),
+    Rgb([0x00, 0xa8, 0x00]),
+    Rgb([0x00, 0x68, 0x00]),
+    Rgb([0xb8, 0xf8, 0xd8]),
+    Rgb([0x58, 0xf8, 0x98]),
+    Rgb([0x00, 0xa8, 0x44]),
+    Rgb([0x00, 0x58, 0x00]),
+    Rgb([0x00, 0xfc, 0xfc]),
+    Rgb([0x00, 0xe8, 0xd8]),
+    Rgb([0x00, 0x88, 0x88]),
+    Rgb([0x00, 0x40, 0x58]),
+    Rgb([0xf8, 0xd8, 0xf8]),
+    Rgb([0x78, 0x78, 0x78]),
+]);
+
+/// PICO-8
+pub const PICO_8: Palette = Palette::new(&[
+    Rgb([0x00, 0x00, 0x00]),
+    Rgb([0x1d, 0x2b, 0x53]),
+    Rgb([0x7e, 0x25, 0x53]),
+    Rgb([0x00, 0x87, 0x51]),
+    Rgb([0xab, 0x52, 0x36]),
+    Rgb([0x5f, 0x57, 0x4f]),
+    Rgb([0xc2, 0xc3, 0xc7]),
+    Rgb([0xff, 0xf1, 0xe8]),
+    Rgb([0xff, 0x00, 0x4d]),
+    Rgb([0xff, 0xa3, 0x00]),
+    Rgb([0xff, 0xec, 0x27]),
+    Rgb([0x00, 0xe4, 0x36]),
+    Rgb([0x29, 0xad, 0xff]),
+    Rgb([0x83, 0x76, 0x9c]),
+    Rgb([0xff, 0x77, 0xa8]),
+    Rgb([0xff, 0xcc, 0xaa]),
+]);
+
+/// Map every pixel to the nearest colour in `palette`
+#[derive(Clone, Debug)]
+pub struct PaletteMap {
+    pub palette: Palette,
+    pub dither: Dither,
+}
+
+impl Transform for PaletteMap {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        dither(image, self.dither, |px| {
+            let clamp = |c: i32| c.clamp(0, 255) as u8;
+            let nearest = self
+                .palette
+                .nearest(Rgb([clamp(px[0]), clamp(px[1]), clamp(px[2])]));
+            Rgba([
+                nearest[0] as i32,
+                nearest[1] as i32,
+                nearest[2] as i32,
+                px[3],
+            ])
+        })
+    }
+}
@@ -0,0 +1,207 @@
+//! LabPBR normal (`_n`) and specular (`_s`) maps made up for each block
+//! texture, so that shader packs (through Iris or OptiFine) have something to
+//! light the generated packs with.  The height of each pixel is guessed from
+//! its brightness, and the material from that and the texture's name.  See
+//! <https://shaderlabs.org/wiki/LabPBR_Material_Standard> for the format.
+
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    animation::{Animation, map_frames},
+    filter::{luma, texture_category},
+};
+
+/// How the maps are made
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PbrOptions {
+    /// How steep the normals are for a change in brightness
+    pub normal_strength: f32,
+}
+
+impl Default for PbrOptions {
+    fn default() -> Self {
+        Self {
+            normal_strength: 2.,
+        }
+    }
+}
+
+/// The normal and specular map of a texture are next to it, with these
+/// added to its name
+const SUFFIXES: [&str; 2] = ["_n", "_s"];
+
+/// Where the normal and specular maps of the texture at `path` go, if it's a
+/// block texture (and not a map itself)
+pub fn companion_paths(path: &Path) -> Option<[PathBuf; 2]> {
+    if texture_category(path) != Some("block") || path.extension()? != "png" {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    if SUFFIXES.iter().any(|suffix| stem.ends_with(suffix)) {
+        return None;
+    }
+    Some(SUFFIXES.map(|suffix| path.with_file_name(format!("{}{}.png", stem, suffix))))
+}
+
+/// The normal and specular maps of `image`, the texture at `path`, made
+/// frame by frame if it's animated
+pub fn maps(
+    image: &DynamicImage,
+    path: &Path,
+    animation: Option<&Animation>,
+    options: &PbrOptions,
+) -> [DynamicImage; 2] {
+    let name = path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let material = Material::guess(&name);
+    let per_frame = |f: &dyn Fn(&RgbaImage) -> RgbaImage| match animation {
+        Some(animation) => map_frames(image.clone(), animation, |frame| {
+            f(&frame.into_rgba8()).into()
+        }),
+        None => f(&image.to_rgba8()).into(),
+    };
+    [
+        per_frame(&|frame| normal_map(frame, options.normal_strength)),
+        per_frame(&|frame| specular_map(frame, &material)),
+    ]
+}
+
+/// Brightness of each pixel, for its height, read with wrapping as block
+/// textures tile
+struct Heights {
+    width: i64,
+    height: i64,
+    heights: Vec<f32>,
+}
+
+impl Heights {
+    fn new(image: &RgbaImage) -> Self {
+        Self {
+            width: image.width() as i64,
+            height: image.height() as i64,
+            heights: image.pixels().map(|px| luma(*px)).collect(),
+        }
+    }
+
+    fn get(&self, x: i64, y: i64) -> f32 {
+        let (x, y) = (x.rem_euclid(self.width), y.rem_euclid(self.height));
+        self.heights[(y * self.width + x) as usize]
+    }
+
+    /// Mean of the pixel and the 8 around it
+    fn mean(&self, x: i64, y: i64) -> f32 {
+        let mut sum = 0.;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                sum += self.get(x + dx, y + dy);
+            }
+        }
+        sum / 9.
+    }
+}
+
+fn to_u8(c: f32) -> u8 {
+    (c.clamp(0., 1.) * 255.).round() as u8
+}
+
+/// Normals from the Sobel gradient of the heights (in DirectX's `Y-`
+/// orientation, like LabPBR wants), ambient occlusion where a pixel is lower
+/// than those around it, and a shallow height map in the alpha
+fn normal_map(image: &RgbaImage, strength: f32) -> RgbaImage {
+    let heights = Heights::new(image);
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let h = |dx, dy| heights.get(x + dx, y + dy);
+        let gx = (h(1, -1) + 2. * h(1, 0) + h(1, 1)) - (h(-1, -1) + 2. * h(-1, 0) + h(-1, 1));
+        let gy = (h(-1, 1) + 2. * h(0, 1) + h(1, 1)) - (h(-1, -1) + 2. * h(0, -1) + h(1, -1));
+        let (nx, ny) = (-gx * strength / 4., -gy * strength / 4.);
+        let len = (nx * nx + ny * ny + 1.).sqrt();
+
+        let height = h(0, 0);
+        let occlusion = ((heights.mean(x, y) - height) * 2.).clamp(0., 0.5);
+        Rgba([
+            to_u8(nx / len * 0.5 + 0.5),
+            to_u8(ny / len * 0.5 + 0.5),
+            to_u8(1. - occlusion),
+            to_u8(0.5 + height * 0.5),
+        ])
+    })
+}
+
+/// What a texture is made of, as far as the specular map cares
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Material {
+    /// Of a flat pixel, rough ones get less
+    smoothness: f32,
+    /// Reflectance of a dielectric (up to 229), or one of LabPBR's metals
+    f0: u8,
+    /// Porosity (up to 64) or subsurface scattering (65 and up)
+    porosity: u8,
+}
+
+impl Material {
+    /// From the `_`-separated words of a texture's name, like `iron_block`
+    fn guess(name: &str) -> Self {
+        let words: Vec<_> = name.split('_').collect();
+        let has = |list: &[&str]| words.iter().any(|w| list.contains(w));
+
+        let metal = if has(&["ore", "raw"]) {
+            None
+        } else if has(&["iron", "chain", "anvil"]) {
+            Some(230)
+        } else if has(&["gold"]) {
+            Some(231)
+        } else if has(&["copper"]) {
+            Some(234)
+        } else if has(&["netherite"]) {
+            // the albedo is the reflectance
+            Some(255)
+        } else {
+            None
+        };
+        let smooth = has(&[
+            "glass", "ice", "polished", "smooth", "glazed", "quartz", "obsidian", "amethyst",
+            "diamond", "emerald",
+        ]);
+
+        Self {
+            smoothness: match (metal, smooth) {
+                (_, true) => 0.8,
+                (Some(_), false) => 0.6,
+                (None, false) => 0.15,
+            },
+            // about 4%, like most non-metals
+            f0: metal.unwrap_or(10),
+            porosity: if has(&[
+                "leaves", "grass", "vine", "vines", "fern", "kelp", "seagrass",
+            ]) {
+                200
+            } else if has(&["dirt", "sand", "gravel", "mud", "clay", "soul"]) {
+                48
+            } else {
+                0
+            },
+        }
+    }
+}
+
+/// Smoothness (lower where a pixel stands out from those around it),
+/// reflectance and porosity from `material`, and no emission
+fn specular_map(image: &RgbaImage, material: &Material) -> RgbaImage {
+    let heights = Heights::new(image);
+    RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let roughness = ((heights.get(x, y) - heights.mean(x, y)).abs() * 4.).min(1.);
+        Rgba([
+            to_u8(material.smoothness * (1. - roughness * 0.5)),
+            material.f0,
+            material.porosity,
+            255,
+        ])
+    })
+}
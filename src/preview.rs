@@ -0,0 +1,258 @@
+//! Previews of what a pack does to the textures, to review it without
+//! launching the game.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageReader, Rgba, RgbaImage, imageops};
+use walkdir::WalkDir;
+
+use crate::{
+    GenerateOptions,
+    bitmap_font::{GLYPH_HEIGHT, draw_text},
+    error::{GenRpError, IoContext, Result},
+    filter::Transform,
+};
+
+/// The textures shown in previews, inside of a pack
+const PREVIEW_DIRS: [&str; 2] = [
+    "assets/minecraft/textures/block",
+    "assets/minecraft/textures/item",
+];
+
+const BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const LABEL: Rgba<u8> = Rgba([220, 220, 220, 255]);
+/// Space around and between cells
+const PADDING: u32 = 4;
+/// Space between the before and after of a texture
+const GAP: u32 = 2;
+
+/// The block and item textures in `textures_dir`, as paths in the pack,
+/// sorted
+pub fn preview_textures(textures_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for dir in PREVIEW_DIRS {
+        for entry in WalkDir::new(textures_dir.join(dir)).sort_by_file_name() {
+            let entry = entry?;
+            if entry.path().extension().is_some_and(|ext| ext == "png") {
+                let path = entry
+                    .path()
+                    .strip_prefix(textures_dir)
+                    .expect("path is in textures_dir");
+                paths.push(path.to_path_buf());
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// The texture at `path` in the pack (just the first frame of animations)
+/// and it transformed by `f`
+pub fn before_after(
+    textures_dir: &Path,
+    path: &Path,
+    f: &dyn Transform,
+) -> Result<(DynamicImage, DynamicImage)> {
+    let full_path = textures_dir.join(path);
+    let image = ImageReader::open(&full_path)
+        .io_context(|| format!("Opening {}", full_path.display()))?
+        .decode()
+        .map_err(|source| GenRpError::Decode {
+            path: full_path,
+            source,
+        })?;
+    let (width, height) = image.dimensions();
+    let before = image.crop_imm(0, 0, width, height.min(width));
+    let after = f.apply_with_path(before.clone(), path);
+    Ok((before, after))
+}
+
+/// All of a pack's block and item textures on one image, each shown before
+/// and after next to each other
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContactSheet {
+    /// Textures across
+    pub columns: u32,
+    /// Size textures are scaled to (nearest neighbour)
+    pub cell_size: u32,
+    /// Write the name of each texture under it
+    pub labels: bool,
+}
+
+impl Default for ContactSheet {
+    fn default() -> Self {
+        Self {
+            columns: 16,
+            cell_size: 32,
+            labels: false,
+        }
+    }
+}
+
+impl ContactSheet {
+    pub fn render(&self, textures_dir: &Path, f: &dyn Transform) -> Result<RgbaImage> {
+        let paths = preview_textures(textures_dir)?;
+        let columns = self.columns.max(1);
+        let rows = (paths.len() as u32).div_ceil(columns);
+        let size = self.cell_size.max(1);
+        let label_height = if self.labels { GLYPH_HEIGHT + 2 } else { 0 };
+        let cell_width = size * 2 + GAP;
+        let cell_height = size + label_height;
+
+        let mut sheet = RgbaImage::from_pixel(
+            columns * (cell_width + PADDING) + PADDING,
+            rows * (cell_height + PADDING) + PADDING,
+            BACKGROUND,
+        );
+        for (i, path) in paths.iter().enumerate() {
+            let (before, after) = match before_after(textures_dir, path, f) {
+                Ok(images) => images,
+                // leave a gap rather than failing the whole sheet
+                Err(_) => continue,
+            };
+
+            let x = PADDING + (i as u32 % columns) * (cell_width + PADDING);
+            let y = PADDING + (i as u32 / columns) * (cell_height + PADDING);
+            for (image, x) in [(before, x), (after, x + size + GAP)] {
+                let scaled = image.resize_exact(size, size, imageops::FilterType::Nearest);
+                imageops::overlay(&mut sheet, &scaled.to_rgba8(), x as i64, y as i64);
+            }
+
+            if self.labels {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                draw_text(&mut sheet, &name, x, y + size + 2, cell_width, LABEL);
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /// Render the sheet for a pack transformed by `f` and write it next to
+    /// the pack, as `<pack_name>.contact.png`
+    pub fn write(
+        &self,
+        pack_name: &str,
+        f: &dyn Transform,
+        options: &GenerateOptions,
+    ) -> Result<PathBuf> {
+        let sheet = self.render(&options.textures_dir, f)?;
+        let path = options.out_dir.join(format!("{}.contact.png", pack_name));
+        sheet.save(&path)?;
+        Ok(path)
+    }
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+    Ok(data)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `s` without colour and formatting codes (`§a`)
+fn strip_formatting(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+const GALLERY_STYLE: &str = "\
+body { background: #202020; color: #ddd; font-family: sans-serif; margin: 2em; }
+main { display: grid; grid-template-columns: repeat(auto-fill, minmax(160px, 1fr)); gap: 1em; }
+figure { margin: 0; text-align: center; }
+figcaption { font-size: 0.75em; overflow-wrap: anywhere; }
+img { width: 64px; height: 64px; image-rendering: pixelated; }";
+
+/// An `index.html` showing a pack's block and item textures before and after
+/// next to each other, to look over or share a pack without the game
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Gallery {
+    /// Put the images in the page as data URIs, so the page is one file that
+    /// can be sent around, instead of linking to `before/` and `after/`
+    /// directories next to it
+    pub embed: bool,
+}
+
+impl Gallery {
+    /// Write the gallery for a pack transformed by `f` to
+    /// `<pack_name>.preview/index.html` next to the pack, returning its path
+    pub fn write(
+        &self,
+        pack_name: &str,
+        description: &str,
+        f: &dyn Transform,
+        options: &GenerateOptions,
+    ) -> Result<PathBuf> {
+        let dir = options.out_dir.join(format!("{}.preview", pack_name));
+        fs::create_dir_all(&dir).io_context(|| format!("Creating {}", dir.display()))?;
+
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+             <style>\n{1}\n</style>\n</head>\n<body>\n<h1>{0}</h1>\n<p>{2}</p>\n<main>\n",
+            escape_html(pack_name),
+            GALLERY_STYLE,
+            escape_html(&strip_formatting(description)).replace('\n', "<br>"),
+        );
+
+        for path in preview_textures(&options.textures_dir)? {
+            let Ok((before, after)) = before_after(&options.textures_dir, &path, f) else {
+                continue;
+            };
+
+            let name = path
+                .strip_prefix("assets/minecraft/textures")
+                .unwrap_or(&path)
+                .with_extension("");
+            let name = name.to_string_lossy().replace('\\', "/");
+            let mut srcs = Vec::with_capacity(2);
+            for (kind, image) in [("before", before), ("after", after)] {
+                let data = encode_png(&image)?;
+                if self.embed {
+                    srcs.push(format!("data:image/png;base64,{}", BASE64.encode(&data)));
+                } else {
+                    let file = dir.join(kind).join(format!("{}.png", name));
+                    if let Some(parent) = file.parent() {
+                        fs::create_dir_all(parent)
+                            .io_context(|| format!("Creating {}", parent.display()))?;
+                    }
+                    fs::write(&file, &data).io_context(|| format!("Writing {}", file.display()))?;
+                    srcs.push(format!("{}/{}.png", kind, escape_html(&name)));
+                }
+            }
+
+            writeln!(
+                html,
+                "<figure><img src=\"{}\" alt=\"before\" title=\"before\"> \
+                 <img src=\"{}\" alt=\"after\" title=\"after\">\
+                 <figcaption>{}</figcaption></figure>",
+                srcs[0],
+                srcs[1],
+                escape_html(&name)
+            )
+            .expect("writing to a string can't fail");
+        }
+        html.push_str("</main>\n</body>\n</html>\n");
+
+        let index = dir.join("index.html");
+        fs::write(&index, html).io_context(|| format!("Writing {}", index.display()))?;
+        Ok(index)
+    }
+}
@@ -0,0 +1,131 @@
+//! Progress reporting for [`crate::generate_pack`], so that the library
+//! isn't tied to terminal progress bars
+
+use std::path::{Path, PathBuf};
+
+use prog::Progress;
+use serde::Serialize;
+
+use crate::{PackOutput, timings::StageTimings};
+
+pub trait ProgressSink {
+    /// `done` entries of the textures directory have been processed
+    fn update(&mut self, done: usize);
+    fn set_status(&mut self, status: &str);
+    /// The file being processed, relative to the textures directory
+    fn set_file(&mut self, _file: &Path) {}
+}
+
+/// Ignore all progress
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn update(&mut self, _done: usize) {}
+
+    fn set_status(&mut self, _status: &str) {}
+}
+
+impl ProgressSink for Progress<usize> {
+    fn update(&mut self, done: usize) {
+        Progress::update(self, done);
+    }
+
+    fn set_status(&mut self, status: &str) {
+        Progress::set_status(self, status);
+    }
+}
+
+/// A line of machine-readable progress, for tools that run the generator and
+/// show its progress themselves
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent<'a> {
+    /// `done` of the `total` entries of the textures directory have been
+    /// written to `pack`, and it's in the directory `status` working on
+    /// `file`
+    Progress {
+        pack: &'a str,
+        done: usize,
+        total: usize,
+        status: &'a str,
+        file: Option<&'a Path>,
+    },
+    /// `pack` has been written
+    Finished {
+        pack: &'a str,
+        path: Option<&'a Path>,
+        files: usize,
+        bytes: u64,
+        skipped: &'a [PathBuf],
+        warnings: &'a [String],
+        sha1: Option<&'a str>,
+        timings: StageTimings,
+    },
+    /// `pack` couldn't be written
+    Failed { pack: &'a str, error: String },
+}
+
+impl<'a> ProgressEvent<'a> {
+    pub fn finished(pack: &'a str, output: &'a PackOutput) -> Self {
+        Self::Finished {
+            pack,
+            path: output.data.path(),
+            files: output.report.files_written,
+            bytes: output.report.bytes_written,
+            skipped: &output.report.skipped,
+            warnings: &output.report.warnings,
+            sha1: output.sha1.as_deref(),
+            timings: output.report.timings,
+        }
+    }
+
+    /// Print the event to stdout, as one line of JSON
+    pub fn emit(&self) {
+        let line = serde_json::to_string(self).expect("events serialize");
+        println!("{}", line);
+    }
+}
+
+/// Prints a [`ProgressEvent::Progress`] line for each update
+#[derive(Clone, Debug)]
+pub struct JsonProgress {
+    pack: String,
+    total: usize,
+    status: String,
+    file: Option<PathBuf>,
+}
+
+impl JsonProgress {
+    /// `total` is how many entries the textures directory has
+    pub fn new(pack: impl Into<String>, total: usize) -> Self {
+        Self {
+            pack: pack.into(),
+            total,
+            status: String::new(),
+            file: None,
+        }
+    }
+}
+
+impl ProgressSink for JsonProgress {
+    fn update(&mut self, done: usize) {
+        ProgressEvent::Progress {
+            pack: &self.pack,
+            // the end of the walk is counted as an entry too
+            done: done.min(self.total),
+            total: self.total,
+            status: &self.status,
+            file: self.file.as_deref(),
+        }
+        .emit();
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.status = status.to_string();
+    }
+
+    fn set_file(&mut self, file: &Path) {
+        self.file = Some(file.to_path_buf());
+    }
+}
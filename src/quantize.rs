@@ -0,0 +1,201 @@
+//! Reducing the colours of a texture to a small palette.
+//!
+//! [`KMeans`] gives the closest fit but depends on its random centroids, so
+//! the deterministic [`MedianCut`] and [`Octree`] are usually better for
+//! per-texture palettes, and a lot faster.
+
+use image::Rgb;
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::k_means::k_means;
+
+pub trait Quantizer {
+    /// Up to `k` colours to represent `pixels` with
+    fn palette(&self, pixels: &[Rgb<u8>], k: usize) -> Vec<Rgb<u8>>;
+}
+
+/// Lloyd's k-means, see [`crate::k_means`], with the same `seed` always
+/// giving the same palette
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KMeans {
+    pub seed: u64,
+}
+
+impl Quantizer for KMeans {
+    fn palette(&self, pixels: &[Rgb<u8>], k: usize) -> Vec<Rgb<u8>> {
+        k_means(k, pixels, &mut StdRng::seed_from_u64(self.seed))
+    }
+}
+
+/// Repeatedly split the box of colours with the widest range at the median
+/// of that channel, then take the mean of each box.
+/// <https://en.wikipedia.org/wiki/Median_cut>
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MedianCut;
+
+/// The channel with the widest range in `pixels`, and that range
+fn widest_channel(pixels: &[Rgb<u8>]) -> (usize, u8) {
+    (0..3)
+        .map(|c| {
+            let min = pixels.iter().map(|px| px[c]).min().unwrap_or(0);
+            let max = pixels.iter().map(|px| px[c]).max().unwrap_or(0);
+            (c, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .expect("there are three channels")
+}
+
+/// Different boxes or branches can still average to the same colour
+fn dedup(mut palette: Vec<Rgb<u8>>) -> Vec<Rgb<u8>> {
+    palette.sort_unstable_by_key(|c| c.0);
+    palette.dedup();
+    palette
+}
+
+fn mean(pixels: &[Rgb<u8>]) -> Rgb<u8> {
+    let mut sum = [0u64; 3];
+    for px in pixels {
+        for (sum, c) in sum.iter_mut().zip(px.0) {
+            *sum += c as u64;
+        }
+    }
+    let len = pixels.len().max(1) as u64;
+    Rgb(sum.map(|sum| ((sum + len / 2) / len) as u8))
+}
+
+impl Quantizer for MedianCut {
+    fn palette(&self, pixels: &[Rgb<u8>], k: usize) -> Vec<Rgb<u8>> {
+        if pixels.is_empty() || k == 0 {
+            return Vec::new();
+        }
+
+        let mut boxes = vec![pixels.to_vec()];
+        while boxes.len() < k {
+            let Some((i, channel)) = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .map(|(i, b)| (i, widest_channel(b)))
+                .filter(|&(_, (_, range))| range > 0)
+                .max_by_key(|&(_, (_, range))| range)
+                .map(|(i, (channel, _))| (i, channel))
+            else {
+                // every box is a single colour
+                break;
+            };
+
+            let mut b = boxes.swap_remove(i);
+            b.sort_unstable_by_key(|px| px[channel]);
+            let upper = b.split_off(b.len() / 2);
+            boxes.push(b);
+            boxes.push(upper);
+        }
+
+        dedup(boxes.iter().map(|b| mean(b)).collect())
+    }
+}
+
+/// Count the colours in an octree of their bits, then merge the deepest,
+/// least used branches until only `k` leaves are left.
+/// <https://en.wikipedia.org/wiki/Octree#Color_quantization>
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Octree;
+
+const OCTREE_DEPTH: usize = 8;
+
+#[derive(Default)]
+struct OctreeNode {
+    sum: [u64; 3],
+    count: u64,
+    children: [Option<usize>; 8],
+}
+
+impl OctreeNode {
+    fn is_leaf(&self) -> bool {
+        self.children.iter().all(Option::is_none)
+    }
+}
+
+impl Quantizer for Octree {
+    fn palette(&self, pixels: &[Rgb<u8>], k: usize) -> Vec<Rgb<u8>> {
+        if pixels.is_empty() || k == 0 {
+            return Vec::new();
+        }
+
+        let mut nodes = vec![OctreeNode::default()];
+        // the branches at each depth, to reduce from the bottom up
+        let mut levels: Vec<Vec<usize>> = vec![Vec::new(); OCTREE_DEPTH];
+        for px in pixels {
+            let mut node = 0;
+            for (depth, level) in levels.iter_mut().enumerate() {
+                let bit = 7 - depth;
+                let octant = (((px[0] >> bit) & 1) << 2
+                    | ((px[1] >> bit) & 1) << 1
+                    | ((px[2] >> bit) & 1)) as usize;
+                node = match nodes[node].children[octant] {
+                    Some(child) => child,
+                    None => {
+                        if nodes[node].is_leaf() {
+                            level.push(node);
+                        }
+                        nodes.push(OctreeNode::default());
+                        let child = nodes.len() - 1;
+                        nodes[node].children[octant] = Some(child);
+                        child
+                    }
+                };
+            }
+
+            let leaf = &mut nodes[node];
+            for (sum, c) in leaf.sum.iter_mut().zip(px.0) {
+                *sum += c as u64;
+            }
+            leaf.count += 1;
+        }
+
+        let mut leaves = nodes.iter().filter(|n| n.is_leaf()).count();
+        for level in levels.iter_mut().rev() {
+            if leaves <= k {
+                break;
+            }
+
+            // children of these branches are all leaves, as deeper levels
+            // have been merged first
+            for &node in level.iter() {
+                let count: u64 = nodes[node]
+                    .children
+                    .iter()
+                    .flatten()
+                    .map(|&c| nodes[c].count)
+                    .sum();
+                nodes[node].count = count;
+            }
+            level.sort_by_key(|&node| std::cmp::Reverse(nodes[node].count));
+
+            while leaves > k {
+                let Some(node) = level.pop() else {
+                    break;
+                };
+                let children = std::mem::take(&mut nodes[node].children);
+                let mut sum = [0; 3];
+                for &child in children.iter().flatten() {
+                    for (sum, c) in sum.iter_mut().zip(nodes[child].sum) {
+                        *sum += c;
+                    }
+                    // no longer part of the tree
+                    nodes[child].count = 0;
+                    leaves -= 1;
+                }
+                nodes[node].sum = sum;
+                leaves += 1;
+            }
+        }
+
+        let palette = nodes
+            .iter()
+            .filter(|n| n.is_leaf() && n.count > 0)
+            .map(|n| Rgb(n.sum.map(|sum| ((sum + n.count / 2) / n.count) as u8)))
+            .collect();
+        dedup(palette)
+    }
+}
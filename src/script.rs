@@ -0,0 +1,183 @@
+//! Per-pixel filters written as short [Rhai](https://rhai.rs) scripts in the
+//! config:
+//!
+//! ```toml
+//! filters = [{ type = "script", script = "[b, g, r, a]" }]
+//! ```
+//!
+//! The script sees the pixel as `x`, `y`, `r`, `g`, `b` and `a` (all
+//! integers, the channels 0-255) along with the texture's `path`, and
+//! evaluates to the new pixel as `[r, g, b]` or `[r, g, b, a]`.
+//!
+//! Scripts are compiled once when the config is loaded, and those that don't
+//! use `x` or `y` are only run once per distinct colour in each texture.
+//!
+//! Only available with the `script` feature.
+
+use std::{collections::HashMap, fmt, path::Path};
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use lazy_static::lazy_static;
+use rhai::{AST, Array, Dynamic, Engine, EvalAltResult, INT, Scope};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::GenRpError, filter::Transform};
+
+lazy_static! {
+    static ref ENGINE: Engine = Engine::new();
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "ScriptConfig", into = "ScriptConfig")]
+pub struct ScriptFilter {
+    source: String,
+    ast: AST,
+    /// The script doesn't use `x` or `y`, so its result only depends on the
+    /// colour
+    per_colour: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScriptConfig {
+    script: String,
+}
+
+impl From<ScriptFilter> for ScriptConfig {
+    fn from(filter: ScriptFilter) -> Self {
+        Self {
+            script: filter.source,
+        }
+    }
+}
+
+impl TryFrom<ScriptConfig> for ScriptFilter {
+    type Error = GenRpError;
+
+    fn try_from(config: ScriptConfig) -> Result<Self, Self::Error> {
+        Self::compile(config.script)
+    }
+}
+
+impl ScriptFilter {
+    pub fn compile(source: impl Into<String>) -> Result<Self, GenRpError> {
+        let source = source.into();
+
+        // with strict variables, compiling fails if the script reads a
+        // variable that isn't in scope, which also catches typos up front
+        let mut engine = Engine::new();
+        engine.set_strict_variables(true);
+        let mut scope = Scope::new();
+        for name in ["r", "g", "b", "a"] {
+            scope.push(name, 0 as INT);
+        }
+        scope.push("path", String::new());
+        let per_colour = engine.compile_with_scope(&scope, &source).is_ok();
+
+        scope.push("x", 0 as INT).push("y", 0 as INT);
+        let ast = engine
+            .compile_with_scope(&scope, &source)
+            .map_err(|e| GenRpError::Config(format!("script `{}`: {}", source, e)))?;
+
+        Ok(Self {
+            source,
+            ast,
+            per_colour,
+        })
+    }
+
+    fn run(&self, image: &mut RgbaImage, path: &Path) -> Result<(), Box<EvalAltResult>> {
+        let mut scope = Scope::new();
+        scope.push_constant("path", path.to_string_lossy().into_owned());
+        for name in ["x", "y", "r", "g", "b", "a"] {
+            scope.push(name, 0 as INT);
+        }
+        let len = scope.len();
+
+        let mut cache = HashMap::new();
+        for (x, y, px) in image.enumerate_pixels_mut() {
+            if let Some(&cached) = cache.get(px) {
+                *px = cached;
+                continue;
+            }
+
+            let [r, g, b, a] = px.0;
+            scope
+                .set_value("x", x as INT)
+                .set_value("y", y as INT)
+                .set_value("r", r as INT)
+                .set_value("g", g as INT)
+                .set_value("b", b as INT)
+                .set_value("a", a as INT);
+            let result: Dynamic = ENGINE.eval_ast_with_scope(&mut scope, &self.ast)?;
+            // drop anything the script declared with `let`
+            scope.rewind(len);
+
+            let new = to_pixel(result, a)?;
+            if self.per_colour {
+                cache.insert(*px, new);
+            }
+            *px = new;
+        }
+
+        Ok(())
+    }
+}
+
+/// `[r, g, b]` or `[r, g, b, a]`, keeping `alpha` if it's left out
+fn to_pixel(result: Dynamic, alpha: u8) -> Result<Rgba<u8>, Box<EvalAltResult>> {
+    let type_name = result.type_name();
+    let channels = result
+        .try_cast::<Array>()
+        .ok_or_else(|| format!("expected [r, g, b] or [r, g, b, a], got {}", type_name))?;
+    if !matches!(channels.len(), 3 | 4) {
+        return Err(format!("expected 3 or 4 channels, got {}", channels.len()).into());
+    }
+
+    let mut px = [0, 0, 0, alpha];
+    for (c, value) in px.iter_mut().zip(channels) {
+        let value = match value.as_int() {
+            Ok(i) => i as f64,
+            Err(_) => value
+                .as_float()
+                .map_err(|t| format!("expected a number, got {}", t))?,
+        };
+        *c = value.round().clamp(0., 255.) as u8;
+    }
+    Ok(Rgba(px))
+}
+
+impl Transform for ScriptFilter {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        self.apply_with_path(image, Path::new(""))
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        let original = image.into_rgba8();
+        let mut image = original.clone();
+        match self.run(&mut image, path) {
+            Ok(()) => DynamicImage::ImageRgba8(image),
+            Err(e) => {
+                tracing::warn!(
+                    "Script failed on {}, leaving it unchanged: {}",
+                    path.display(),
+                    e
+                );
+                DynamicImage::ImageRgba8(original)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ScriptFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScriptFilter")
+            .field("source", &self.source)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for ScriptFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
@@ -0,0 +1,135 @@
+//! A small HTTP server for the generated packs, so a local server can point
+//! `resource-pack` in `server.properties` at it while testing
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{
+    download::sha1_file,
+    error::{GenRpError, IoContext, Result},
+};
+
+/// Serve every zip in `dir` until the process is stopped.  `GET /` lists the
+/// packs with their hashes, and `GET /<pack>.zip` sends the pack with its
+/// SHA-1 in the `ETag` and `X-Resource-Pack-Sha1` headers.
+///
+/// Hashes are recomputed whenever a zip changes, so this can be left running
+/// next to `watch`.
+pub fn serve(addr: impl ToSocketAddrs, dir: &Path) -> Result<()> {
+    let server = Server::http(addr).map_err(GenRpError::Serve)?;
+    let mut hashes = HashCache::default();
+
+    for request in server.incoming_requests() {
+        if let Err(e) = handle(request, dir, &mut hashes) {
+            tracing::error!("Error while serving request: {:?}", e);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct HashCache(HashMap<PathBuf, (SystemTime, String)>);
+
+impl HashCache {
+    fn get(&mut self, path: &Path) -> Result<String> {
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .io_context(|| format!("Reading metadata of {}", path.display()))?;
+
+        match self.0.get(path) {
+            Some((time, sha1)) if *time == modified => Ok(sha1.clone()),
+            _ => {
+                let sha1 = sha1_file(path)?;
+                self.0.insert(path.to_path_buf(), (modified, sha1.clone()));
+                Ok(sha1)
+            }
+        }
+    }
+}
+
+fn handle(request: Request, dir: &Path, hashes: &mut HashCache) -> Result<()> {
+    let url = percent_decode(request.url().split('?').next().unwrap_or_default());
+    let name = url.trim_start_matches('/');
+
+    if name.is_empty() {
+        let mut listing = String::new();
+        let mut entries = fs::read_dir(dir)
+            .io_context(|| format!("Reading {}", dir.display()))?
+            .collect::<Result<Vec<_>, _>>()
+            .io_context(|| format!("Reading {}", dir.display()))?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "zip") {
+                listing.push_str(&format!(
+                    "{} {}\n",
+                    entry.file_name().to_string_lossy(),
+                    hashes.get(&path)?
+                ));
+            }
+        }
+        return request
+            .respond(Response::from_string(listing))
+            .io_context(|| "Sending response".into());
+    }
+
+    // only serve zips directly inside of `dir`
+    let path = dir.join(name);
+    if name.contains(['/', '\\']) || !name.ends_with(".zip") || !path.is_file() {
+        return request
+            .respond(Response::from_string("Not Found").with_status_code(404))
+            .io_context(|| "Sending response".into());
+    }
+
+    let sha1 = hashes.get(&path)?;
+    let file = File::open(&path).io_context(|| format!("Opening {}", path.display()))?;
+    let header = |name: &str, value: &str| {
+        Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("header is valid")
+    };
+    let response = Response::from_file(file)
+        .with_header(header("Content-Type", "application/zip"))
+        .with_header(header("ETag", &format!("\"{}\"", sha1)))
+        .with_header(header("X-Resource-Pack-Sha1", &sha1));
+
+    request
+        .respond(response)
+        .io_context(|| format!("Sending {}", path.display()))
+}
+
+/// Decode `%XX` escapes, since pack names usually contain spaces
+fn percent_decode(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let [b, tail @ ..] = rest {
+        match (b, tail) {
+            (b'%', [hi, lo, tail @ ..]) => {
+                match std::str::from_utf8(&[*hi, *lo])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        bytes.push(byte);
+                        rest = tail;
+                    }
+                    None => {
+                        bytes.push(b'%');
+                        rest = &rest[1..];
+                    }
+                }
+            }
+            _ => {
+                bytes.push(*b);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
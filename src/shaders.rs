@@ -0,0 +1,124 @@
+//! Shader templates copied into every texture pack, so a pack can change how
+//! the world and entities are drawn as well as their textures.
+//!
+//! The templates directory is laid out like `assets/minecraft/shaders` (so
+//! `core/rendertype_solid.fsh`, `post/blur.json`, ...), and any `${NAME}` in
+//! a template is replaced with a variable worked out for each pack:
+//!
+//! - `${TINT}`: how the pack's transform changes a mid grey, as
+//!   `r, g, b` multipliers (`1.000, 1.000, 1.000` if it doesn't), so it
+//!   works as `vec3(${TINT})` in GLSL or `[${TINT}]` in JSON
+//! - `${SEED}`: see [`GenerateOptions::seed`](crate::GenerateOptions::seed)
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, Rgb, RgbImage};
+use walkdir::WalkDir;
+
+use crate::{
+    error::{IoContext, Result},
+    filter::Transform,
+};
+
+/// Where the templates go, inside of a pack
+const SHADERS_PATH: &str = "assets/minecraft/shaders";
+
+/// The grey [`Transform`]s are sampled with for `${TINT}`
+const TINT_SAMPLE: u8 = 128;
+
+/// Shader files loaded from a templates directory
+#[derive(Clone, Debug, Default)]
+pub struct ShaderTemplates {
+    /// Path in the pack and the contents of each file
+    files: Vec<(PathBuf, String)>,
+}
+
+impl ShaderTemplates {
+    /// Load every file in `dir`, which must all be text
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let mut files = Vec::new();
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                continue;
+            }
+
+            let template = fs::read_to_string(entry.path())
+                .io_context(|| format!("Reading shader {}", entry.path().display()))?;
+            let path = entry.path().strip_prefix(dir).expect("path is in dir");
+            files.push((Path::new(SHADERS_PATH).join(path), template));
+        }
+
+        Ok(Self { files })
+    }
+
+    /// Paths of the files in the pack
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| &**path)
+    }
+
+    /// Every file with the variables for `f` filled in, along with a warning
+    /// for each variable that doesn't exist (which is left as-is)
+    pub fn render(&self, f: &dyn Transform, seed: u64) -> (Vec<(&Path, String)>, Vec<String>) {
+        let vars = variables(f, seed);
+        let mut warnings = Vec::new();
+        let files =
+            self.files
+                .iter()
+                .map(|(path, template)| {
+                    let (rendered, unknown) = substitute(template, &vars);
+                    warnings.extend(unknown.into_iter().map(|name| {
+                        format!("unknown variable ${{{}}} in {}", name, path.display())
+                    }));
+                    (&**path, rendered)
+                })
+                .collect();
+
+        (files, warnings)
+    }
+}
+
+/// The variables available to templates for a pack transformed by `f`
+pub fn variables(f: &dyn Transform, seed: u64) -> BTreeMap<&'static str, String> {
+    let grey = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([TINT_SAMPLE; 3])));
+    let Rgb(tint) = *f
+        .apply_with_path(grey, Path::new("assets/minecraft/textures/block/stone.png"))
+        .to_rgb8()
+        .get_pixel(0, 0);
+    let tint = tint.map(|c| format!("{:.3}", c as f32 / TINT_SAMPLE as f32));
+
+    BTreeMap::from([("TINT", tint.join(", ")), ("SEED", seed.to_string())])
+}
+
+/// Replace each `${NAME}` in `template` with its value in `vars`, returning
+/// the result and the names that weren't in `vars`
+pub(crate) fn substitute(template: &str, vars: &BTreeMap<&str, String>) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(template.len());
+    let mut unknown = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start + 2..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let name = &rest[start + 2..start + 2 + len];
+        match vars.get(name) {
+            Some(value) => out.push_str(value),
+            None => {
+                unknown.push(name.to_string());
+                out.push_str(&rest[start..start + 3 + len]);
+            }
+        }
+        rest = &rest[start + 3 + len..];
+    }
+    out.push_str(rest);
+
+    (out, unknown)
+}
@@ -0,0 +1,276 @@
+//! Transforming the game's sounds, which aren't in the client jar but are
+//! downloaded through the asset index (see [`crate::assets`]).
+//!
+//! Every `.ogg` is decoded, run through a [`SoundTransform`] and encoded
+//! again, and `sounds.json` is copied alongside so the pack matches the
+//! version it was built for.
+//!
+//! Only available with the `sound` feature.
+
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    num::{NonZeroU8, NonZeroU32},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::Instant,
+};
+
+use serde_json::Value;
+use vorbis_rs::{VorbisDecoder, VorbisEncoderBuilder, VorbisError};
+use walkdir::WalkDir;
+
+use crate::{
+    GenerateOptions, GenerationReport, PackOutput,
+    error::{GenRpError, IoContext, Result},
+    finish_pack,
+};
+
+/// How many sounds are decoded and encoded before they're written to the
+/// pack, to keep memory use down with the music
+const BATCH_SIZE: usize = 64;
+
+/// Decoded audio, with the samples (`-1.0..=1.0`) of each channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct Audio {
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl Audio {
+    pub fn decode(path: &Path) -> Result<Self> {
+        let file = File::open(path).io_context(|| format!("Opening {}", path.display()))?;
+        let mut decoder =
+            VorbisDecoder::new(BufReader::new(file)).map_err(|e| sound_error(path, e))?;
+
+        let mut channels = vec![Vec::new(); decoder.channels().get() as usize];
+        let sample_rate = decoder.sampling_frequency().get();
+        while let Some(block) = decoder
+            .decode_audio_block()
+            .map_err(|e| sound_error(path, e))?
+        {
+            for (channel, samples) in channels.iter_mut().zip(block.samples()) {
+                channel.extend_from_slice(samples);
+            }
+        }
+
+        Ok(Self {
+            sample_rate,
+            channels,
+        })
+    }
+
+    /// Encode as Ogg Vorbis, the only format the game plays
+    pub fn encode(&self, path: &Path) -> Result<Vec<u8>> {
+        let sample_rate = NonZeroU32::new(self.sample_rate)
+            .ok_or_else(|| GenRpError::Sound(format!("{} has no sample rate", path.display())))?;
+        let channels = u8::try_from(self.channels.len())
+            .ok()
+            .and_then(NonZeroU8::new)
+            .ok_or_else(|| {
+                GenRpError::Sound(format!(
+                    "{} has {} channels",
+                    path.display(),
+                    self.channels.len()
+                ))
+            })?;
+
+        encode_ogg(sample_rate, channels, &self.channels).map_err(|e| sound_error(path, e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn encode_ogg(
+    sample_rate: NonZeroU32,
+    channels: NonZeroU8,
+    samples: &[Vec<f32>],
+) -> Result<Vec<u8>, VorbisError> {
+    let mut ogg = Vec::new();
+    let mut encoder = VorbisEncoderBuilder::new(sample_rate, channels, &mut ogg)?.build()?;
+    encoder.encode_audio_block(samples)?;
+    encoder.finish()?;
+    Ok(ogg)
+}
+
+fn sound_error(path: &Path, e: VorbisError) -> GenRpError {
+    GenRpError::Sound(format!("{}: {}", path.display(), e))
+}
+
+pub trait SoundTransform: Sync {
+    fn apply(&self, audio: Audio) -> Audio;
+}
+
+impl<F> SoundTransform for F
+where
+    F: Fn(Audio) -> Audio + Sync,
+{
+    fn apply(&self, audio: Audio) -> Audio {
+        self(audio)
+    }
+}
+
+/// Play everything backwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reverse;
+
+impl SoundTransform for Reverse {
+    fn apply(&self, mut audio: Audio) -> Audio {
+        for channel in &mut audio.channels {
+            channel.reverse();
+        }
+        audio
+    }
+}
+
+/// Speed up (or slow down, below 1) by `factor`, which raises (or lowers) the
+/// pitch with it like a record played at the wrong speed.  1.25 is roughly
+/// "nightcore".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Speed {
+    pub factor: f32,
+}
+
+impl SoundTransform for Speed {
+    fn apply(&self, mut audio: Audio) -> Audio {
+        if self.factor <= 0. {
+            return audio;
+        }
+
+        let len = (audio.len() as f32 / self.factor).round() as usize;
+        for channel in &mut audio.channels {
+            // linear interpolation between the nearest samples
+            let resampled = (0..len)
+                .map(|i| {
+                    let pos = i as f32 * self.factor;
+                    let j = pos as usize;
+                    let a = channel.get(j).copied().unwrap_or(0.);
+                    let b = channel.get(j + 1).copied().unwrap_or(a);
+                    a + (b - a) * pos.fract()
+                })
+                .collect();
+            *channel = resampled;
+        }
+        audio
+    }
+}
+
+/// Crunchy old console audio: samples are rounded to `bits` of resolution,
+/// and each one is held for `downsample` samples to lower the sample rate
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BitCrush {
+    pub bits: u32,
+    pub downsample: usize,
+}
+
+impl SoundTransform for BitCrush {
+    fn apply(&self, mut audio: Audio) -> Audio {
+        // levels either side of 0
+        let levels = (1u32 << self.bits.clamp(1, 24).saturating_sub(1)) as f32;
+        let downsample = self.downsample.max(1);
+        for channel in &mut audio.channels {
+            for chunk in channel.chunks_mut(downsample) {
+                let held = (chunk[0] * levels).round() / levels;
+                chunk.fill(held);
+            }
+        }
+        audio
+    }
+}
+
+/// Where the file at `path` goes in the pack
+fn path_in_pack(sounds_dir: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(sounds_dir)
+        .expect("path is in sounds_dir")
+        .to_path_buf()
+}
+
+fn transform_sound(path: &Path, f: &dyn SoundTransform) -> Result<Vec<u8>> {
+    let audio = Audio::decode(path)?;
+    f.apply(audio).encode(path)
+}
+
+/// Write a pack with every sound in `sounds_dir` (see
+/// [`crate::assets::extract_sounds`]) transformed by `f`, in
+/// `options.format`.  Sounds that can't be decoded are left out with a
+/// warning.
+pub fn generate_sound_pack(
+    pack_name: &str,
+    description: &str,
+    f: &dyn SoundTransform,
+    sounds_dir: &Path,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut sink = options.sink(pack_name)?;
+    let mut report = GenerationReport {
+        seed: options.seed,
+        ..Default::default()
+    };
+
+    let mut sounds = Vec::new();
+    for entry in WalkDir::new(sounds_dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "ogg") {
+            sounds.push(path.to_path_buf());
+        } else {
+            let data = fs::read(path).io_context(|| format!("Reading {}", path.display()))?;
+            sink.add_file(&path_in_pack(sounds_dir, path), &data)?;
+            report.files_written += 1;
+            report.bytes_written += data.len() as u64;
+        }
+    }
+
+    let threads = options.threads();
+    for batch in sounds.chunks(BATCH_SIZE) {
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<_> = thread::scope(|s| {
+            let workers: Vec<_> = (0..threads.min(batch.len()))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(path) = batch.get(i) else {
+                                return done;
+                            };
+                            done.push((i, transform_sound(path, f)));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("sound thread panicked"))
+                .collect()
+        });
+        // write in a stable order, so reproducible packs stay reproducible
+        results.sort_by_key(|&(i, _)| i);
+
+        for (i, result) in results {
+            let path = path_in_pack(sounds_dir, &batch[i]);
+            match result {
+                Ok(data) => {
+                    sink.add_file(&path, &data)?;
+                    report.files_written += 1;
+                    report.bytes_written += data.len() as u64;
+                }
+                Err(e) => options.on_error.handle(&mut report, &path, e)?,
+            }
+        }
+    }
+
+    finish_pack(sink, description, Value::Null, options, report, start)
+}
@@ -0,0 +1,107 @@
+//! How long generating a pack spent in each of its stages, to tell whether
+//! it's a filter or encoding the PNGs that's slow, see
+//! [`GenerationReport::timings`](crate::GenerationReport::timings)
+
+use std::{
+    fmt::Write,
+    ops::AddAssign,
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, Serializer};
+
+/// Time spent in each stage of generating a pack, serialized in
+/// milliseconds.  Textures transformed on a pack's spare threads (see
+/// [`GenerateOptions::jobs`](crate::GenerateOptions::jobs)) add up the time
+/// on each, so the stages can add up to more than the pack took.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StageTimings {
+    /// Reading and decoding the pack's textures.  Each texture is decoded
+    /// once for every pack generated together, so this is counted in each.
+    #[serde(serialize_with = "millis")]
+    pub decode: Duration,
+    /// Running the pack's transform, on the GPU or not
+    #[serde(serialize_with = "millis")]
+    pub transform: Duration,
+    /// Encoding PNGs, along with bleeding alpha and making PBR maps
+    #[serde(serialize_with = "millis")]
+    pub encode: Duration,
+    /// Adding files to the pack and finishing the archive
+    #[serde(serialize_with = "millis")]
+    pub write: Duration,
+}
+
+fn millis<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64() * 1000.)
+}
+
+impl StageTimings {
+    pub fn total(&self) -> Duration {
+        self.decode + self.transform + self.encode + self.write
+    }
+}
+
+impl AddAssign for StageTimings {
+    fn add_assign(&mut self, other: Self) {
+        self.decode += other.decode;
+        self.transform += other.transform;
+        self.encode += other.encode;
+        self.write += other.write;
+    }
+}
+
+/// Run `f`, adding how long it took to `stage`
+pub(crate) fn time<T>(stage: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let out = f();
+    *stage += start.elapsed();
+    out
+}
+
+/// A table of how long each pack spent in each stage, with the share of the
+/// pack's time each took, and a total row for more than one pack
+pub fn table<'a>(packs: impl IntoIterator<Item = (&'a str, StageTimings)>) -> String {
+    let mut rows: Vec<_> = packs.into_iter().collect();
+    if rows.len() > 1 {
+        let mut total = StageTimings::default();
+        for (_, timings) in &rows {
+            total += *timings;
+        }
+        rows.push(("total", total));
+    }
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("pack".len());
+
+    let mut out = format!(
+        "{:<width$} {:>16} {:>16} {:>16} {:>16} {:>9}\n",
+        "pack", "decode", "transform", "encode", "write", "total"
+    );
+    for (name, timings) in rows {
+        let total = timings.total();
+        let stage = |duration: Duration| {
+            let share = if total.is_zero() {
+                0.
+            } else {
+                duration.as_secs_f64() / total.as_secs_f64() * 100.
+            };
+            format!("{:.1?} ({:.0}%)", duration, share)
+        };
+        let total = format!("{:.1?}", total);
+        writeln!(
+            out,
+            "{:<width$} {:>16} {:>16} {:>16} {:>16} {:>9}",
+            name,
+            stage(timings.decode),
+            stage(timings.transform),
+            stage(timings.encode),
+            stage(timings.write),
+            total,
+        )
+        .expect("writing to a String");
+    }
+    out
+}
@@ -0,0 +1,161 @@
+//! Pixel-art aware upscaling, which keeps edges crisp and rounds off
+//! diagonals instead of blurring everything like bilinear would
+// https://www.scale2x.it/algorithm
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::filter::Transform;
+
+/// Double the size of every texture with Scale2x (EPX)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scale2x {
+    /// Blend the new corner pixels with the original instead of copying the
+    /// neighbour, which anti-aliases diagonals like hq2x does
+    #[serde(default)]
+    pub smooth: bool,
+}
+
+impl Transform for Scale2x {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        scale2x(&image.into_rgba8(), self.smooth).into()
+    }
+}
+
+/// Whether two pixels should be treated as the same colour, using the same
+/// YUV thresholds as hqx so that slight noise in a texture doesn't stop an
+/// edge from being detected
+fn similar(a: Rgba<u8>, b: Rgba<u8>) -> bool {
+    fn yuv(px: Rgba<u8>) -> [f32; 3] {
+        let [r, g, b] = [px[0] as f32, px[1] as f32, px[2] as f32];
+        [
+            0.299 * r + 0.587 * g + 0.114 * b,
+            -0.169 * r - 0.331 * g + 0.5 * b + 128.,
+            0.5 * r - 0.419 * g - 0.081 * b + 128.,
+        ]
+    }
+
+    if a == b {
+        return true;
+    }
+    if a[3].abs_diff(b[3]) > 32 {
+        return false;
+    }
+    // fully transparent pixels can have any colour
+    if a[3] == 0 && b[3] == 0 {
+        return true;
+    }
+
+    let (ya, yb) = (yuv(a), yuv(b));
+    (ya[0] - yb[0]).abs() <= 48. && (ya[1] - yb[1]).abs() <= 7. && (ya[2] - yb[2]).abs() <= 6.
+}
+
+/// `3:1` mix of `a` and `b`
+fn blend(a: Rgba<u8>, b: Rgba<u8>) -> Rgba<u8> {
+    Rgba(std::array::from_fn(|i| {
+        ((a[i] as u16 * 3 + b[i] as u16 + 2) / 4) as u8
+    }))
+}
+
+pub fn scale2x(image: &RgbaImage, smooth: bool) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let get = |x: i64, y: i64| {
+        *image.get_pixel(
+            x.clamp(0, width as i64 - 1) as u32,
+            y.clamp(0, height as i64 - 1) as u32,
+        )
+    };
+    let pick = |neighbour: Rgba<u8>, centre: Rgba<u8>, matches: bool| match (matches, smooth) {
+        (false, _) => centre,
+        (true, false) => neighbour,
+        (true, true) => blend(neighbour, centre),
+    };
+
+    let mut out = RgbaImage::new(width * 2, height * 2);
+    for y in 0..height {
+        for x in 0..width {
+            let (xi, yi) = (x as i64, y as i64);
+            //   B
+            // D E F
+            //   H
+            let b = get(xi, yi - 1);
+            let d = get(xi - 1, yi);
+            let e = get(xi, yi);
+            let f = get(xi + 1, yi);
+            let h = get(xi, yi + 1);
+
+            let (e0, e1, e2, e3) = if !similar(b, h) && !similar(d, f) {
+                (
+                    pick(d, e, similar(d, b)),
+                    pick(f, e, similar(b, f)),
+                    pick(d, e, similar(d, h)),
+                    pick(f, e, similar(h, f)),
+                )
+            } else {
+                (e, e, e, e)
+            };
+
+            out.put_pixel(x * 2, y * 2, e0);
+            out.put_pixel(x * 2 + 1, y * 2, e1);
+            out.put_pixel(x * 2, y * 2 + 1, e2);
+            out.put_pixel(x * 2 + 1, y * 2 + 1, e3);
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image from rows of `K` (black), `W` (white), and the greys `D`
+    /// (dark) and `L` (light) that smoothing mixes them into
+    fn image(rows: &[&str]) -> RgbaImage {
+        RgbaImage::from_fn(rows[0].len() as u32, rows.len() as u32, |x, y| {
+            let grey = match rows[y as usize].as_bytes()[x as usize] {
+                b'K' => 0,
+                b'D' => 64,
+                b'L' => 191,
+                b'W' => 255,
+                c => panic!("unknown pixel {}", c as char),
+            };
+            Rgba([grey, grey, grey, 255])
+        })
+    }
+
+    #[test]
+    fn flat_block_stays_flat() {
+        let flat = image(&["WW", "WW"]);
+        for smooth in [false, true] {
+            assert_eq!(scale2x(&flat, smooth), image(&["WWWW"; 4]));
+        }
+    }
+
+    #[test]
+    fn single_pixel_becomes_a_block() {
+        let dot = image(&["WWW", "WKW", "WWW"]);
+        let expected = image(&["WWWWWW", "WWWWWW", "WWKKWW", "WWKKWW", "WWWWWW", "WWWWWW"]);
+        for smooth in [false, true] {
+            assert_eq!(scale2x(&dot, smooth), expected);
+        }
+    }
+
+    #[test]
+    fn diagonal_edge_is_rounded() {
+        let stairs = image(&["KKW", "KWW", "WWW"]);
+        assert_eq!(
+            scale2x(&stairs, false),
+            image(&["KKKKWW", "KKKWWW", "KKKWWW", "KWWWWW", "WWWWWW", "WWWWWW",])
+        );
+    }
+
+    #[test]
+    fn smooth_diagonal_edge_is_blended() {
+        let stairs = image(&["KKW", "KWW", "WWW"]);
+        assert_eq!(
+            scale2x(&stairs, true),
+            image(&["KKKKWW", "KKKLWW", "KKDWWW", "KLWWWW", "WWWWWW", "WWWWWW",])
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! "Natural variation" packs, which add slightly different copies of block
+//! textures and let the game pick one at random for each block, like it
+//! already does with rotations of stone and dirt.
+
+use std::{collections::BTreeMap, path::Path, time::Instant};
+
+use image::ImageReader;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde_json::json;
+
+use crate::{
+    GenerateOptions, GenerationReport, PackOutput,
+    error::{GenRpError, IoContext, Result},
+    filter::{Grain, HueRotate, Transform, path_seed},
+    finish_pack,
+    models::BlockModels,
+    optimize::encode_png,
+};
+
+/// How the variants of each texture are made
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VariantOptions {
+    /// Variants of each texture, on top of the original
+    pub count: usize,
+    /// Each variant's hue is shifted by up to this many degrees either way
+    pub hue_jitter: f32,
+    /// Each pixel's brightness is shifted by up to this either way, from 0
+    /// to 1
+    pub noise: f32,
+}
+
+impl Default for VariantOptions {
+    fn default() -> Self {
+        Self {
+            count: 3,
+            hue_jitter: 6.,
+            noise: 0.04,
+        }
+    }
+}
+
+/// Textures that look natural with some variation, for when none are picked
+pub const DEFAULT_TEXTURES: &[&str] = &[
+    "block/stone",
+    "block/cobblestone",
+    "block/dirt",
+    "block/gravel",
+    "block/sand",
+    "block/andesite",
+    "block/diorite",
+    "block/granite",
+    "block/deepslate",
+    "block/netherrack",
+    "block/end_stone",
+    "block/oak_planks",
+];
+
+/// Where a texture id like `block/stone` is in a pack
+fn texture_path(texture: &str) -> String {
+    format!(
+        "assets/minecraft/textures/{}.png",
+        texture.strip_prefix("minecraft:").unwrap_or(texture)
+    )
+}
+
+/// Write a pack with `variant.count` variants of each of `textures` (ids
+/// like `block/stone`) and the blockstates and models that pick between
+/// them.  Animated textures are skipped, as each variant would need its own
+/// animation.
+pub fn generate_variant_pack(
+    pack_name: &str,
+    description: &str,
+    textures: &[String],
+    models: &BlockModels,
+    variant: &VariantOptions,
+    options: &GenerateOptions,
+) -> Result<PackOutput> {
+    let start = Instant::now();
+    let mut sink = options.sink(pack_name)?;
+    let mut report = GenerationReport {
+        seed: options.seed,
+        ..Default::default()
+    };
+
+    let mut variants = BTreeMap::new();
+    for texture in textures {
+        let in_pack = texture_path(texture);
+        let path = options.textures_dir.join(&in_pack);
+        if !path.exists() {
+            report
+                .warnings
+                .push(format!("{} doesn't exist, skipping it", texture));
+            continue;
+        }
+        if path.with_extension("png.mcmeta").exists() {
+            report
+                .warnings
+                .push(format!("{} is animated, skipping it", texture));
+            continue;
+        }
+
+        let image = ImageReader::open(&path)
+            .io_context(|| format!("Opening {}", path.display()))?
+            .decode()
+            .map_err(|source| GenRpError::Decode {
+                path: path.clone(),
+                source,
+            })?;
+
+        let mut rng = StdRng::seed_from_u64(path_seed(Path::new(&in_pack), options.seed));
+        let mut ids = Vec::with_capacity(variant.count);
+        for i in 1..=variant.count {
+            let hue = HueRotate {
+                degrees: rng.random_range(-variant.hue_jitter..=variant.hue_jitter),
+            };
+            let grain = Grain {
+                amount: variant.noise,
+                seed: rng.random(),
+            };
+            let varied = grain.apply(hue.apply(image.clone()));
+
+            let id = format!("{}_variant_{}", texture, i);
+            let data = encode_png(&varied, options.optimize)?;
+            sink.add_file(Path::new(&texture_path(&id)), &data)?;
+            report.files_written += 1;
+            report.bytes_written += data.len() as u64;
+            ids.push(id);
+        }
+        variants.insert(texture.clone(), ids);
+    }
+
+    let files = models.variant_files(&variants)?;
+    for texture in variants.keys() {
+        if models.blocks_using(&[texture]).is_empty() {
+            report.warnings.push(format!(
+                "no block uses {}, its variants won't show",
+                texture
+            ));
+        }
+    }
+    for (path, data) in files {
+        sink.add_file(&path, &data)?;
+        report.files_written += 1;
+        report.bytes_written += data.len() as u64;
+    }
+
+    let transform = json!({
+        "variants": textures,
+        "count": variant.count,
+        "hue_jitter": variant.hue_jitter,
+        "noise": variant.noise,
+    });
+    finish_pack(sink, description, transform, options, report, start)
+}
@@ -0,0 +1,123 @@
+//! Filters written in any language that compiles to WASM, loaded at runtime
+//! with wasmtime so they can be used from a config without recompiling.
+//!
+//! A filter module exports its `memory` and two functions:
+//!
+//! - `alloc(len: i32) -> i32`, returning a pointer to `len` free bytes
+//! - `transform(width: i32, height: i32, ptr: i32) -> i32`, given the texture
+//!   as `width * height * 4` bytes of RGBA at `ptr`, returning a pointer to
+//!   the transformed texture of the same size (which may just be `ptr`)
+//!
+//! Each texture gets a fresh instance, so modules never need to free
+//! anything.
+//!
+//! Only available with the `wasm` feature.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use image::DynamicImage;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use wasmtime::{Engine, Instance, Module, Store};
+
+use crate::{
+    error::{GenRpError, Result},
+    filter::Transform,
+};
+
+lazy_static! {
+    static ref ENGINE: Engine = Engine::default();
+}
+
+/// ```toml
+/// filters = [{ type = "wasm", path = "filters/sepia.wasm" }]
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WasmFilter {
+    /// The `.wasm` or `.wat` module, relative to the config file
+    pub path: PathBuf,
+    #[serde(skip)]
+    module: Option<Module>,
+}
+
+impl WasmFilter {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let module = Module::from_file(&ENGINE, &path).map_err(|source| GenRpError::Wasm {
+            path: path.clone(),
+            source: source.into(),
+        })?;
+        Ok(Self {
+            path,
+            module: Some(module),
+        })
+    }
+
+    /// Compile the module, with `path` relative to `base`
+    pub(crate) fn resolve(&mut self, base: &Path) -> Result<()> {
+        *self = Self::load(base.join(&self.path))?;
+        Ok(())
+    }
+
+    fn run(
+        &self,
+        module: &Module,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> wasmtime::Result<Vec<u8>> {
+        let mut store = Store::new(&ENGINE, ());
+        let instance = Instance::new(&mut store, module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("module doesn't export `memory`"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let transform = instance.get_typed_func::<(i32, i32, i32), i32>(&mut store, "transform")?;
+
+        let ptr = alloc.call(&mut store, i32::try_from(rgba.len())?)?;
+        memory.write(&mut store, ptr as u32 as usize, rgba)?;
+        let out = transform.call(&mut store, (width as i32, height as i32, ptr))?;
+
+        let mut buf = vec![0; rgba.len()];
+        memory.read(&store, out as u32 as usize, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl Transform for WasmFilter {
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        let module = self
+            .module
+            .as_ref()
+            .expect("WASM filters are compiled when they're loaded");
+        let mut image = image.into_rgba8();
+
+        match self.run(module, image.width(), image.height(), &image) {
+            Ok(out) => image.copy_from_slice(&out),
+            Err(e) => tracing::warn!(
+                "WASM filter {} failed, leaving texture unchanged: {:#}",
+                self.path.display(),
+                e
+            ),
+        }
+
+        DynamicImage::ImageRgba8(image)
+    }
+}
+
+impl fmt::Debug for WasmFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmFilter")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for WasmFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
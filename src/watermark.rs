@@ -0,0 +1,206 @@
+//! Stamping a small image or some text into a corner of a few textures, like
+//! the pack icon and the title screen, to brand the packs made from a config:
+//!
+//! ```toml
+//! filters = [
+//!     { type = "watermark", text = "gen-rp", targets = ["pack.png", "gui/title"] },
+//!     { type = "watermark", image = "logo.png", corner = "top_left" },
+//! ]
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, GenericImageView, Rgb, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bitmap_font::render_text,
+    blend::{BlendMode, blend_image},
+    error::{GenRpError, Result},
+    filter::{HexColour, Transform},
+};
+
+/// Where a [`Watermark`] goes on a texture
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// Stamp `image`, or else `text` in the [bitmap font](crate::bitmap_font),
+/// into a corner of each of `targets`.  The other textures are left alone.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Watermark {
+    /// The image to stamp, relative to the config file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default = "default_colour")]
+    pub colour: HexColour,
+    /// How many pixels across each pixel of the text is
+    #[serde(default = "default_scale")]
+    pub scale: u32,
+    #[serde(default)]
+    pub corner: Corner,
+    /// Pixels between the stamp and the edges of the texture
+    #[serde(default = "default_margin")]
+    pub margin: u32,
+    #[serde(default)]
+    pub mode: BlendMode,
+    /// How much of the blended stamp to use, from 0 to 1
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    /// The textures to stamp, as paths in the pack like `pack.png` or under
+    /// `assets/minecraft/textures` like `gui/title/minecraft`, with or
+    /// without `.png`.  A directory stamps everything in it.
+    #[serde(default = "default_targets")]
+    pub targets: Vec<String>,
+    /// `image`, once it's loaded
+    #[serde(skip)]
+    stamp: Option<RgbaImage>,
+}
+
+fn default_colour() -> HexColour {
+    HexColour(Rgb([255, 255, 255]))
+}
+
+fn default_scale() -> u32 {
+    1
+}
+
+fn default_margin() -> u32 {
+    1
+}
+
+fn default_opacity() -> f32 {
+    1.
+}
+
+fn default_targets() -> Vec<String> {
+    vec!["pack.png".into()]
+}
+
+impl Watermark {
+    /// Stamp `text` onto the pack icon, in white in the bottom right
+    pub fn with_text(text: impl Into<String>) -> Self {
+        Self {
+            image: None,
+            text: Some(text.into()),
+            colour: default_colour(),
+            scale: default_scale(),
+            corner: Corner::default(),
+            margin: default_margin(),
+            mode: BlendMode::default(),
+            opacity: default_opacity(),
+            targets: default_targets(),
+            stamp: None,
+        }
+    }
+
+    /// Stamp the image at `path` onto the pack icon, in the bottom right
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let mut watermark = Self {
+            image: Some(path.into()),
+            text: None,
+            ..Self::with_text("")
+        };
+        watermark.resolve(Path::new(""))?;
+        Ok(watermark)
+    }
+
+    /// Load `image`, with its path relative to `base`, and check that there's
+    /// something to stamp
+    pub(crate) fn resolve(&mut self, base: &Path) -> Result<()> {
+        let Some(path) = &self.image else {
+            return match self.text {
+                Some(_) => Ok(()),
+                None => Err(GenRpError::Config(
+                    "a watermark needs either an image or text".into(),
+                )),
+            };
+        };
+
+        let path = base.join(path);
+        let stamp = image::open(&path).map_err(|source| GenRpError::Decode {
+            path: path.clone(),
+            source,
+        })?;
+        self.stamp = Some(stamp.into_rgba8());
+        self.image = Some(path);
+        Ok(())
+    }
+
+    /// Whether the texture at `path` (in the pack) gets stamped
+    pub fn stamps(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let in_textures = path.strip_prefix("assets/minecraft/textures/");
+        let without_png = |p: &str| p.strip_suffix(".png").map(str::to_owned);
+        self.targets.iter().any(|target| {
+            [Some(path.as_str()), in_textures]
+                .into_iter()
+                .flatten()
+                .any(|p| {
+                    p == target
+                        || without_png(p).as_deref() == Some(target.as_str())
+                        || p.starts_with(&format!("{}/", target.trim_end_matches('/')))
+                })
+        })
+    }
+
+    /// Where the top left of a stamp of this size goes on a texture of
+    /// that size
+    fn position(&self, (width, height): (u32, u32), (sw, sh): (u32, u32)) -> (i64, i64) {
+        let left = self.margin as i64;
+        let top = self.margin as i64;
+        let right = width as i64 - sw as i64 - self.margin as i64;
+        let bottom = height as i64 - sh as i64 - self.margin as i64;
+        match self.corner {
+            Corner::TopLeft => (left, top),
+            Corner::TopRight => (right, top),
+            Corner::BottomLeft => (left, bottom),
+            Corner::BottomRight => (right, bottom),
+        }
+    }
+}
+
+impl Transform for Watermark {
+    /// Nothing, as only some textures are stamped, see
+    /// [`Transform::apply_with_path`]
+    fn apply(&self, image: DynamicImage) -> DynamicImage {
+        image
+    }
+
+    fn apply_with_path(&self, image: DynamicImage, path: &Path) -> DynamicImage {
+        if !self.stamps(path) {
+            return image;
+        }
+
+        let rendered;
+        let stamp = match (&self.stamp, &self.text) {
+            (Some(stamp), _) => stamp,
+            (None, Some(text)) => {
+                let Rgb([r, g, b]) = self.colour.0;
+                rendered = render_text(text, Rgba([r, g, b, 255]), self.scale);
+                &rendered
+            }
+            (None, None) => return image,
+        };
+
+        let (x, y) = self.position(image.dimensions(), stamp.dimensions());
+        let mut image = image.into_rgba8();
+        blend_image(&mut image, stamp, x, y, self.mode, self.opacity);
+        image.into()
+    }
+
+    /// Nothing to do on the GPU for the textures that aren't stamped, and
+    /// the CPU for the ones that are
+    #[cfg(feature = "gpu")]
+    fn wgsl(&self, path: &Path) -> Option<String> {
+        (!self.stamps(path)).then(String::new)
+    }
+}
@@ -0,0 +1,106 @@
+//! Blend modes against values worked out by hand from the formulas in the
+//! W3C compositing spec (with Rec. 601 luma, as the blend module uses).
+
+use gen_rp_rs::blend::{BlendMode, blend_image};
+use image::{Rgba, RgbaImage};
+
+fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+    for (a, e) in actual.into_iter().zip(expected) {
+        assert!(
+            (a - e).abs() < 1e-4,
+            "got {:?}, expected {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+/// Each channel of `top` blended onto the same channel of `base`
+fn channels(mode: BlendMode, base: [f32; 3], top: [f32; 3]) -> [f32; 3] {
+    mode.apply(base, top)
+}
+
+#[test]
+fn multiply() {
+    assert_close(
+        channels(BlendMode::Multiply, [0.5, 1., 0.], [0.5, 0.25, 1.]),
+        [0.25, 0.25, 0.],
+    );
+}
+
+#[test]
+fn screen() {
+    assert_close(
+        channels(BlendMode::Screen, [0.5, 1., 0.], [0.5, 0.25, 0.2]),
+        [0.75, 1., 0.2],
+    );
+}
+
+#[test]
+fn overlay() {
+    // multiplies below half and screens above it, both doubled
+    assert_close(
+        channels(BlendMode::Overlay, [0.25, 0.75, 0.5], [0.5, 0.5, 1.]),
+        [0.25, 0.75, 1.],
+    );
+}
+
+#[test]
+fn soft_light() {
+    assert_close(
+        channels(BlendMode::SoftLight, [0.5, 0.25, 0.64], [0.25, 0.75, 0.75]),
+        [0.375, 0.375, 0.72],
+    );
+}
+
+#[test]
+fn colour_keeps_luma() {
+    // pure red at the luma of mid grey doesn't fit, so it's desaturated
+    // until it does
+    let g = 0.5 - 0.5 * 0.299 / 0.701;
+    assert_close(BlendMode::Color.apply([0.5; 3], [1., 0., 0.]), [1., g, g]);
+    // grey stays grey
+    assert_close(BlendMode::Color.apply([0.2; 3], [0.9; 3]), [0.2; 3]);
+}
+
+#[test]
+fn opacity_mixes_with_base() {
+    assert_close(
+        BlendMode::Normal.blend([0.; 3], [1., 0.5, 0.], 0.5),
+        [0.5, 0.25, 0.],
+    );
+}
+
+#[test]
+fn pixels_use_alpha() {
+    let top = Rgba([200, 100, 50, 255]);
+    // nothing to blend with under a transparent pixel
+    assert_eq!(
+        BlendMode::Multiply.blend_pixel(Rgba([0, 0, 0, 0]), top, 1.),
+        top
+    );
+    // a transparent pixel on top changes nothing
+    let base = Rgba([10, 20, 30, 255]);
+    assert_eq!(
+        BlendMode::Screen.blend_pixel(base, Rgba([255, 255, 255, 0]), 1.),
+        base
+    );
+    assert_eq!(
+        BlendMode::Normal.blend_pixel(base, Rgba([210, 220, 230, 128]), 1.),
+        Rgba([110, 120, 130, 255])
+    );
+}
+
+#[test]
+fn images_are_cut_off() {
+    let mut base = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]));
+    let top = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255]));
+    blend_image(&mut base, &top, 3, -1, BlendMode::Normal, 1.);
+
+    let white: Vec<_> = base
+        .enumerate_pixels()
+        .filter(|(_, _, px)| px[0] == 255)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert_eq!(white, [(3, 0)]);
+}
@@ -0,0 +1,141 @@
+//! Runs every built-in pack over the images in `tests/fixtures` and compares
+//! them to the images in `tests/golden`, so that changes to the filters can't
+//! change how packs look without anyone noticing.
+//!
+//! After a change that is meant to change how a pack looks, write the golden
+//! images again with `UPDATE_GOLDEN=1 cargo test --test golden` and commit
+//! them.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use gen_rp_rs::builtin::PACKS;
+use image::RgbaImage;
+
+/// Most a channel of a pixel may be off by, for float differences between
+/// platforms
+const TOLERANCE: u8 = 2;
+
+fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// `Hue Shift` -> `hue_shift`
+fn slug(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut fixtures: Vec<_> = fs::read_dir(tests_dir().join("fixtures"))
+        .expect("reading tests/fixtures")
+        .map(|entry| entry.expect("reading tests/fixtures").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+/// Why `actual` doesn't match `expected`, if it doesn't
+fn compare(actual: &RgbaImage, expected: &RgbaImage) -> Option<String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Some(format!(
+            "size {:?}, expected {:?}",
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+
+    let mut different = actual
+        .enumerate_pixels()
+        .zip(expected.pixels())
+        .filter(|((_, _, a), b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > TOLERANCE));
+    let ((x, y, px), expected_px) = different.next()?;
+    Some(format!(
+        "{} pixels differ, first at ({}, {}): {:?}, expected {:?}",
+        different.count() + 1,
+        x,
+        y,
+        px.0,
+        expected_px.0
+    ))
+}
+
+#[test]
+fn builtin_packs_match_golden() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no fixtures in tests/fixtures");
+
+    let mut failures = Vec::new();
+    for fixture in &fixtures {
+        let image = image::open(fixture).expect("opening fixture");
+        let file_name = fixture.file_name().expect("fixture has a name");
+
+        for pack in PACKS {
+            let actual = (pack.func)(image.clone()).into_rgba8();
+            let golden = tests_dir()
+                .join("golden")
+                .join(slug(pack.name))
+                .join(file_name);
+
+            if update {
+                fs::create_dir_all(golden.parent().expect("golden has a parent"))
+                    .expect("creating golden directory");
+                actual.save(&golden).expect("writing golden image");
+                continue;
+            }
+
+            let Ok(expected) = image::open(&golden) else {
+                failures.push(format!(
+                    "{}: missing {} (run with UPDATE_GOLDEN=1 to write it)",
+                    pack.name,
+                    golden.display()
+                ));
+                continue;
+            };
+            if let Some(reason) = compare(&actual, &expected.into_rgba8()) {
+                failures.push(format!(
+                    "{} on {}: {}",
+                    pack.name,
+                    file_name.to_string_lossy(),
+                    reason
+                ));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} images don't match:\n{}",
+        failures.len(),
+        fixtures.len() * PACKS.len(),
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn golden_images_belong_to_a_pack() {
+    let packs: Vec<_> = PACKS.iter().map(|pack| slug(pack.name)).collect();
+    let Ok(entries) = fs::read_dir(tests_dir().join("golden")) else {
+        return;
+    };
+    for entry in entries {
+        let name = entry.expect("reading tests/golden").file_name();
+        let name = name.to_string_lossy();
+        assert!(
+            packs.iter().any(|pack| *pack == name),
+            "tests/golden/{} isn't for any built-in pack, remove it",
+            name
+        );
+    }
+}